@@ -0,0 +1,104 @@
+use std::time::{Duration, Instant};
+
+use bobsics_gui::{widgets::UniversalBrush, BobsicsHeadlessApp};
+use bobsics_render::{Color, Fill, Layer, Path, Quad, RendererConfig, Shadow, Stroke};
+
+const WIDTH: u32 = 1280;
+const HEIGHT: u32 = 720;
+const WARMUP_FRAMES: u32 = 5;
+const FRAMES: u32 = 50;
+
+// A list of rows like a settings page, each switching between brushes a few times.
+// Before all brushes shared render passes, every switch started a new pass.
+fn queue_scene(brush: &mut UniversalBrush, rows: usize) {
+    for row in 0..rows {
+        let y = (row % 24) as f32 * 30.0;
+        let x = (row / 24) as f32 * 320.0;
+
+        // Every fourth row is drawn into a layer, like a fading list item
+        let layered = row % 4 == 0;
+        if layered {
+            brush.push_layer(Layer::new().with_opacity(0.8));
+        }
+        brush
+            .queue_shadow_raw(Shadow {
+                top_left: [x + 4.0, y + 4.0],
+                bottom_right: [x + 300.0, y + 28.0],
+                color: [0.0, 0.0, 0.0, 0.3],
+                ..Default::default()
+            })
+            .unwrap();
+        brush
+            .queue_quad_raw(Quad {
+                top_left: [x + 2.0, y + 2.0],
+                bottom_right: [x + 300.0, y + 26.0],
+                color: Color::from_hex(0x2c2c44).into(),
+                border_radius: [6.0; 4],
+                ..Default::default()
+            })
+            .unwrap();
+        brush
+            .queue_text_raw(
+                &wgpu_glyph::Section::default()
+                    .add_text(
+                        wgpu_glyph::Text::new("Setting")
+                            .with_scale(18.0)
+                            .with_color([1.0, 1.0, 1.0, 1.0]),
+                    )
+                    .with_screen_position((x + 10.0, y + 6.0)),
+            )
+            .unwrap();
+        brush
+            .queue_fill_path_raw(
+                &Path::circle([x + 280.0, y + 14.0], 8.0),
+                &Fill::new(Color::from_hex(0x3fa7ff)),
+            )
+            .unwrap();
+        brush
+            .queue_stroke_path_raw(
+                &Path::line([x + 200.0, y + 14.0], [x + 260.0, y + 14.0]),
+                &Stroke::new(2.0, Color::WHITE),
+            )
+            .unwrap();
+        if layered {
+            brush.pop_layer();
+        }
+    }
+}
+
+fn bench(sample_count: u32, rows: usize) {
+    let config = RendererConfig::new().with_sample_count(sample_count);
+    let mut app = match BobsicsHeadlessApp::with_config(WIDTH, HEIGHT, config) {
+        Ok(app) => app,
+        Err(err) => {
+            println!("skipped, no adapter: {err}");
+            return;
+        }
+    };
+
+    let mut total = Duration::ZERO;
+    for frame in 0..WARMUP_FRAMES + FRAMES {
+        let start = Instant::now();
+        queue_scene(&mut app.brush, rows);
+        app.renderer.render(&mut app.brush).unwrap();
+        // Include the time the GPU takes, not just recording
+        app.renderer.device.poll(wgpu::Maintain::Wait);
+        if frame >= WARMUP_FRAMES {
+            total += start.elapsed();
+        }
+    }
+
+    let average = total / FRAMES;
+    println!(
+        "{rows:>4} rows, {sample_count}x MSAA: {:>8.3} ms/frame",
+        average.as_secs_f64() * 1000.0
+    );
+}
+
+fn main() {
+    for sample_count in [1, 4] {
+        for rows in [24, 96] {
+            bench(sample_count, rows);
+        }
+    }
+}
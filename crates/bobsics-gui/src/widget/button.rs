@@ -1,4 +1,4 @@
-use bobsics_render::Color;
+use bobsics_render::{Color, Gradient};
 
 use crate::{
     widgets::{BBox, Font, GUIEvent, Label, UniversalBrush, Vector2},
@@ -26,6 +26,8 @@ pub struct Button {
 pub struct ButtonOptions {
     pub color: Color,
     pub hover_color: Color,
+    // Drawn instead of `color` when not hovered
+    pub gradient: Option<Gradient>,
     pub text_color: Color,
     pub font: Font,
     pub scale: f32,
@@ -42,6 +44,7 @@ impl Default for ButtonOptions {
         Self {
             color: Color::from_hex(0x1a6bf5),
             hover_color: Color::from_hex(0x1a6bf5).with_alpha(0.5),
+            gradient: None,
             text_color: Color::WHITE,
             font: Font::default(),
             scale: 16.0,
@@ -80,6 +83,11 @@ impl Button {
         self
     }
 
+    pub fn with_gradient(mut self, gradient: Gradient) -> Self {
+        self.options.gradient = Some(gradient);
+        self
+    }
+
     pub fn with_text_color(mut self, color: Color) -> Self {
         self.options.text_color = color;
         self
@@ -254,6 +262,10 @@ impl Widget for Button {
         );
 
         // Draw background
+        let fill_gradient = match &self.options.gradient {
+            Some(gradient) if !self._is_hovered => brush.queue_gradient(gradient),
+            _ => 0,
+        };
         brush.queue_quad_raw(bobsics_render::Quad {
             top_left: n_offset.into(),
             bottom_right: (n_offset + button_size).into(),
@@ -262,9 +274,11 @@ impl Widget for Button {
             } else {
                 self.options.color.into()
             },
-            border_radius: self.options.radius,
-            border_color: Color::TRANSPARENT.into(),
-            border_width: 0.0,
+            border_radius: [self.options.radius; 4],
+            border_color: [Color::TRANSPARENT.into(); 4],
+            border_width: [0.0; 4],
+            fill_gradient,
+            ..Default::default()
         }).expect("Failed to draw button background");
 
         // Draw text
@@ -281,8 +295,6 @@ impl Widget for Button {
         brush: &mut UniversalBrush,
         globals: &Globals,
     ) -> BBox {
-        let offset = offset;
-
         // Get label size
         let label_bbox = self._label.measure(offset, scale, brush, globals);
 
@@ -1,9 +1,19 @@
-use std::ops::{Div, Sub};
+use std::{
+    marker::PhantomData,
+    ops::{Div, Range, Sub},
+};
 
-use bobsics_render::{Brush, Color, QuadBrush};
+use bobsics_render::{
+    AnyCustomBrush, Backdrop, BackdropBrush, Brush, ClipRect, Color, CustomBrush, CustomInstance,
+    Fill, FrameTarget, Gradient, ImageBrush, ImageId, ImageQuad, Layer, LayerBrush, NineSlice,
+    Path, PathBrush, QuadBrush, RenderError, Shadow, ShadowBrush, Shape, ShapeBrush, Stroke,
+    SvgCache, SvgId, SvgQuad, TextBrush, TextureAtlas, Transform,
+};
 use wgpu::{Device, TextureFormat};
 use wgpu_glyph::GlyphCruncher;
 
+use super::draw_list::{DrawList, DrawOp};
+
 // ====< FONTS >====
 pub enum FontWeight {
     Thin,
@@ -16,7 +26,9 @@ pub enum FontWeight {
     ExtraBold,
     Black,
 }
+#[derive(Default)]
 pub enum Font {
+    #[default]
     LeagueSpartan,
 }
 
@@ -31,12 +43,6 @@ impl Font {
     }
 }
 
-impl Default for Font {
-    fn default() -> Self {
-        Self::LeagueSpartan
-    }
-}
-
 // ====< POSITIONING >====
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct Vector2 {
@@ -172,50 +178,1186 @@ impl Div<f32> for Vector2 {
 
 // ====< OTHER >====
 
-pub struct UniversalBrush {
-    pub glyph_brush: wgpu_glyph::GlyphBrush<()>,
+// Everything queued on the brush, in submission order.
+// Later commands are drawn on top of earlier ones.
+enum DrawCommand {
+    // Range of batches in the quad brush
+    Quads(Range<usize>),
+    // Range of batches in the shadow brush
+    Shadows(Range<usize>),
+    // Range of batches in the shape brush
+    Shapes(Range<usize>),
+    // Range of batches in the image brush
+    Images(Range<usize>),
+    // Range of batches in the path brush
+    Paths(Range<usize>),
+    // Range of batches in a custom brush
+    Custom {
+        brush: usize,
+        batches: Range<usize>,
+    },
+    // Range of batches in the text brush
+    Text(Range<usize>),
+    // Batch in the backdrop brush, blurring everything drawn before it
+    Backdrop(usize),
+    // Everything up to the matching `PopLayer` is drawn into an offscreen layer
+    PushLayer {
+        layer: Layer,
+        clip: Option<ClipRect>,
+    },
+    PopLayer,
+}
+
+// How the commands of a brush are drawn this frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Upload {
+    // Everything was uploaded up front, so they share render passes with the other brushes
+    Shared,
+    // Too much to upload at once, they are drawn in render passes of their own
+    Separate,
+    // Uploading failed, they are skipped
+    Failed,
+}
+
+impl Upload {
+    fn from_result(
+        prepared: Result<bool, RenderError>,
+        result: &mut Result<(), RenderError>,
+    ) -> Self {
+        match prepared {
+            Ok(true) => Self::Shared,
+            Ok(false) => Self::Separate,
+            Err(err) => {
+                *result = std::mem::replace(result, Ok(())).and(Err(err));
+                Self::Failed
+            }
+        }
+    }
+}
+
+struct Uploads {
+    quads: Upload,
+    shadows: Upload,
+    shapes: Upload,
+    images: Upload,
+    paths: Upload,
+    text: Upload,
+    // Never separate, backdrops are blurred one by one anyway
+    backdrops: Upload,
+    custom: Vec<Upload>,
+}
+
+impl Uploads {
+    fn of(&self, command: &DrawCommand) -> Upload {
+        match command {
+            DrawCommand::Quads(_) => self.quads,
+            DrawCommand::Shadows(_) => self.shadows,
+            DrawCommand::Shapes(_) => self.shapes,
+            DrawCommand::Images(_) => self.images,
+            DrawCommand::Paths(_) => self.paths,
+            DrawCommand::Custom { brush, .. } => self.custom[*brush],
+            DrawCommand::Text(_) => self.text,
+            DrawCommand::Backdrop(_) => self.backdrops,
+            DrawCommand::PushLayer { .. } | DrawCommand::PopLayer => Upload::Shared,
+        }
+    }
+}
+
+// A layer that is being drawn into
+struct OpenLayer {
+    // Position among all layers of the frame, as given to `LayerBrush::prepare`
+    index: usize,
+    layer: Layer,
+    clip: Option<ClipRect>,
+    // Layers are cleared by the first render pass that draws into them,
+    // and have to be resolved by the last one when multisampling
+    drawn: bool,
+    resolved: bool,
+}
+
+/// The brushes a [`UniversalBrush`] draws with, missing on a [`UniversalBrush::recorder`].
+pub struct GpuBrushes {
+    pub text_brush: TextBrush,
     pub quad_brush: QuadBrush,
+    pub shadow_brush: ShadowBrush,
+    pub shape_brush: ShapeBrush,
+    pub image_brush: ImageBrush,
+    pub path_brush: PathBrush,
+    pub backdrop_brush: BackdropBrush,
+    pub layer_brush: LayerBrush,
+    pub custom_brushes: Vec<Box<dyn AnyCustomBrush>>,
+
+    commands: Vec<DrawCommand>,
+}
+
+impl GpuBrushes {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        Self {
+            text_brush: TextBrush::new(
+                device,
+                format,
+                sample_count,
+                globals_bind_group_layout,
+                vec![Font::LeagueSpartan.get_font()],
+            ),
+            quad_brush: QuadBrush::new(device, format, sample_count, globals_bind_group_layout),
+            shadow_brush: ShadowBrush::new(device, format, sample_count, globals_bind_group_layout),
+            shape_brush: ShapeBrush::new(device, format, sample_count, globals_bind_group_layout),
+            image_brush: ImageBrush::new(device, format, sample_count, globals_bind_group_layout),
+            path_brush: PathBrush::new(device, format, sample_count, globals_bind_group_layout),
+            backdrop_brush: BackdropBrush::new(
+                device,
+                format,
+                sample_count,
+                globals_bind_group_layout,
+            ),
+            layer_brush: LayerBrush::new(device, format, sample_count, globals_bind_group_layout),
+            custom_brushes: Vec::new(),
+            commands: Vec::new(),
+        }
+    }
+
+    fn set_clip(&mut self, clip: Option<ClipRect>) {
+        self.text_brush.set_clip(clip);
+        self.quad_brush.set_clip(clip);
+        self.shadow_brush.set_clip(clip);
+        self.shape_brush.set_clip(clip);
+        self.image_brush.set_clip(clip);
+        self.path_brush.set_clip(clip);
+        self.backdrop_brush.set_clip(clip);
+        for brush in &mut self.custom_brushes {
+            brush.set_clip(clip);
+        }
+    }
+
+    fn set_transform(&mut self, transform: Transform) {
+        self.text_brush.set_transform(transform);
+        self.quad_brush.set_transform(transform);
+        self.shadow_brush.set_transform(transform);
+        self.shape_brush.set_transform(transform);
+        self.image_brush.set_transform(transform);
+        self.path_brush.set_transform(transform);
+        self.backdrop_brush.set_transform(transform);
+    }
+
+    fn queue_text(&mut self, section: &wgpu_glyph::Section) {
+        if !matches!(self.commands.last(), Some(DrawCommand::Text(_))) {
+            self.split_batches();
+        }
+        self.text_brush.queue_section(section);
+
+        // The section may have started a new batch
+        let batch_count = self.text_brush.batches.len();
+        match self.commands.last_mut() {
+            Some(DrawCommand::Text(batches)) => batches.end = batch_count,
+            _ => self
+                .commands
+                .push(DrawCommand::Text(batch_count - 1..batch_count)),
+        }
+    }
+
+    fn queue_quad(&mut self, quad: bobsics_render::Quad) {
+        if !matches!(self.commands.last(), Some(DrawCommand::Quads(_))) {
+            self.split_batches();
+        }
+        self.quad_brush.queue_quad(quad);
+
+        // The quad may have started a new batch
+        let batch_count = self.quad_brush.batches.len();
+        match self.commands.last_mut() {
+            Some(DrawCommand::Quads(batches)) => batches.end = batch_count,
+            _ => self
+                .commands
+                .push(DrawCommand::Quads(batch_count - 1..batch_count)),
+        }
+    }
+
+    fn queue_shadow(&mut self, shadow: Shadow) {
+        if !matches!(self.commands.last(), Some(DrawCommand::Shadows(_))) {
+            self.split_batches();
+        }
+        self.shadow_brush.queue_shadow(shadow);
+
+        // The shadow may have started a new batch
+        let batch_count = self.shadow_brush.batches.len();
+        match self.commands.last_mut() {
+            Some(DrawCommand::Shadows(batches)) => batches.end = batch_count,
+            _ => self
+                .commands
+                .push(DrawCommand::Shadows(batch_count - 1..batch_count)),
+        }
+    }
+
+    fn queue_shape(&mut self, shape: Shape) {
+        if !matches!(self.commands.last(), Some(DrawCommand::Shapes(_))) {
+            self.split_batches();
+        }
+        self.shape_brush.queue_shape(shape);
+
+        // The shape may have started a new batch
+        let batch_count = self.shape_brush.batches.len();
+        match self.commands.last_mut() {
+            Some(DrawCommand::Shapes(batches)) => batches.end = batch_count,
+            _ => self
+                .commands
+                .push(DrawCommand::Shapes(batch_count - 1..batch_count)),
+        }
+    }
+
+    fn queue_backdrop(&mut self, backdrop: Backdrop) {
+        // Every backdrop is a batch of its own, drawn after everything queued before it
+        self.split_batches();
+        self.backdrop_brush.queue_backdrop(backdrop);
+        self.commands
+            .push(DrawCommand::Backdrop(self.backdrop_brush.batches.len() - 1));
+    }
+
+    fn queue_image(&mut self, image: ImageQuad) {
+        if !matches!(self.commands.last(), Some(DrawCommand::Images(_))) {
+            self.split_batches();
+        }
+        self.image_brush.queue_image(image);
+        self.extend_images();
+    }
+
+    fn queue_nine_slice(&mut self, slice: NineSlice) {
+        if !matches!(self.commands.last(), Some(DrawCommand::Images(_))) {
+            self.split_batches();
+        }
+        self.image_brush.queue_nine_slice(slice);
+        self.extend_images();
+    }
+
+    // Adds the batches of the images queued last to the draw commands
+    fn extend_images(&mut self) {
+        // The images may have started a new batch
+        let batch_count = self.image_brush.batches.len();
+        match self.commands.last_mut() {
+            Some(DrawCommand::Images(batches)) => batches.end = batch_count,
+            _ => self
+                .commands
+                .push(DrawCommand::Images(batch_count - 1..batch_count)),
+        }
+    }
+
+    fn fill_path(&mut self, path: &Path, fill: &Fill) -> Result<(), RenderError> {
+        if !matches!(self.commands.last(), Some(DrawCommand::Paths(_))) {
+            self.split_batches();
+        }
+        self.path_brush.fill_path(path, fill)?;
+        self.push_path_batches();
+        Ok(())
+    }
+
+    fn stroke_path(&mut self, path: &Path, stroke: &Stroke) -> Result<(), RenderError> {
+        if !matches!(self.commands.last(), Some(DrawCommand::Paths(_))) {
+            self.split_batches();
+        }
+        self.path_brush.stroke_path(path, stroke)?;
+        self.push_path_batches();
+        Ok(())
+    }
+
+    fn queue_custom(&mut self, brush: usize, instance: &[u8]) -> Result<(), RenderError> {
+        let continues_batch = matches!(
+            self.commands.last(),
+            Some(DrawCommand::Custom { brush: last, .. }) if *last == brush
+        );
+        if !continues_batch {
+            self.split_batches();
+        }
+        let custom_brush = self.custom_brushes.get_mut(brush).ok_or_else(|| {
+            RenderError::Brush(format!("there is no custom brush with index {brush}"))
+        })?;
+        custom_brush.queue_instance_bytes(instance)?;
+
+        // The instance may have started a new batch
+        let batch_count = custom_brush.batch_count();
+        match self.commands.last_mut() {
+            Some(DrawCommand::Custom {
+                brush: last,
+                batches,
+            }) if *last == brush => batches.end = batch_count,
+            _ => self.commands.push(DrawCommand::Custom {
+                brush,
+                batches: batch_count - 1..batch_count,
+            }),
+        }
+        Ok(())
+    }
+
+    // The path may have started a new batch
+    fn push_path_batches(&mut self) {
+        let batch_count = self.path_brush.batches.len();
+        match self.commands.last_mut() {
+            Some(DrawCommand::Paths(batches)) => batches.end = batch_count,
+            _ => self
+                .commands
+                .push(DrawCommand::Paths(batch_count - 1..batch_count)),
+        }
+    }
+
+    // Whether a backdrop has to copy what is drawn before it, see `Brush::reads_frame`
+    fn reads_frame(&self) -> bool {
+        self.commands
+            .iter()
+            .any(|command| matches!(command, DrawCommand::Backdrop(_)))
+    }
+
+    // Makes sure the next queued primitive of every brush starts a new batch,
+    // so it is drawn after whatever was queued in between
+    fn split_batches(&mut self) {
+        self.text_brush.split_batch();
+        self.quad_brush.split_batch();
+        self.shadow_brush.split_batch();
+        self.shape_brush.split_batch();
+        self.image_brush.split_batch();
+        self.path_brush.split_batch();
+        for brush in &mut self.custom_brushes {
+            brush.split_batch();
+        }
+    }
+
+    // Uploads everything queued, before any of it is drawn
+    #[allow(clippy::too_many_arguments)]
+    fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        width: u32,
+        height: u32,
+        commands: &[DrawCommand],
+        result: &mut Result<(), RenderError>,
+    ) -> Uploads {
+        let uploads = Uploads {
+            quads: Upload::from_result(
+                self.quad_brush.prepare(device, staging_belt, encoder),
+                result,
+            ),
+            shadows: Upload::from_result(
+                Ok(self.shadow_brush.prepare(device, staging_belt, encoder)),
+                result,
+            ),
+            shapes: Upload::from_result(
+                Ok(self.shape_brush.prepare(device, staging_belt, encoder)),
+                result,
+            ),
+            images: Upload::from_result(
+                self.image_brush.prepare(device, staging_belt, encoder),
+                result,
+            ),
+            paths: Upload::from_result(
+                Ok(self.path_brush.prepare(device, staging_belt, encoder)),
+                result,
+            ),
+            text: Upload::from_result(
+                self.text_brush.prepare(device, staging_belt, encoder),
+                result,
+            ),
+            backdrops: if self
+                .backdrop_brush
+                .prepare(device, staging_belt, encoder, width, height)
+            {
+                Upload::Shared
+            } else {
+                Upload::Failed
+            },
+            custom: self
+                .custom_brushes
+                .iter_mut()
+                .map(|brush| {
+                    Upload::from_result(Ok(brush.prepare(device, staging_belt, encoder)), result)
+                })
+                .collect(),
+        };
+
+        // Layers are numbered in the order they are pushed
+        let mut layers = Vec::new();
+        let (mut depth, mut max_depth) = (0, 0);
+        for command in commands {
+            match command {
+                DrawCommand::PushLayer { layer, .. } => {
+                    layers.push(*layer);
+                    depth += 1;
+                    max_depth = max_depth.max(depth);
+                }
+                DrawCommand::PopLayer => depth -= 1,
+                _ => {}
+            }
+        }
+        self.layer_brush.prepare(
+            device,
+            staging_belt,
+            encoder,
+            width,
+            height,
+            max_depth,
+            &layers,
+        );
+
+        uploads
+    }
+}
+
+impl Brush for GpuBrushes {
+    fn draw_queued(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        target: &mut FrameTarget,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        let (width, height) = (target.width, target.height);
+        let commands = std::mem::take(&mut self.commands);
+
+        // Keep drawing after an error, and report the first one
+        let mut result = Ok(());
+        let uploads = self.prepare(
+            device,
+            staging_belt,
+            encoder,
+            width,
+            height,
+            &commands,
+            &mut result,
+        );
+
+        // Commands are recorded into as few render passes as possible, only layers and brushes
+        // with too much queued start new ones
+        let mut layers: Vec<OpenLayer> = Vec::new();
+        let mut layer_count = 0;
+        let mut index = 0;
+        while index < commands.len() {
+            // A popped layer is composited at the start of the next render pass below it
+            let mut composite = None;
+            // A backdrop is drawn at the start of the render pass after blurring
+            let mut start = index;
+            match &commands[index] {
+                DrawCommand::PushLayer { layer, clip } => {
+                    layers.push(OpenLayer {
+                        index: layer_count,
+                        layer: *layer,
+                        clip: *clip,
+                        drawn: false,
+                        resolved: false,
+                    });
+                    layer_count += 1;
+                    index += 1;
+                    continue;
+                }
+                DrawCommand::PopLayer => {
+                    let depth = layers.len();
+                    // Empty layers don't change anything
+                    composite = layers.pop().filter(|layer| layer.drawn);
+                    if matches!(&composite, Some(layer) if !layer.resolved) {
+                        self.layer_brush
+                            .begin_render_pass(encoder, depth, false, true);
+                    }
+                    index += 1;
+                    start = index;
+                }
+                DrawCommand::Backdrop(batch) => {
+                    index += 1;
+                    if uploads.backdrops == Upload::Failed {
+                        continue;
+                    }
+
+                    // Blur a copy of everything drawn so far into the layer or frame
+                    let Some(texture) = self.backdrop_brush.texture() else {
+                        continue;
+                    };
+                    let depth = layers.len();
+                    let copied = match layers.last_mut() {
+                        Some(layer) => {
+                            self.layer_brush
+                                .copy_to(encoder, depth, !layer.drawn, texture);
+                            layer.drawn = true;
+                            layer.resolved = false;
+                            true
+                        }
+                        None => target.copy_to(encoder, texture),
+                    };
+                    if !copied {
+                        continue;
+                    }
+                    self.backdrop_brush
+                        .blur(encoder, width, height, *batch, globals_bind_group);
+                }
+                command if uploads.of(command) == Upload::Separate => {
+                    let depth = layers.len();
+                    let view = match layers.last_mut() {
+                        Some(layer) => {
+                            if !layer.drawn {
+                                self.layer_brush
+                                    .begin_render_pass(encoder, depth, true, false);
+                            }
+                            layer.drawn = true;
+                            layer.resolved = false;
+                            self.layer_brush.layer_view(depth)
+                        }
+                        None => target.load_view(encoder),
+                    };
+                    let drawn = match command {
+                        DrawCommand::Quads(batches) => self.quad_brush.draw_batches(
+                            device,
+                            staging_belt,
+                            encoder,
+                            view,
+                            width,
+                            height,
+                            batches.clone(),
+                            globals_bind_group,
+                        ),
+                        DrawCommand::Shadows(batches) => self.shadow_brush.draw_batches(
+                            device,
+                            staging_belt,
+                            encoder,
+                            view,
+                            width,
+                            height,
+                            batches.clone(),
+                            globals_bind_group,
+                        ),
+                        DrawCommand::Shapes(batches) => self.shape_brush.draw_batches(
+                            device,
+                            staging_belt,
+                            encoder,
+                            view,
+                            width,
+                            height,
+                            batches.clone(),
+                            globals_bind_group,
+                        ),
+                        DrawCommand::Images(batches) => self.image_brush.draw_batches(
+                            device,
+                            staging_belt,
+                            encoder,
+                            view,
+                            width,
+                            height,
+                            batches.clone(),
+                            globals_bind_group,
+                        ),
+                        DrawCommand::Paths(batches) => self.path_brush.draw_batches(
+                            device,
+                            staging_belt,
+                            encoder,
+                            view,
+                            width,
+                            height,
+                            batches.clone(),
+                            globals_bind_group,
+                        ),
+                        DrawCommand::Custom { brush, batches } => self.custom_brushes[*brush]
+                            .draw_batches(
+                                device,
+                                staging_belt,
+                                encoder,
+                                view,
+                                width,
+                                height,
+                                batches.clone(),
+                                globals_bind_group,
+                            ),
+                        DrawCommand::Text(batches) => {
+                            self.text_brush.draw_batches(
+                                device,
+                                staging_belt,
+                                encoder,
+                                view,
+                                width,
+                                height,
+                                batches.clone(),
+                                globals_bind_group,
+                            );
+                            Ok(())
+                        }
+                        DrawCommand::Backdrop(_)
+                        | DrawCommand::PushLayer { .. }
+                        | DrawCommand::PopLayer => Ok(()),
+                    };
+                    result = result.and(drawn);
+                    index += 1;
+                    continue;
+                }
+                _ => {}
+            }
+
+            // Everything up to the next layer, backdrop or separately drawn brush shares one render pass
+            let end = index
+                + commands[index..]
+                    .iter()
+                    .take_while(|command| {
+                        !matches!(
+                            command,
+                            DrawCommand::PushLayer { .. }
+                                | DrawCommand::PopLayer
+                                | DrawCommand::Backdrop(_)
+                        ) && uploads.of(command) != Upload::Separate
+                    })
+                    .count();
+            if composite.is_none() && end == start {
+                continue;
+            }
+
+            let depth = layers.len();
+            let mut rpass = match layers.last_mut() {
+                Some(layer) => {
+                    // Resolve the layer if it is composited next
+                    let resolve = matches!(commands.get(end), Some(DrawCommand::PopLayer));
+                    let clear = !layer.drawn;
+                    layer.drawn = true;
+                    layer.resolved = resolve;
+                    self.layer_brush
+                        .begin_render_pass(encoder, depth, clear, resolve)
+                }
+                None => {
+                    target.begin_render_pass(encoder, "Bobsics render pass", end == commands.len())
+                }
+            };
+            if let Some(layer) = composite {
+                self.layer_brush.draw_prepared(
+                    &mut rpass,
+                    width,
+                    height,
+                    depth + 1,
+                    layer.index,
+                    &layer.layer,
+                    layer.clip,
+                    globals_bind_group,
+                );
+            }
+            for command in &commands[start..end] {
+                if uploads.of(command) == Upload::Failed {
+                    continue;
+                }
+                match command {
+                    DrawCommand::Quads(batches) => self.quad_brush.draw_prepared(
+                        &mut rpass,
+                        width,
+                        height,
+                        batches.clone(),
+                        globals_bind_group,
+                    ),
+                    DrawCommand::Shadows(batches) => self.shadow_brush.draw_prepared(
+                        &mut rpass,
+                        width,
+                        height,
+                        batches.clone(),
+                        globals_bind_group,
+                    ),
+                    DrawCommand::Shapes(batches) => self.shape_brush.draw_prepared(
+                        &mut rpass,
+                        width,
+                        height,
+                        batches.clone(),
+                        globals_bind_group,
+                    ),
+                    DrawCommand::Images(batches) => self.image_brush.draw_prepared(
+                        &mut rpass,
+                        width,
+                        height,
+                        batches.clone(),
+                        globals_bind_group,
+                    ),
+                    DrawCommand::Paths(batches) => self.path_brush.draw_prepared(
+                        &mut rpass,
+                        width,
+                        height,
+                        batches.clone(),
+                        globals_bind_group,
+                    ),
+                    DrawCommand::Custom { brush, batches } => self.custom_brushes[*brush]
+                        .draw_prepared(
+                            &mut rpass,
+                            width,
+                            height,
+                            batches.clone(),
+                            globals_bind_group,
+                        ),
+                    DrawCommand::Text(batches) => self.text_brush.draw_prepared(
+                        &mut rpass,
+                        width,
+                        height,
+                        batches.clone(),
+                        globals_bind_group,
+                    ),
+                    DrawCommand::Backdrop(batch) => self.backdrop_brush.draw_prepared(
+                        &mut rpass,
+                        width,
+                        height,
+                        *batch..*batch + 1,
+                        globals_bind_group,
+                    ),
+                    DrawCommand::PushLayer { .. } | DrawCommand::PopLayer => {}
+                }
+            }
+            drop(rpass);
+            index = end;
+        }
+
+        self.text_brush.clear();
+        self.quad_brush.clear();
+        self.shadow_brush.clear();
+        self.shape_brush.clear();
+        self.image_brush.clear();
+        self.path_brush.clear();
+        self.backdrop_brush.clear();
+        for brush in &mut self.custom_brushes {
+            brush.clear();
+        }
+
+        result
+    }
+
+    fn recreate(
+        &mut self,
+        device: &wgpu::Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        self.text_brush
+            .recreate(device, format, sample_count, globals_bind_group_layout);
+        self.quad_brush
+            .recreate(device, format, sample_count, globals_bind_group_layout);
+        self.shadow_brush
+            .recreate(device, format, sample_count, globals_bind_group_layout);
+        self.shape_brush
+            .recreate(device, format, sample_count, globals_bind_group_layout);
+        self.image_brush
+            .recreate(device, format, sample_count, globals_bind_group_layout);
+        self.path_brush
+            .recreate(device, format, sample_count, globals_bind_group_layout);
+        self.backdrop_brush
+            .recreate(device, format, sample_count, globals_bind_group_layout);
+        self.layer_brush
+            .recreate(device, format, sample_count, globals_bind_group_layout);
+        for brush in &mut self.custom_brushes {
+            brush.recreate(device, format, sample_count, globals_bind_group_layout);
+        }
+    }
+}
+
+/// Handle to a [`CustomBrush`] added to a [`UniversalBrush`].
+pub struct CustomBrushId<I, U = ()> {
+    index: usize,
+    _types: PhantomData<fn() -> (I, U)>,
+}
+
+impl<I, U> CustomBrushId<I, U> {
+    /// Position of the brush in [`GpuBrushes::custom_brushes`], as used by [`DrawOp::Custom`].
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<I, U> Clone for CustomBrushId<I, U> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<I, U> Copy for CustomBrushId<I, U> {}
+
+impl<I, U> std::fmt::Debug for CustomBrushId<I, U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("CustomBrushId").field(&self.index).finish()
+    }
+}
+
+pub struct UniversalBrush {
+    pub gpu: Option<GpuBrushes>,
+
+    // Lays out text without a device
+    glyph_calculator: glyph_brush::GlyphCalculator<wgpu_glyph::ab_glyph::FontArc>,
+    // Images loaded while there is no GPU, handed to the image brush once there is one
+    images: TextureAtlas,
+    // Loaded SVGs, rasterized into whichever atlas is in use
+    svgs: SvgCache,
+    // Gradients queued this frame while there is no GPU
+    gradient_count: u32,
+    recording: Option<DrawList>,
+    clip_stack: Vec<ClipRect>,
+    transform_stack: Vec<Transform>,
+    // Number of layers pushed and not popped yet
+    layer_depth: usize,
 }
 
 impl UniversalBrush {
     pub fn new(
         device: &Device,
         format: TextureFormat,
+        sample_count: u32,
         globals_bind_group_layout: &wgpu::BindGroupLayout,
     ) -> Self {
         Self {
-            glyph_brush: wgpu_glyph::GlyphBrushBuilder::using_font(
-                wgpu_glyph::ab_glyph::FontArc::try_from_slice(include_bytes!(
-                    "../assets/LeagueSpartan-Bold.ttf"
-                ))
-                .unwrap(),
+            gpu: Some(GpuBrushes::new(
+                device,
+                format,
+                sample_count,
+                globals_bind_group_layout,
+            )),
+            ..Self::recorder_inner(None)
+        }
+    }
+
+    /// A brush without a GPU, that only records what is drawn with it into a [`DrawList`].
+    /// Text is still measured, so widgets lay out the same as on a real brush.
+    pub fn recorder() -> Self {
+        Self::recorder_inner(Some(DrawList::new()))
+    }
+
+    fn recorder_inner(recording: Option<DrawList>) -> Self {
+        Self {
+            gpu: None,
+            glyph_calculator: glyph_brush::GlyphCalculatorBuilder::using_font(
+                Font::LeagueSpartan.get_font(),
             )
-            .build(device, format),
-            quad_brush: QuadBrush::new(device, format, globals_bind_group_layout),
+            .build(),
+            images: TextureAtlas::new(),
+            svgs: SvgCache::new(),
+            gradient_count: 0,
+            recording,
+            clip_stack: Vec::new(),
+            transform_stack: Vec::new(),
+            layer_depth: 0,
+        }
+    }
+
+    // ====< RECORDING >====
+    /// Records everything drawn from now on, until [`UniversalBrush::finish_recording`].
+    /// Drawing on the GPU carries on as usual.
+    pub fn start_recording(&mut self) {
+        self.recording = Some(DrawList::new());
+    }
+
+    /// Stops recording and returns what was drawn since [`UniversalBrush::start_recording`],
+    /// or since the brush was created for a [`UniversalBrush::recorder`].
+    pub fn finish_recording(&mut self) -> DrawList {
+        self.recording.take().unwrap_or_default()
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recording.is_some()
+    }
+
+    fn record(&mut self, op: DrawOp) {
+        if let Some(recording) = &mut self.recording {
+            recording.push(op);
+        }
+    }
+
+    // ====< CLIPPING >====
+    /// Restricts all drawing to `bbox` (intersected with the current clip) until the matching [`UniversalBrush::pop_clip`].
+    /// Clips are axis aligned on screen, under a rotation the bounding box of the transformed `bbox` is used.
+    pub fn push_clip(&mut self, bbox: BBox) {
+        self.record(DrawOp::PushClip(bbox.into()));
+        let (min, max) = self
+            .current_transform()
+            .transform_rect(bbox.min.into(), bbox.max.into());
+        let bbox = BBox::new(min.into(), max.into());
+        let clip = match self.clip_stack.last() {
+            Some(current) => current.intersect(&bbox.into()),
+            None => bbox.into(),
+        };
+        self.clip_stack.push(clip);
+        self.apply_clip();
+    }
+
+    pub fn pop_clip(&mut self) {
+        self.record(DrawOp::PopClip);
+        self.clip_stack.pop();
+        self.apply_clip();
+    }
+
+    fn apply_clip(&mut self) {
+        let clip = self.current_clip();
+        if let Some(gpu) = &mut self.gpu {
+            gpu.set_clip(clip);
+        }
+    }
+
+    pub fn current_clip(&self) -> Option<ClipRect> {
+        self.clip_stack.last().copied()
+    }
+
+    // ====< TRANSFORMS >====
+    /// Applies `transform` to everything drawn until the matching [`UniversalBrush::pop_transform`],
+    /// before the transforms pushed earlier. Text is rasterized at its untransformed size.
+    pub fn push_transform(&mut self, transform: Transform) {
+        self.record(DrawOp::PushTransform(transform));
+        let transform = transform.then(&self.current_transform());
+        self.transform_stack.push(transform);
+        self.apply_transform();
+    }
+
+    pub fn pop_transform(&mut self) {
+        self.record(DrawOp::PopTransform);
+        self.transform_stack.pop();
+        self.apply_transform();
+    }
+
+    fn apply_transform(&mut self) {
+        let transform = self.current_transform();
+        if let Some(gpu) = &mut self.gpu {
+            gpu.set_transform(transform);
+        }
+    }
+
+    /// Combined transform of the stack, its inverse maps the cursor into the local space of a widget.
+    pub fn current_transform(&self) -> Transform {
+        self.transform_stack.last().copied().unwrap_or_default()
+    }
+
+    // ====< LAYERS >====
+    /// Draws everything until the matching [`UniversalBrush::pop_layer`] into an offscreen texture,
+    /// which is then composited with the opacity, blend mode and mask of `layer`.
+    /// The mask is in the current transform, and the layer is limited to the current clip.
+    pub fn push_layer(&mut self, mut layer: Layer) {
+        self.record(DrawOp::PushLayer(layer));
+        layer.mask_transform = layer.mask_transform.then(&self.current_transform());
+        let clip = self.current_clip();
+        if let Some(gpu) = &mut self.gpu {
+            gpu.commands.push(DrawCommand::PushLayer { layer, clip });
+        }
+        self.layer_depth += 1;
+    }
+
+    pub fn pop_layer(&mut self) {
+        if self.layer_depth > 0 {
+            self.record(DrawOp::PopLayer);
+            self.layer_depth -= 1;
+            if let Some(gpu) = &mut self.gpu {
+                gpu.commands.push(DrawCommand::PopLayer);
+            }
         }
     }
 
     // ====< FONTS >====
-    pub fn queue_text_raw(&mut self, section: &wgpu_glyph::Section) -> Result<(), &str> {
-        self.glyph_brush.queue(section);
+    pub fn queue_text_raw(&mut self, section: &wgpu_glyph::Section) -> Result<(), RenderError> {
+        self.record(DrawOp::Text(section.into()));
+        if let Some(gpu) = &mut self.gpu {
+            gpu.queue_text(section);
+        }
         Ok(())
     }
     pub fn measure(&mut self, section: &wgpu_glyph::Section) -> (f32, f32) {
-        let bounds = self.glyph_brush.glyph_bounds(section).unwrap_or_else(|| {
-            println!("WARNING: No bounds for section: {section:?}");
-            wgpu_glyph::ab_glyph::Rect {
-                min: wgpu_glyph::ab_glyph::Point { x: 0.0, y: 0.0 },
-                max: wgpu_glyph::ab_glyph::Point { x: 0.0, y: 0.0 },
-            }
-        });
+        let bounds = self
+            .glyph_calculator
+            .cache_scope()
+            .glyph_bounds(section)
+            .unwrap_or_else(|| {
+                println!("WARNING: No bounds for section: {section:?}");
+                wgpu_glyph::ab_glyph::Rect {
+                    min: wgpu_glyph::ab_glyph::Point { x: 0.0, y: 0.0 },
+                    max: wgpu_glyph::ab_glyph::Point { x: 0.0, y: 0.0 },
+                }
+            });
         (bounds.width(), bounds.height())
     }
 
     // ====< QUADS >====
-    pub fn queue_quad_raw(&mut self, quad: bobsics_render::Quad) -> Result<(), &str> {
-        self.quad_brush.queue_quad(quad);
+    pub fn queue_quad_raw(&mut self, quad: bobsics_render::Quad) -> Result<(), RenderError> {
+        self.record(DrawOp::Quad(quad));
+        if let Some(gpu) = &mut self.gpu {
+            gpu.queue_quad(quad);
+        }
+        Ok(())
+    }
+
+    /// Adds a gradient for this frame, to be used as [`bobsics_render::Quad::fill_gradient`]
+    /// or [`bobsics_render::Quad::border_gradient`] of quads queued afterwards.
+    pub fn queue_gradient(&mut self, gradient: &Gradient) -> u32 {
+        self.record(DrawOp::Gradient(gradient.clone()));
+        match &mut self.gpu {
+            Some(gpu) => gpu.quad_brush.queue_gradient(gradient),
+            None => {
+                self.gradient_count += 1;
+                self.gradient_count
+            }
+        }
+    }
+
+    // ====< SHADOWS >====
+    pub fn queue_shadow_raw(&mut self, shadow: Shadow) -> Result<(), RenderError> {
+        self.record(DrawOp::Shadow(shadow));
+        if let Some(gpu) = &mut self.gpu {
+            gpu.queue_shadow(shadow);
+        }
+        Ok(())
+    }
+
+    // ====< SHAPES >====
+    pub fn queue_shape_raw(&mut self, shape: Shape) -> Result<(), RenderError> {
+        self.record(DrawOp::Shape(shape));
+        if let Some(gpu) = &mut self.gpu {
+            gpu.queue_shape(shape);
+        }
+        Ok(())
+    }
+
+    // ====< BACKDROPS >====
+    /// Blurs and tints everything drawn so far behind a rounded rect, like frosted glass.
+    /// Each backdrop copies the whole frame, so use them sparingly.
+    pub fn queue_backdrop_raw(&mut self, backdrop: Backdrop) -> Result<(), RenderError> {
+        self.record(DrawOp::Backdrop(backdrop));
+        if let Some(gpu) = &mut self.gpu {
+            gpu.queue_backdrop(backdrop);
+        }
         Ok(())
     }
+
+    // ====< IMAGES >====
+    /// Adds an image given as tightly packed sRGB RGBA8 pixels to the image atlas.
+    /// It is uploaded to the GPU the first time it is drawn.
+    pub fn load_image(
+        &mut self,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Result<ImageId, RenderError> {
+        match &mut self.gpu {
+            Some(gpu) => gpu.image_brush.load_image(width, height, rgba),
+            None => self.images.add_image(width, height, rgba),
+        }
+    }
+
+    pub fn image_size(&self, image: ImageId) -> (u32, u32) {
+        match &self.gpu {
+            Some(gpu) => gpu.image_brush.atlas.image_size(image),
+            None => self.images.image_size(image),
+        }
+    }
+
+    pub fn queue_image_raw(&mut self, image: ImageQuad) -> Result<(), RenderError> {
+        self.record(DrawOp::Image(image));
+        if let Some(gpu) = &mut self.gpu {
+            gpu.queue_image(image);
+        }
+        Ok(())
+    }
+
+    pub fn queue_nine_slice_raw(&mut self, slice: NineSlice) -> Result<(), RenderError> {
+        self.record(DrawOp::NineSlice(slice));
+        if let Some(gpu) = &mut self.gpu {
+            gpu.queue_nine_slice(slice);
+        }
+        Ok(())
+    }
+
+    // ====< SVGS >====
+    /// Parses an SVG document, e.g. an icon read from a file or embedded with `include_bytes!`.
+    pub fn load_svg(&mut self, data: &[u8]) -> Result<SvgId, RenderError> {
+        self.svgs.load(data)
+    }
+
+    pub fn svg_size(&self, svg: SvgId) -> (f32, f32) {
+        self.svgs.size(svg)
+    }
+
+    /// Draws the SVG as an image rasterized at its size on screen.
+    /// Sizes and colors are cached by a [`bobsics_render::SvgCache`], and recorded as the resulting image.
+    pub fn queue_svg_raw(&mut self, svg: SvgQuad) -> Result<(), RenderError> {
+        let (width, height) = svg.pixel_size(self.current_transform());
+        let atlas = match &mut self.gpu {
+            Some(gpu) => &mut gpu.image_brush.atlas,
+            None => &mut self.images,
+        };
+        let image = self.svgs.image(atlas, svg.svg, width, height, svg.color)?;
+        self.queue_image_raw(
+            ImageQuad::new(image, svg.top_left, svg.bottom_right).with_transform(svg.transform),
+        )
+    }
+
+    // ====< CUSTOM BRUSHES >====
+    /// Adds a brush with a user shader, whose instances are then queued with
+    /// [`UniversalBrush::queue_custom_raw`] in between everything else.
+    /// Fails on a [`UniversalBrush::recorder`], which has no GPU to draw custom brushes with.
+    pub fn add_custom_brush<I: CustomInstance, U: bytemuck::Pod>(
+        &mut self,
+        brush: CustomBrush<I, U>,
+    ) -> Result<CustomBrushId<I, U>, RenderError> {
+        let gpu = self.gpu.as_mut().ok_or_else(|| {
+            RenderError::Brush("custom brushes can't be added to a recorder".to_string())
+        })?;
+        let mut brush: Box<dyn AnyCustomBrush> = Box::new(brush);
+        brush.set_clip(self.clip_stack.last().copied());
+        gpu.custom_brushes.push(brush);
+        Ok(CustomBrushId {
+            index: gpu.custom_brushes.len() - 1,
+            _types: PhantomData,
+        })
+    }
+
+    pub fn custom_brush_mut<I: CustomInstance, U: bytemuck::Pod>(
+        &mut self,
+        id: CustomBrushId<I, U>,
+    ) -> Option<&mut CustomBrush<I, U>> {
+        self.gpu
+            .as_mut()?
+            .custom_brushes
+            .get_mut(id.index)?
+            .as_any_mut()
+            .downcast_mut()
+    }
+
+    /// Sets the uniforms of a custom brush, shared by all of its instances this frame.
+    pub fn set_custom_uniforms<I: CustomInstance, U: bytemuck::Pod>(
+        &mut self,
+        id: CustomBrushId<I, U>,
+        uniforms: U,
+    ) {
+        if let Some(brush) = self.custom_brush_mut(id) {
+            brush.set_uniforms(uniforms);
+        }
+    }
+
+    /// Queues an instance of a custom brush. Custom instances are drawn in screen space,
+    /// the clip stack applies to them but the transform stack doesn't.
+    pub fn queue_custom_raw<I: CustomInstance, U: bytemuck::Pod>(
+        &mut self,
+        id: CustomBrushId<I, U>,
+        instance: I,
+    ) -> Result<(), RenderError> {
+        self.queue_custom_bytes(id.index, bytemuck::bytes_of(&instance))
+    }
+
+    /// Like [`UniversalBrush::queue_custom_raw`], with the instance given as its raw bytes.
+    pub fn queue_custom_bytes(&mut self, brush: usize, instance: &[u8]) -> Result<(), RenderError> {
+        self.record(DrawOp::Custom {
+            brush,
+            instance: instance.to_vec(),
+        });
+        match &mut self.gpu {
+            Some(gpu) => gpu.queue_custom(brush, instance),
+            None => Ok(()),
+        }
+    }
+
+    // ====< PATHS >====
+    pub fn queue_fill_path_raw(&mut self, path: &Path, fill: &Fill) -> Result<(), RenderError> {
+        self.record(DrawOp::FillPath(path.clone(), *fill));
+        match &mut self.gpu {
+            Some(gpu) => gpu.fill_path(path, fill),
+            None => Ok(()),
+        }
+    }
+
+    pub fn queue_stroke_path_raw(&mut self, path: &Path, stroke: &Stroke) -> Result<(), RenderError> {
+        self.record(DrawOp::StrokePath(path.clone(), *stroke));
+        match &mut self.gpu {
+            Some(gpu) => gpu.stroke_path(path, stroke),
+            None => Ok(()),
+        }
+    }
 }
 
 impl Brush for UniversalBrush {
@@ -224,23 +1366,54 @@ impl Brush for UniversalBrush {
         device: &wgpu::Device,
         staging_belt: &mut wgpu::util::StagingBelt,
         encoder: &mut wgpu::CommandEncoder,
-        target: &wgpu::TextureView,
-        width: u32,
-        height: u32,
+        target: &mut FrameTarget,
         globals_bind_group: &wgpu::BindGroup,
-    ) -> Result<(), &str> {
-        self.quad_brush
-            .draw_queued(device, staging_belt, encoder, target, globals_bind_group);
+    ) -> Result<(), RenderError> {
+        // Layers that were never popped end with the frame
+        for _ in 0..self.layer_depth {
+            self.pop_layer();
+        }
+        self.gradient_count = 0;
+        // Images of recorded SVGs have to stay valid until the recording is finished
+        if self.recording.is_none() {
+            self.svgs.end_frame();
+        }
 
-        self.glyph_brush
-            .draw_queued(device, staging_belt, encoder, target, width, height)
-            .unwrap();
+        match &mut self.gpu {
+            Some(gpu) => gpu.draw_queued(device, staging_belt, encoder, target, globals_bind_group),
+            // A recorder draws nothing until it gets its brushes in `recreate`
+            None => Ok(()),
+        }
+    }
 
-        Ok(())
+    fn reads_frame(&self) -> bool {
+        self.gpu.as_ref().is_some_and(GpuBrushes::reads_frame)
+    }
+
+    fn recreate(
+        &mut self,
+        device: &wgpu::Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        match &mut self.gpu {
+            Some(gpu) => gpu.recreate(device, format, sample_count, globals_bind_group_layout),
+            None => {
+                let mut gpu =
+                    GpuBrushes::new(device, format, sample_count, globals_bind_group_layout);
+                // Images loaded so far keep their ids
+                gpu.image_brush.atlas = std::mem::replace(&mut self.images, TextureAtlas::new());
+                self.gpu = Some(gpu);
+                self.apply_clip();
+                self.apply_transform();
+            }
+        }
     }
 }
 
 // ====< BOUNDING BOX >====
+#[derive(Debug, Clone, Copy)]
 pub struct BBox {
     pub min: Vector2,
     pub max: Vector2,
@@ -281,19 +1454,20 @@ impl BBox {
             && point.y <= self.max.y
     }
 
-    pub fn draw<'a>(
-        &'a self,
+    pub fn draw(
+        &self,
         offset: Vector2,
-        brush: &'a mut UniversalBrush,
+        brush: &mut UniversalBrush,
         color: Color,
-    ) -> Result<(), &str> {
+    ) -> Result<(), RenderError> {
         brush.queue_quad_raw(bobsics_render::Quad {
             top_left: (self.min + offset).into(),
             bottom_right: (self.max + offset).into(),
             color: Color::TRANSPARENT.into(),
-            border_radius: 0.0,
-            border_color: color.into(),
-            border_width: 1.5,
+            border_radius: [0.0; 4],
+            border_color: [color.into(); 4],
+            border_width: [1.5; 4],
+            ..Default::default()
         })
     }
 }
@@ -304,6 +1478,18 @@ impl From<BBox> for (f32, f32, f32, f32) {
     }
 }
 
+impl From<BBox> for ClipRect {
+    fn from(bbox: BBox) -> Self {
+        ClipRect::new(bbox.min.x, bbox.min.y, bbox.width(), bbox.height())
+    }
+}
+
+impl From<ClipRect> for BBox {
+    fn from(clip: ClipRect) -> Self {
+        (clip.x, clip.y, clip.width, clip.height).into()
+    }
+}
+
 impl From<(f32, f32, f32, f32)> for BBox {
     fn from(bbox: (f32, f32, f32, f32)) -> Self {
         Self {
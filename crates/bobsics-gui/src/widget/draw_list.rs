@@ -0,0 +1,298 @@
+use bobsics_render::{
+    Backdrop, ClipRect, Fill, Gradient, ImageQuad, Layer, NineSlice, Path, Quad, RenderError,
+    Shadow, Shape, Stroke, Transform,
+};
+use wgpu_glyph::{FontId, HorizontalAlign, Layout, Section, Text, VerticalAlign};
+
+use super::{
+    widgets::{UniversalBrush, Vector2},
+    Globals, Widget,
+};
+
+// ====< DRAW LIST >====
+/// Everything a widget tree drew, as plain data in submission order.
+///
+/// Record one with [`UniversalBrush::recorder`] (no GPU needed) or
+/// [`UniversalBrush::start_recording`], compare it with [`DrawList::diff`]
+/// and draw it again with [`DrawList::replay`].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DrawList {
+    pub ops: Vec<DrawOp>,
+}
+
+/// A single call on a [`UniversalBrush`], with the arguments it was given.
+/// Positions are before the transforms of the brush, like the calls themselves.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DrawOp {
+    Quad(Quad),
+    // Gradients are numbered from 1 in the order they are queued
+    Gradient(Gradient),
+    Shadow(Shadow),
+    Shape(Shape),
+    Backdrop(Backdrop),
+    Image(ImageQuad),
+    NineSlice(NineSlice),
+    FillPath(Path, Fill),
+    StrokePath(Path, Stroke),
+    Text(TextRun),
+    PushClip(ClipRect),
+    PopClip,
+    PushTransform(Transform),
+    PopTransform,
+    PushLayer(Layer),
+    PopLayer,
+    // Raw bytes of an instance of the custom brush at index `brush`
+    Custom { brush: usize, instance: Vec<u8> },
+}
+
+/// Difference between two draw lists, see [`DrawList::diff`].
+/// Indices are into the old list for removed ops, and into the new list otherwise.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawListChange<'a> {
+    Changed {
+        index: usize,
+        old: &'a DrawOp,
+        new: &'a DrawOp,
+    },
+    Added {
+        index: usize,
+        op: &'a DrawOp,
+    },
+    Removed {
+        index: usize,
+        op: &'a DrawOp,
+    },
+}
+
+impl DrawList {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records what `widget` draws at the origin, without a GPU.
+    /// Widgets showing images need a recorder that loaded them, use
+    /// [`UniversalBrush::recorder`] directly for those.
+    pub fn record(widget: &dyn Widget, globals: &Globals) -> Self {
+        let mut brush = UniversalBrush::recorder();
+        widget.draw(Vector2::ZERO, Vector2::UNIT, &mut brush, globals);
+        brush.finish_recording()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn push(&mut self, op: DrawOp) {
+        self.ops.push(op);
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, DrawOp> {
+        self.ops.iter()
+    }
+
+    /// Queues every op on `brush` again, for the next frame it draws.
+    /// Gradient references only match if no other gradients were queued on `brush` this frame.
+    pub fn replay(&self, brush: &mut UniversalBrush) -> Result<(), RenderError> {
+        for op in &self.ops {
+            match op {
+                DrawOp::Quad(quad) => brush.queue_quad_raw(*quad)?,
+                DrawOp::Gradient(gradient) => {
+                    brush.queue_gradient(gradient);
+                }
+                DrawOp::Shadow(shadow) => brush.queue_shadow_raw(*shadow)?,
+                DrawOp::Shape(shape) => brush.queue_shape_raw(*shape)?,
+                DrawOp::Backdrop(backdrop) => brush.queue_backdrop_raw(*backdrop)?,
+                DrawOp::Image(image) => brush.queue_image_raw(*image)?,
+                DrawOp::NineSlice(slice) => brush.queue_nine_slice_raw(*slice)?,
+                DrawOp::FillPath(path, fill) => brush.queue_fill_path_raw(path, fill)?,
+                DrawOp::StrokePath(path, stroke) => brush.queue_stroke_path_raw(path, stroke)?,
+                DrawOp::Text(run) => brush.queue_text_raw(&run.to_section())?,
+                DrawOp::PushClip(clip) => brush.push_clip((*clip).into()),
+                DrawOp::PopClip => brush.pop_clip(),
+                DrawOp::PushTransform(transform) => brush.push_transform(*transform),
+                DrawOp::PopTransform => brush.pop_transform(),
+                DrawOp::PushLayer(layer) => brush.push_layer(*layer),
+                DrawOp::PopLayer => brush.pop_layer(),
+                DrawOp::Custom {
+                    brush: index,
+                    instance,
+                } => brush.queue_custom_bytes(*index, instance)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Lists the ops that differ from `self` in `new`.
+    /// Ops in front of and behind the first and last difference are matched up, so
+    /// inserting or removing ops doesn't report everything after them as changed.
+    pub fn diff<'a>(&'a self, new: &'a DrawList) -> Vec<DrawListChange<'a>> {
+        let (old, new) = (&self.ops, &new.ops);
+        let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+
+        let old_changed = &old[prefix..old.len() - suffix];
+        let new_changed = &new[prefix..new.len() - suffix];
+        let common = old_changed.len().min(new_changed.len());
+
+        let mut changes = Vec::new();
+        for (offset, (old, new)) in old_changed.iter().zip(new_changed).enumerate() {
+            if old != new {
+                changes.push(DrawListChange::Changed {
+                    index: prefix + offset,
+                    old,
+                    new,
+                });
+            }
+        }
+        for (offset, op) in old_changed.iter().enumerate().skip(common) {
+            changes.push(DrawListChange::Removed {
+                index: prefix + offset,
+                op,
+            });
+        }
+        for (offset, op) in new_changed.iter().enumerate().skip(common) {
+            changes.push(DrawListChange::Added {
+                index: prefix + offset,
+                op,
+            });
+        }
+        changes
+    }
+}
+
+impl<'a> IntoIterator for &'a DrawList {
+    type Item = &'a DrawOp;
+    type IntoIter = std::slice::Iter<'a, DrawOp>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.ops.iter()
+    }
+}
+
+// ====< TEXT >====
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextAlign {
+    #[default]
+    Start,
+    Center,
+    End,
+}
+
+/// Owned copy of a [`wgpu_glyph::Section`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextRun {
+    pub position: [f32; 2],
+    // Width and height, `None` where the text is unbounded
+    pub bounds: [Option<f32>; 2],
+    pub h_align: TextAlign,
+    pub v_align: TextAlign,
+    // Wraps words at the bounds instead of keeping everything on a single line
+    pub wrap: bool,
+    pub spans: Vec<TextSpan>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TextSpan {
+    pub text: String,
+    pub scale: [f32; 2],
+    pub color: [f32; 4],
+    pub font: usize,
+}
+
+impl TextRun {
+    pub fn to_section(&self) -> Section<'_> {
+        let h_align = match self.h_align {
+            TextAlign::Start => HorizontalAlign::Left,
+            TextAlign::Center => HorizontalAlign::Center,
+            TextAlign::End => HorizontalAlign::Right,
+        };
+        let v_align = match self.v_align {
+            TextAlign::Start => VerticalAlign::Top,
+            TextAlign::Center => VerticalAlign::Center,
+            TextAlign::End => VerticalAlign::Bottom,
+        };
+        let layout = if self.wrap {
+            Layout::default_wrap()
+        } else {
+            Layout::default_single_line()
+        };
+
+        Section::default()
+            .with_screen_position((self.position[0], self.position[1]))
+            .with_bounds((
+                self.bounds[0].unwrap_or(f32::INFINITY),
+                self.bounds[1].unwrap_or(f32::INFINITY),
+            ))
+            .with_layout(layout.h_align(h_align).v_align(v_align))
+            .with_text(
+                self.spans
+                    .iter()
+                    .map(|span| {
+                        Text::new(&span.text)
+                            .with_scale(wgpu_glyph::ab_glyph::PxScale {
+                                x: span.scale[0],
+                                y: span.scale[1],
+                            })
+                            .with_color(span.color)
+                            .with_font_id(FontId(span.font))
+                    })
+                    .collect(),
+            )
+    }
+}
+
+impl From<&Section<'_>> for TextRun {
+    fn from(section: &Section<'_>) -> Self {
+        let (wrap, h_align, v_align) = match section.layout {
+            Layout::SingleLine {
+                h_align, v_align, ..
+            } => (false, h_align, v_align),
+            Layout::Wrap {
+                h_align, v_align, ..
+            } => (true, h_align, v_align),
+        };
+        let (width, height) = section.bounds;
+        Self {
+            position: [section.screen_position.0, section.screen_position.1],
+            bounds: [
+                width.is_finite().then_some(width),
+                height.is_finite().then_some(height),
+            ],
+            h_align: match h_align {
+                HorizontalAlign::Left => TextAlign::Start,
+                HorizontalAlign::Center => TextAlign::Center,
+                HorizontalAlign::Right => TextAlign::End,
+            },
+            v_align: match v_align {
+                VerticalAlign::Top => TextAlign::Start,
+                VerticalAlign::Center => TextAlign::Center,
+                VerticalAlign::Bottom => TextAlign::End,
+            },
+            wrap,
+            spans: section
+                .text
+                .iter()
+                .map(|text| TextSpan {
+                    text: text.text.to_string(),
+                    scale: [text.scale.x, text.scale.y],
+                    color: text.extra.color,
+                    font: text.font_id.0,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use bobsics_render::{Color, SvgId, SvgQuad};
+
+use crate::{
+    widgets::{BBox, GUIEvent, UniversalBrush, Vector2},
+    Globals, Widget,
+};
+
+pub struct Icon {
+    pub svg: SvgId,
+    // Replaces `currentColor` in the SVG
+    pub color: Option<Color>,
+    pub margin: Vector2,
+
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+}
+
+impl Icon {
+    // Create a new icon from an SVG loaded with `UniversalBrush::load_svg`
+    pub fn new(svg: SvgId) -> Self {
+        Self {
+            svg,
+            color: None,
+            margin: Vector2::ZERO,
+            width: None,
+            height: None,
+        }
+    }
+
+    pub fn build(self) -> Box<Self> {
+        Box::new(self)
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_margin(mut self, margin: Vector2) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    pub fn with_size(mut self, width: f32, height: f32) -> Self {
+        self.width = Some(width);
+        self.height = Some(height);
+        self
+    }
+
+    // Size of the icon itself, without margin.
+    // Falls back to the size declared by the SVG.
+    fn icon_size(&self, scale: Vector2, brush: &UniversalBrush) -> Vector2 {
+        let (width, height) = brush.svg_size(self.svg);
+        Vector2::new(self.width.unwrap_or(width), self.height.unwrap_or(height)) * scale
+    }
+}
+
+impl Widget for Icon {
+    fn draw(
+        &self,
+        offset: Vector2,
+        scale: Vector2,
+        brush: &mut UniversalBrush,
+        globals: &Globals,
+    ) -> BBox {
+        let n_offset = offset + self.margin * scale;
+        let size = self.icon_size(scale, brush);
+
+        let mut svg = SvgQuad::new(self.svg, n_offset.into(), (n_offset + size).into());
+        svg.color = self.color;
+        brush.queue_svg_raw(svg).expect("Failed to draw icon");
+
+        self.measure(offset, scale, brush, globals)
+    }
+
+    fn measure(
+        &self,
+        offset: Vector2,
+        scale: Vector2,
+        brush: &mut UniversalBrush,
+        _globals: &Globals,
+    ) -> BBox {
+        let size = self.icon_size(scale, brush) + self.margin * scale * 2.0;
+        BBox::from_wh(offset, size)
+    }
+
+    fn handle_event(
+        &mut self,
+        _window: &winit::window::Window,
+        _brush: &mut UniversalBrush,
+        _offset: Vector2,
+        _scale: Vector2,
+        _event: &GUIEvent,
+        _globals: &Globals,
+    ) {
+        // Do nothing
+    }
+}
@@ -0,0 +1,103 @@
+use bobsics_render::{Color, ImageId, ImageQuad};
+
+use crate::{
+    widgets::{BBox, GUIEvent, UniversalBrush, Vector2},
+    Globals, Widget,
+};
+
+pub struct Image {
+    pub image: ImageId,
+    pub tint: Color,
+    pub margin: Vector2,
+
+    pub width: Option<f32>,
+    pub height: Option<f32>,
+}
+
+impl Image {
+    // Create a new image from an image loaded with `UniversalBrush::load_image`
+    pub fn new(image: ImageId) -> Self {
+        Self {
+            image,
+            tint: Color::WHITE,
+            margin: Vector2::ZERO,
+            width: None,
+            height: None,
+        }
+    }
+
+    pub fn build(self) -> Box<Self> {
+        Box::new(self)
+    }
+
+    pub fn with_tint(mut self, tint: Color) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn with_margin(mut self, margin: Vector2) -> Self {
+        self.margin = margin;
+        self
+    }
+
+    pub fn with_size(mut self, width: f32, height: f32) -> Self {
+        self.width = Some(width);
+        self.height = Some(height);
+        self
+    }
+
+    // Size of the image itself, without margin.
+    // Falls back to the size of the loaded image.
+    fn image_size(&self, scale: Vector2, brush: &UniversalBrush) -> Vector2 {
+        let (width, height) = brush.image_size(self.image);
+        Vector2::new(
+            self.width.unwrap_or(width as f32),
+            self.height.unwrap_or(height as f32),
+        ) * scale
+    }
+}
+
+impl Widget for Image {
+    fn draw(
+        &self,
+        offset: Vector2,
+        scale: Vector2,
+        brush: &mut UniversalBrush,
+        globals: &Globals,
+    ) -> BBox {
+        let n_offset = offset + self.margin * scale;
+        let size = self.image_size(scale, brush);
+
+        brush
+            .queue_image_raw(
+                ImageQuad::new(self.image, n_offset.into(), (n_offset + size).into())
+                    .with_tint(self.tint),
+            )
+            .expect("Failed to draw image");
+
+        self.measure(offset, scale, brush, globals)
+    }
+
+    fn measure(
+        &self,
+        offset: Vector2,
+        scale: Vector2,
+        brush: &mut UniversalBrush,
+        _globals: &Globals,
+    ) -> BBox {
+        let size = self.image_size(scale, brush) + self.margin * scale * 2.0;
+        BBox::from_wh(offset, size)
+    }
+
+    fn handle_event(
+        &mut self,
+        _window: &winit::window::Window,
+        _brush: &mut UniversalBrush,
+        _offset: Vector2,
+        _scale: Vector2,
+        _event: &GUIEvent,
+        _globals: &Globals,
+    ) {
+        // Do nothing
+    }
+}
@@ -1,5 +1,3 @@
-use bobsics_render::Color;
-
 use crate::{
     widgets::{BBox, GUIEvent, UniversalBrush, Vector2},
     Globals, Widget,
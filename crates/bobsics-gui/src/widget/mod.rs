@@ -1,21 +1,29 @@
-use bobsics_render::BobsicsRenderer;
+use bobsics_render::{
+    BobsicsRenderer, CustomInstance, CustomShader, RenderError, RendererConfig, Screenshot,
+};
 use winit::{
     dpi::PhysicalSize,
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
     window::{Window, WindowBuilder},
 };
 
-use self::widgets::{BBox, GUIEvent, UniversalBrush, Vector2};
+use self::widgets::{BBox, CustomBrushId, GUIEvent, UniversalBrush, Vector2};
 
 mod button;
 mod common;
+mod draw_list;
+mod icon;
+mod image;
 mod label;
 mod layout;
 
 pub mod widgets {
     pub use super::button::*;
     pub use super::common::*;
+    pub use super::draw_list::*;
+    pub use super::icon::*;
+    pub use super::image::*;
     pub use super::label::*;
     pub use super::layout::*;
 }
@@ -30,6 +38,10 @@ pub struct BobsicsGUIApp {
     pub default_screen_size: (u32, u32),
 
     pub mouse_pos: Vector2,
+
+    // Key that requests a screenshot of the next frame
+    pub screenshot_hotkey: Option<VirtualKeyCode>,
+    screenshot_handler: Option<Box<dyn FnMut(Screenshot)>>,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -64,21 +76,25 @@ pub trait Widget {
 }
 
 impl BobsicsGUIApp {
-    pub fn new(title: &str) -> Self {
+    pub fn new(title: &str) -> Result<Self, RenderError> {
+        Self::with_config(title, RendererConfig::default())
+    }
+
+    pub fn with_config(title: &str, config: RendererConfig) -> Result<Self, RenderError> {
         let event_loop = EventLoop::new();
         let window = WindowBuilder::new()
             .with_title(title)
             .with_inner_size(PhysicalSize::new(1200, 700))
-            .build(&event_loop)
-            .unwrap();
-        let renderer = pollster::block_on(BobsicsRenderer::new(&window));
+            .build(&event_loop)?;
+        let renderer = pollster::block_on(BobsicsRenderer::new_with_config(&window, config))?;
 
         let brush = UniversalBrush::new(
             &renderer.device,
             renderer.format,
+            renderer.sample_count,
             &renderer.globals_bind_group_layout,
         );
-        Self {
+        Ok(Self {
             renderer,
             window,
             event_loop: Some(event_loop),
@@ -86,7 +102,9 @@ impl BobsicsGUIApp {
             widget: None,
             brush,
             default_screen_size: (1200, 700),
-        }
+            screenshot_hotkey: None,
+            screenshot_handler: None,
+        })
     }
 
     pub fn run(mut self) -> ! {
@@ -169,6 +187,15 @@ impl BobsicsGUIApp {
                                 }
                             }
                         }
+                        WindowEvent::KeyboardInput {
+                            input:
+                                KeyboardInput {
+                                    state: ElementState::Pressed,
+                                    virtual_keycode: Some(key),
+                                    ..
+                                },
+                            ..
+                        } if Some(*key) == self.screenshot_hotkey => self.request_screenshot(),
                         WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
                         WindowEvent::Resized(physical_size) => {
                             self.renderer.resize(*physical_size);
@@ -189,11 +216,15 @@ impl BobsicsGUIApp {
                         );
                         match self.renderer.render(&mut self.brush) {
                             Ok(_) => {}
-                            Err(wgpu::SurfaceError::OutOfMemory) => {
+                            Err(RenderError::OutOfMemory) => {
+                                eprintln!("Error: {}", RenderError::OutOfMemory);
                                 *control_flow = ControlFlow::Exit
                             }
-                            Err(wgpu::SurfaceError::Outdated) => {}
-                            Err(e) => eprintln!("{e:?}"),
+                            Err(RenderError::SurfaceOutdated) => {}
+                            Err(e) => eprintln!("Error: {e}"),
+                        }
+                        if let Some(screenshot) = self.renderer.take_screenshot() {
+                            self.handle_screenshot(screenshot);
                         }
                     }
 
@@ -206,6 +237,48 @@ impl BobsicsGUIApp {
         self.widget = Some(widget);
     }
 
+    /// Compiles `shader` into a [`CustomBrush`] for the device of the app and adds it to its brush.
+    pub fn add_custom_brush<I: CustomInstance, U: bytemuck::Pod>(
+        &mut self,
+        shader: CustomShader,
+        uniforms: U,
+    ) -> Result<CustomBrushId<I, U>, RenderError> {
+        let brush = self.renderer.create_custom_brush(shader, uniforms)?;
+        self.brush.add_custom_brush(brush)
+    }
+
+    /// Captures the next frame, which is passed to the screenshot handler once it is drawn.
+    pub fn request_screenshot(&mut self) {
+        self.renderer.request_screenshot();
+        self.window.request_redraw();
+    }
+
+    /// Pressing `key` requests a screenshot, `None` disables the hotkey.
+    pub fn set_screenshot_hotkey(&mut self, key: Option<VirtualKeyCode>) {
+        self.screenshot_hotkey = key;
+    }
+
+    /// Replaces the default handler, which saves screenshots as PNG files in the working directory.
+    pub fn set_screenshot_handler(&mut self, handler: impl FnMut(Screenshot) + 'static) {
+        self.screenshot_handler = Some(Box::new(handler));
+    }
+
+    fn handle_screenshot(&mut self, screenshot: Screenshot) {
+        if let Some(handler) = &mut self.screenshot_handler {
+            handler(screenshot);
+            return;
+        }
+
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |time| time.as_millis());
+        let path = format!("screenshot-{timestamp}.png");
+        match screenshot.save_png(&path) {
+            Ok(()) => println!("Saved screenshot to {path}"),
+            Err(e) => eprintln!("Error: {e}"),
+        }
+    }
+
     pub fn draw_widget(&mut self, globals: &Globals, scale_factor: Vector2) {
         if self.widget.is_none() {
             println!("No widget set");
@@ -218,3 +291,83 @@ impl BobsicsGUIApp {
             .into();
     }
 }
+
+/// Renders widget trees into an offscreen texture, without a window or a display.
+/// Useful for CI and golden-image tests.
+pub struct BobsicsHeadlessApp {
+    pub renderer: BobsicsRenderer,
+    pub widget: Option<Box<dyn Widget>>,
+    pub brush: UniversalBrush,
+}
+
+impl BobsicsHeadlessApp {
+    pub fn new(width: u32, height: u32) -> Result<Self, RenderError> {
+        Self::with_config(width, height, RendererConfig::default())
+    }
+
+    pub fn with_config(
+        width: u32,
+        height: u32,
+        config: RendererConfig,
+    ) -> Result<Self, RenderError> {
+        let renderer = pollster::block_on(BobsicsRenderer::new_headless_with_config(
+            width, height, config,
+        ))?;
+
+        let brush = UniversalBrush::new(
+            &renderer.device,
+            renderer.format,
+            renderer.sample_count,
+            &renderer.globals_bind_group_layout,
+        );
+        Ok(Self {
+            renderer,
+            widget: None,
+            brush,
+        })
+    }
+
+    pub fn set_widget(&mut self, widget: Box<dyn Widget>) {
+        self.widget = Some(widget);
+    }
+
+    /// Compiles `shader` into a [`CustomBrush`] for the device of the app and adds it to its brush.
+    pub fn add_custom_brush<I: CustomInstance, U: bytemuck::Pod>(
+        &mut self,
+        shader: CustomShader,
+        uniforms: U,
+    ) -> Result<CustomBrushId<I, U>, RenderError> {
+        let brush = self.renderer.create_custom_brush(shader, uniforms)?;
+        self.brush.add_custom_brush(brush)
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.renderer.resize(PhysicalSize::new(width, height));
+    }
+
+    /// Draws the widget tree and returns the frame as tightly packed RGBA8 pixels.
+    pub fn render_frame(&mut self) -> Result<Vec<u8>, RenderError> {
+        let globals = Globals {
+            screen_size: (self.renderer.width(), self.renderer.height()),
+        };
+        if let Some(widget) = &self.widget {
+            widget.draw(Vector2::ZERO, Vector2::UNIT, &mut self.brush, &globals);
+        }
+
+        self.renderer.render(&mut self.brush)?;
+        Ok(self
+            .renderer
+            .read_frame()
+            .expect("Headless renderer has no offscreen frame"))
+    }
+
+    /// Like [`BobsicsHeadlessApp::render_frame`], but returns a [`Screenshot`] that can be saved as PNG.
+    pub fn screenshot(&mut self) -> Result<Screenshot, RenderError> {
+        let pixels = self.render_frame()?;
+        Ok(Screenshot::new(
+            self.renderer.width(),
+            self.renderer.height(),
+            pixels,
+        ))
+    }
+}
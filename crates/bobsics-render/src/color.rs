@@ -0,0 +1,577 @@
+use std::{fmt, str::FromStr};
+
+/// RGBA color, stored as linear RGB with straight (not premultiplied) alpha.
+///
+/// Colors written by hand, in CSS or in design tools are sRGB encoded, use the `srgb`/`hex`
+/// constructors for those. HSL and HSV work on the sRGB encoded channels like in CSS.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Color {
+    r: f32,
+    g: f32,
+    b: f32,
+    a: f32,
+}
+
+impl Color {
+    pub const TRANSPARENT: Self = Self::from_linear_rgba(0.0, 0.0, 0.0, 0.0);
+    pub const BLACK: Self = Self::from_linear_rgba(0.0, 0.0, 0.0, 1.0);
+    pub const WHITE: Self = Self::from_linear_rgba(1.0, 1.0, 1.0, 1.0);
+    pub const RED: Self = Self::from_linear_rgba(1.0, 0.0, 0.0, 1.0);
+
+    // ====< CONSTRUCTORS >====
+
+    /// Same as [`Color::from_linear_rgba`].
+    pub const fn from_rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self::from_linear_rgba(r, g, b, a)
+    }
+
+    pub const fn from_linear_rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Channels in `0.0..=1.0`, sRGB encoded.
+    pub fn from_srgba(r: f32, g: f32, b: f32, a: f32) -> Self {
+        Self::from_linear_rgba(srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a)
+    }
+
+    /// sRGB encoded 8 bit channels, like `rgba(r, g, b, a / 255)` in CSS.
+    pub fn from_srgba8(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self::from_srgba(
+            r as f32 / 255.0,
+            g as f32 / 255.0,
+            b as f32 / 255.0,
+            a as f32 / 255.0,
+        )
+    }
+
+    /// Opaque sRGB color from `0xRRGGBB`.
+    pub fn from_hex(hex: u32) -> Self {
+        Self::from_srgba8((hex >> 16) as u8, (hex >> 8) as u8, hex as u8, 255)
+    }
+
+    /// sRGB color from `0xRRGGBBAA`.
+    pub fn from_hex_rgba(hex: u32) -> Self {
+        Self::from_srgba8(
+            (hex >> 24) as u8,
+            (hex >> 16) as u8,
+            (hex >> 8) as u8,
+            hex as u8,
+        )
+    }
+
+    /// Hue in degrees, saturation and lightness in `0.0..=1.0`.
+    pub fn from_hsl(hue: f32, saturation: f32, lightness: f32) -> Self {
+        let s = saturation.clamp(0.0, 1.0);
+        let l = lightness.clamp(0.0, 1.0);
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        Self::from_hue_chroma(hue, chroma, l - chroma / 2.0)
+    }
+
+    /// Hue in degrees, saturation and value in `0.0..=1.0`.
+    pub fn from_hsv(hue: f32, saturation: f32, value: f32) -> Self {
+        let s = saturation.clamp(0.0, 1.0);
+        let v = value.clamp(0.0, 1.0);
+        let chroma = v * s;
+        Self::from_hue_chroma(hue, chroma, v - chroma)
+    }
+
+    /// Perceptual lightness in `0.0..=1.0`, chroma (about `0.0..=0.4`) and hue in degrees.
+    /// Colors outside of the sRGB gamut are clamped.
+    pub fn from_oklch(lightness: f32, chroma: f32, hue: f32) -> Self {
+        let (sin, cos) = hue.to_radians().sin_cos();
+        Self::from_oklab(lightness, chroma * cos, chroma * sin, 1.0)
+    }
+
+    /// Parses a CSS color, see the [`FromStr`] implementation.
+    pub fn parse(css: &str) -> Result<Self, ParseColorError> {
+        css.parse()
+    }
+
+    pub fn with_alpha(&self, alpha: f32) -> Self {
+        Self {
+            r: self.r,
+            g: self.g,
+            b: self.b,
+            a: alpha,
+        }
+    }
+
+    // ====< ACCESSORS >====
+
+    /// sRGB encoded red channel.
+    pub fn r(&self) -> u8 {
+        to_u8(linear_to_srgb(self.r))
+    }
+    /// sRGB encoded green channel.
+    pub fn g(&self) -> u8 {
+        to_u8(linear_to_srgb(self.g))
+    }
+    /// sRGB encoded blue channel.
+    pub fn b(&self) -> u8 {
+        to_u8(linear_to_srgb(self.b))
+    }
+    pub fn a(&self) -> u8 {
+        to_u8(self.a)
+    }
+
+    pub fn to_linear_rgba(&self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    pub fn to_srgba(&self) -> [f32; 4] {
+        [
+            linear_to_srgb(self.r),
+            linear_to_srgb(self.g),
+            linear_to_srgb(self.b),
+            self.a,
+        ]
+    }
+
+    pub fn to_srgba8(&self) -> [u8; 4] {
+        [self.r(), self.g(), self.b(), self.a()]
+    }
+
+    /// sRGB color as `0xRRGGBBAA`.
+    pub fn to_hex_rgba(&self) -> u32 {
+        u32::from_be_bytes(self.to_srgba8())
+    }
+
+    /// `[hue, saturation, lightness]`, hue in degrees.
+    pub fn to_hsl(&self) -> [f32; 3] {
+        let [r, g, b, _] = self.to_srgba();
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let chroma = max - min;
+        let l = (max + min) / 2.0;
+        let s = if l <= 0.0 || l >= 1.0 {
+            0.0
+        } else {
+            chroma / (1.0 - (2.0 * l - 1.0).abs())
+        };
+        [hue(r, g, b, max, chroma), s, l]
+    }
+
+    /// `[hue, saturation, value]`, hue in degrees.
+    pub fn to_hsv(&self) -> [f32; 3] {
+        let [r, g, b, _] = self.to_srgba();
+        let max = r.max(g).max(b);
+        let chroma = max - r.min(g).min(b);
+        let s = if max <= 0.0 { 0.0 } else { chroma / max };
+        [hue(r, g, b, max, chroma), s, max]
+    }
+
+    /// `[lightness, chroma, hue]`, hue in degrees.
+    pub fn to_oklch(&self) -> [f32; 3] {
+        let [l, a, b] = self.to_oklab();
+        let hue = b.atan2(a).to_degrees();
+        [l, a.hypot(b), hue.rem_euclid(360.0)]
+    }
+
+    // ====< COLOR MATH >====
+
+    /// Interpolates in Oklab, which keeps the lightness and hue of gradients and animations even.
+    pub fn lerp(&self, other: Color, t: f32) -> Self {
+        let from = self.to_oklab();
+        let to = other.to_oklab();
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        Self::from_oklab(
+            mix(from[0], to[0]),
+            mix(from[1], to[1]),
+            mix(from[2], to[2]),
+            mix(self.a, other.a),
+        )
+    }
+
+    /// Interpolates the linear channels, like blending on the GPU does.
+    pub fn lerp_linear(&self, other: Color, t: f32) -> Self {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        Self::from_linear_rgba(
+            mix(self.r, other.r),
+            mix(self.g, other.g),
+            mix(self.b, other.b),
+            mix(self.a, other.a),
+        )
+    }
+
+    /// Raises the perceptual (OKLCH) lightness by `amount`, keeping hue and chroma.
+    pub fn lighten(&self, amount: f32) -> Self {
+        let [l, a, b] = self.to_oklab();
+        Self::from_oklab((l + amount).clamp(0.0, 1.0), a, b, self.a)
+    }
+
+    pub fn darken(&self, amount: f32) -> Self {
+        self.lighten(-amount)
+    }
+
+    /// Relative luminance as defined by WCAG 2, alpha is ignored.
+    pub fn relative_luminance(&self) -> f32 {
+        let c = |x: f32| x.clamp(0.0, 1.0);
+        0.2126 * c(self.r) + 0.7152 * c(self.g) + 0.0722 * c(self.b)
+    }
+
+    /// WCAG 2 contrast ratio between `1.0` and `21.0`. Text needs at least 4.5 (3.0 when large).
+    pub fn contrast_ratio(&self, other: Color) -> f32 {
+        let a = self.relative_luminance();
+        let b = other.relative_luminance();
+        (a.max(b) + 0.05) / (a.min(b) + 0.05)
+    }
+
+    // ====< HELPERS >====
+
+    fn from_hue_chroma(hue: f32, chroma: f32, m: f32) -> Self {
+        let h = hue.rem_euclid(360.0) / 60.0;
+        let x = chroma * (1.0 - (h % 2.0 - 1.0).abs());
+        let (r, g, b) = match h as u32 {
+            0 => (chroma, x, 0.0),
+            1 => (x, chroma, 0.0),
+            2 => (0.0, chroma, x),
+            3 => (0.0, x, chroma),
+            4 => (x, 0.0, chroma),
+            _ => (chroma, 0.0, x),
+        };
+        Self::from_srgba(r + m, g + m, b + m, 1.0)
+    }
+
+    // https://bottosson.github.io/posts/oklab/
+    fn to_oklab(self) -> [f32; 3] {
+        let l = 0.412_221_46 * self.r + 0.536_332_55 * self.g + 0.051_445_995 * self.b;
+        let m = 0.211_903_5 * self.r + 0.680_699_5 * self.g + 0.107_396_96 * self.b;
+        let s = 0.088_302_46 * self.r + 0.281_718_85 * self.g + 0.629_978_7 * self.b;
+        let (l, m, s) = (l.cbrt(), m.cbrt(), s.cbrt());
+        [
+            0.210_454_26 * l + 0.793_617_8 * m - 0.004_072_047 * s,
+            1.977_998_5 * l - 2.428_592_2 * m + 0.450_593_7 * s,
+            0.025_904_037 * l + 0.782_771_77 * m - 0.808_675_77 * s,
+        ]
+    }
+
+    fn from_oklab(lightness: f32, a: f32, b: f32, alpha: f32) -> Self {
+        let l = (lightness + 0.396_337_78 * a + 0.215_803_76 * b).powi(3);
+        let m = (lightness - 0.105_561_346 * a - 0.063_854_17 * b).powi(3);
+        let s = (lightness - 0.089_484_18 * a - 1.291_485_5 * b).powi(3);
+        let c = |x: f32| x.clamp(0.0, 1.0);
+        Self::from_linear_rgba(
+            c(4.076_741_7 * l - 3.307_711_6 * m + 0.230_969_94 * s),
+            c(-1.268_438 * l + 2.609_757_4 * m - 0.341_319_38 * s),
+            c(-0.004_196_086_3 * l - 0.703_418_6 * m + 1.707_614_7 * s),
+            alpha,
+        )
+    }
+}
+
+impl From<Color> for [f32; 4] {
+    fn from(val: Color) -> Self {
+        [val.r, val.g, val.b, val.a]
+    }
+}
+
+impl From<Color> for wgpu::Color {
+    fn from(val: Color) -> Self {
+        wgpu::Color {
+            r: val.r as f64,
+            g: val.g as f64,
+            b: val.b as f64,
+            a: val.a as f64,
+        }
+    }
+}
+
+pub fn srgb_to_linear(x: f32) -> f32 {
+    if x > 0.04045 {
+        ((x + 0.055) / 1.055).powf(2.4)
+    } else {
+        x / 12.92
+    }
+}
+
+pub fn linear_to_srgb(x: f32) -> f32 {
+    if x > 0.003_130_8 {
+        1.055 * x.powf(1.0 / 2.4) - 0.055
+    } else {
+        x * 12.92
+    }
+}
+
+fn to_u8(x: f32) -> u8 {
+    (x.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+fn hue(r: f32, g: f32, b: f32, max: f32, chroma: f32) -> f32 {
+    if chroma <= 0.0 {
+        return 0.0;
+    }
+    let h = if max == r {
+        (g - b) / chroma
+    } else if max == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+    (h * 60.0).rem_euclid(360.0)
+}
+
+// ====< CSS PARSING >====
+
+/// A string that isn't a supported CSS color.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseColorError {
+    pub input: String,
+}
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` is not a valid CSS color", self.input)
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+/// Supports `#rgb`, `#rgba`, `#rrggbb`, `#rrggbbaa`, `rgb()`, `rgba()`, `hsl()`, `hsla()`,
+/// `oklch()` and the CSS named colors, with comma or space separated arguments.
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let css = input.trim().to_ascii_lowercase();
+        let color = if let Some(hex) = css.strip_prefix('#') {
+            parse_hex(hex)
+        } else if let Some((function, args)) =
+            css.strip_suffix(')').and_then(|css| css.split_once('('))
+        {
+            parse_function(function.trim(), args)
+        } else {
+            named_color(&css)
+        };
+        color.ok_or_else(|| ParseColorError {
+            input: input.to_string(),
+        })
+    }
+}
+
+fn parse_hex(hex: &str) -> Option<Color> {
+    if !hex.bytes().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    let value = u32::from_str_radix(hex, 16).ok()?;
+    // Expands the short forms, 0xRGB to 0xRRGGBB
+    let expand = |v: u32, digits: u32| {
+        (0..digits).rev().fold(0, |acc, i| {
+            let d = (v >> (i * 4)) & 0xF;
+            (acc << 8) | (d << 4) | d
+        })
+    };
+    match hex.len() {
+        3 => Some(Color::from_hex(expand(value, 3))),
+        4 => Some(Color::from_hex_rgba(expand(value, 4))),
+        6 => Some(Color::from_hex(value)),
+        8 => Some(Color::from_hex_rgba(value)),
+        _ => None,
+    }
+}
+
+fn parse_function(function: &str, args: &str) -> Option<Color> {
+    let args: Vec<&str> = args
+        .split(|c: char| c == ',' || c == '/' || c.is_whitespace())
+        .filter(|arg| !arg.is_empty())
+        .collect();
+    let alpha = match args.len() {
+        3 => 1.0,
+        4 => parse_number(args[3], 1.0)?,
+        _ => return None,
+    };
+    let color = match function {
+        "rgb" | "rgba" => Color::from_srgba(
+            parse_number(args[0], 255.0)? / 255.0,
+            parse_number(args[1], 255.0)? / 255.0,
+            parse_number(args[2], 255.0)? / 255.0,
+            1.0,
+        ),
+        "hsl" | "hsla" => Color::from_hsl(
+            parse_hue(args[0])?,
+            parse_number(args[1], 1.0)?,
+            parse_number(args[2], 1.0)?,
+        ),
+        "oklch" => Color::from_oklch(
+            parse_number(args[0], 1.0)?,
+            parse_number(args[1], 0.4)?,
+            parse_hue(args[2])?,
+        ),
+        _ => return None,
+    };
+    Some(color.with_alpha(alpha.clamp(0.0, 1.0)))
+}
+
+/// Parses a number, percentages are relative to `full`.
+fn parse_number(arg: &str, full: f32) -> Option<f32> {
+    match arg.strip_suffix('%') {
+        Some(percent) => Some(percent.parse::<f32>().ok()? / 100.0 * full),
+        None => arg.parse().ok(),
+    }
+}
+
+fn parse_hue(arg: &str) -> Option<f32> {
+    arg.strip_suffix("deg").unwrap_or(arg).parse().ok()
+}
+
+fn named_color(name: &str) -> Option<Color> {
+    if name == "transparent" {
+        return Some(Color::TRANSPARENT);
+    }
+    NAMED_COLORS
+        .binary_search_by(|(named, _)| named.cmp(&name))
+        .ok()
+        .map(|index| Color::from_hex(NAMED_COLORS[index].1))
+}
+
+// Sorted by name, for the binary search
+const NAMED_COLORS: &[(&str, u32)] = &[
+    ("aliceblue", 0xf0f8ff),
+    ("antiquewhite", 0xfaebd7),
+    ("aqua", 0x00ffff),
+    ("aquamarine", 0x7fffd4),
+    ("azure", 0xf0ffff),
+    ("beige", 0xf5f5dc),
+    ("bisque", 0xffe4c4),
+    ("black", 0x000000),
+    ("blanchedalmond", 0xffebcd),
+    ("blue", 0x0000ff),
+    ("blueviolet", 0x8a2be2),
+    ("brown", 0xa52a2a),
+    ("burlywood", 0xdeb887),
+    ("cadetblue", 0x5f9ea0),
+    ("chartreuse", 0x7fff00),
+    ("chocolate", 0xd2691e),
+    ("coral", 0xff7f50),
+    ("cornflowerblue", 0x6495ed),
+    ("cornsilk", 0xfff8dc),
+    ("crimson", 0xdc143c),
+    ("cyan", 0x00ffff),
+    ("darkblue", 0x00008b),
+    ("darkcyan", 0x008b8b),
+    ("darkgoldenrod", 0xb8860b),
+    ("darkgray", 0xa9a9a9),
+    ("darkgreen", 0x006400),
+    ("darkgrey", 0xa9a9a9),
+    ("darkkhaki", 0xbdb76b),
+    ("darkmagenta", 0x8b008b),
+    ("darkolivegreen", 0x556b2f),
+    ("darkorange", 0xff8c00),
+    ("darkorchid", 0x9932cc),
+    ("darkred", 0x8b0000),
+    ("darksalmon", 0xe9967a),
+    ("darkseagreen", 0x8fbc8f),
+    ("darkslateblue", 0x483d8b),
+    ("darkslategray", 0x2f4f4f),
+    ("darkslategrey", 0x2f4f4f),
+    ("darkturquoise", 0x00ced1),
+    ("darkviolet", 0x9400d3),
+    ("deeppink", 0xff1493),
+    ("deepskyblue", 0x00bfff),
+    ("dimgray", 0x696969),
+    ("dimgrey", 0x696969),
+    ("dodgerblue", 0x1e90ff),
+    ("firebrick", 0xb22222),
+    ("floralwhite", 0xfffaf0),
+    ("forestgreen", 0x228b22),
+    ("fuchsia", 0xff00ff),
+    ("gainsboro", 0xdcdcdc),
+    ("ghostwhite", 0xf8f8ff),
+    ("gold", 0xffd700),
+    ("goldenrod", 0xdaa520),
+    ("gray", 0x808080),
+    ("green", 0x008000),
+    ("greenyellow", 0xadff2f),
+    ("grey", 0x808080),
+    ("honeydew", 0xf0fff0),
+    ("hotpink", 0xff69b4),
+    ("indianred", 0xcd5c5c),
+    ("indigo", 0x4b0082),
+    ("ivory", 0xfffff0),
+    ("khaki", 0xf0e68c),
+    ("lavender", 0xe6e6fa),
+    ("lavenderblush", 0xfff0f5),
+    ("lawngreen", 0x7cfc00),
+    ("lemonchiffon", 0xfffacd),
+    ("lightblue", 0xadd8e6),
+    ("lightcoral", 0xf08080),
+    ("lightcyan", 0xe0ffff),
+    ("lightgoldenrodyellow", 0xfafad2),
+    ("lightgray", 0xd3d3d3),
+    ("lightgreen", 0x90ee90),
+    ("lightgrey", 0xd3d3d3),
+    ("lightpink", 0xffb6c1),
+    ("lightsalmon", 0xffa07a),
+    ("lightseagreen", 0x20b2aa),
+    ("lightskyblue", 0x87cefa),
+    ("lightslategray", 0x778899),
+    ("lightslategrey", 0x778899),
+    ("lightsteelblue", 0xb0c4de),
+    ("lightyellow", 0xffffe0),
+    ("lime", 0x00ff00),
+    ("limegreen", 0x32cd32),
+    ("linen", 0xfaf0e6),
+    ("magenta", 0xff00ff),
+    ("maroon", 0x800000),
+    ("mediumaquamarine", 0x66cdaa),
+    ("mediumblue", 0x0000cd),
+    ("mediumorchid", 0xba55d3),
+    ("mediumpurple", 0x9370db),
+    ("mediumseagreen", 0x3cb371),
+    ("mediumslateblue", 0x7b68ee),
+    ("mediumspringgreen", 0x00fa9a),
+    ("mediumturquoise", 0x48d1cc),
+    ("mediumvioletred", 0xc71585),
+    ("midnightblue", 0x191970),
+    ("mintcream", 0xf5fffa),
+    ("mistyrose", 0xffe4e1),
+    ("moccasin", 0xffe4b5),
+    ("navajowhite", 0xffdead),
+    ("navy", 0x000080),
+    ("oldlace", 0xfdf5e6),
+    ("olive", 0x808000),
+    ("olivedrab", 0x6b8e23),
+    ("orange", 0xffa500),
+    ("orangered", 0xff4500),
+    ("orchid", 0xda70d6),
+    ("palegoldenrod", 0xeee8aa),
+    ("palegreen", 0x98fb98),
+    ("paleturquoise", 0xafeeee),
+    ("palevioletred", 0xdb7093),
+    ("papayawhip", 0xffefd5),
+    ("peachpuff", 0xffdab9),
+    ("peru", 0xcd853f),
+    ("pink", 0xffc0cb),
+    ("plum", 0xdda0dd),
+    ("powderblue", 0xb0e0e6),
+    ("purple", 0x800080),
+    ("rebeccapurple", 0x663399),
+    ("red", 0xff0000),
+    ("rosybrown", 0xbc8f8f),
+    ("royalblue", 0x4169e1),
+    ("saddlebrown", 0x8b4513),
+    ("salmon", 0xfa8072),
+    ("sandybrown", 0xf4a460),
+    ("seagreen", 0x2e8b57),
+    ("seashell", 0xfff5ee),
+    ("sienna", 0xa0522d),
+    ("silver", 0xc0c0c0),
+    ("skyblue", 0x87ceeb),
+    ("slateblue", 0x6a5acd),
+    ("slategray", 0x708090),
+    ("slategrey", 0x708090),
+    ("snow", 0xfffafa),
+    ("springgreen", 0x00ff7f),
+    ("steelblue", 0x4682b4),
+    ("tan", 0xd2b48c),
+    ("teal", 0x008080),
+    ("thistle", 0xd8bfd8),
+    ("tomato", 0xff6347),
+    ("turquoise", 0x40e0d0),
+    ("violet", 0xee82ee),
+    ("wheat", 0xf5deb3),
+    ("white", 0xffffff),
+    ("whitesmoke", 0xf5f5f5),
+    ("yellow", 0xffff00),
+    ("yellowgreen", 0x9acd32),
+];
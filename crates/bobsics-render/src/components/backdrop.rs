@@ -0,0 +1,670 @@
+use std::ops::Range;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{CommandEncoder, Device, TextureFormat, TextureView};
+
+use crate::{ClipRect, FrameTarget, RenderError, Transform};
+
+use super::{instances::InstanceBuffer, QuadBatch};
+
+const INITIAL_BACKDROP_CAPACITY: usize = 16;
+
+/// Frosted glass: blurs whatever was drawn behind a rounded rect, and tints it.
+/// Drawing one needs a copy of the target, so backdrops are a lot more expensive than quads.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Backdrop {
+    pub top_left: [f32; 2],
+    pub bottom_right: [f32; 2],
+    // Top left, top right, bottom right and bottom left corner, like `Quad::border_radius`
+    pub border_radius: [f32; 4],
+
+    // Like `Shadow::blur_radius`, 0 just tints the backdrop
+    pub blur_radius: f32,
+    // Mixed over the blurred backdrop by its alpha
+    pub tint: [f32; 4],
+    // Applied to the backdrop before the transform of the brush, like `Quad::transform`
+    pub transform: Transform,
+}
+
+impl Default for Backdrop {
+    fn default() -> Self {
+        Self {
+            transform: Transform::IDENTITY,
+            ..Self::zeroed()
+        }
+    }
+}
+
+impl Backdrop {
+    const ATTRIBS: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x4,
+        3 => Float32,
+        4 => Float32x4,
+        5 => Float32x2,
+        6 => Float32x2,
+        7 => Float32x2
+    ];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    pub fn new(top_left: [f32; 2], bottom_right: [f32; 2], blur_radius: f32) -> Self {
+        Self {
+            top_left,
+            bottom_right,
+            blur_radius,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_border_radius(mut self, border_radius: [f32; 4]) -> Self {
+        self.border_radius = border_radius;
+        self
+    }
+
+    pub fn with_tint(mut self, tint: [f32; 4]) -> Self {
+        self.tint = tint;
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    // Standard deviation of the blur in target pixels
+    fn sigma(&self) -> f32 {
+        (self.blur_radius / 2.0 * self.transform.scale_factor()).max(0.0)
+    }
+}
+
+// One direction of the separable blur behind a backdrop
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct BlurPass {
+    // One pixel along the direction of the pass, in uv space
+    step: [f32; 2],
+    sigma: f32,
+}
+
+impl BlurPass {
+    const ATTRIBS: [wgpu::VertexAttribute; 2] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32
+    ];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+// ====< TEXTURES >====
+// The copy of the target is blurred horizontally into `intermediate`, and back vertically
+#[derive(Debug)]
+struct BackdropTextures {
+    width: u32,
+    height: u32,
+    source: wgpu::Texture,
+    source_view: TextureView,
+    source_bind_group: wgpu::BindGroup,
+    intermediate_view: TextureView,
+    intermediate_bind_group: wgpu::BindGroup,
+}
+
+#[derive(Debug)]
+pub struct BackdropPipeline {
+    instances: InstanceBuffer<Backdrop>,
+    blur_passes: InstanceBuffer<BlurPass>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    blur_pipeline: wgpu::RenderPipeline,
+    pipeline: wgpu::RenderPipeline,
+
+    format: TextureFormat,
+    textures: Option<BackdropTextures>,
+}
+
+impl BackdropPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        // Create shader
+        let shader = device.create_shader_module(include_shader!("backdrop.wgsl"));
+
+        // Create buffers
+        let instance_buffer = InstanceBuffer::new(
+            device,
+            "Backdrop instance buffer",
+            INITIAL_BACKDROP_CAPACITY,
+        );
+        let blur_pass_buffer = InstanceBuffer::new(
+            device,
+            "Backdrop blur pass buffer",
+            INITIAL_BACKDROP_CAPACITY * 2,
+        );
+
+        // Create texture bindings
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Backdrop bind group layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Backdrop sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        // Create pipelines
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Backdrop Pipeline Layout"),
+                bind_group_layouts: &[globals_bind_group_layout, &bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        // The blur passes draw into the single sampled backdrop textures
+        let blur_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Backdrop blur pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_blur",
+                buffers: &[BlurPass::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_blur",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Backdrop Render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Backdrop::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self {
+            instances: instance_buffer,
+            blur_passes: blur_pass_buffer,
+            bind_group_layout,
+            sampler,
+            blur_pipeline,
+            pipeline: render_pipeline,
+            format,
+            textures: None,
+        }
+    }
+
+    /// Uploads all `instances` at once and sizes the textures to the target, so each backdrop
+    /// can be blurred with [`BackdropPipeline::blur`] and drawn with [`BackdropPipeline::draw_prepared`].
+    /// Returns `false` if they don't fit into a single buffer.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        width: u32,
+        height: u32,
+        instances: &[Backdrop],
+    ) -> bool {
+        if instances.is_empty() {
+            return true;
+        }
+        self.instances.reserve(device, instances.len());
+        self.blur_passes.reserve(device, instances.len() * 2);
+        if instances.len() > self.instances.capacity()
+            || instances.len() * 2 > self.blur_passes.capacity()
+        {
+            return false;
+        }
+
+        // Textures follow the size of the target
+        if !matches!(&self.textures, Some(textures) if textures.width == width && textures.height == height)
+        {
+            self.textures = Some(self.create_textures(device, width, height));
+        }
+
+        let step = [1.0 / width.max(1) as f32, 1.0 / height.max(1) as f32];
+        let blur_passes: Vec<BlurPass> = instances
+            .iter()
+            .flat_map(|backdrop| {
+                let sigma = backdrop.sigma();
+                [
+                    BlurPass {
+                        step: [step[0], 0.0],
+                        sigma,
+                    },
+                    BlurPass {
+                        step: [0.0, step[1]],
+                        sigma,
+                    },
+                ]
+            })
+            .collect();
+
+        self.instances
+            .write(device, staging_belt, encoder, instances);
+        self.blur_passes
+            .write(device, staging_belt, encoder, &blur_passes);
+        true
+    }
+
+    /// The texture the target has to be copied into before blurring, once prepared.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.textures.as_ref().map(|textures| &textures.source)
+    }
+
+    /// Blurs the copy of the target behind the backdrop at `index`, within the scissor rects
+    /// `[x, y, width, height]` of the horizontal and the vertical pass.
+    pub fn blur(
+        &self,
+        encoder: &mut wgpu::CommandEncoder,
+        index: usize,
+        horizontal: [u32; 4],
+        vertical: [u32; 4],
+        globals_bind_group: &wgpu::BindGroup,
+    ) {
+        let Some(textures) = &self.textures else {
+            return;
+        };
+        if index * 2 + 1 >= self.blur_passes.capacity() {
+            return;
+        }
+
+        let passes = [
+            (
+                &textures.intermediate_view,
+                &textures.source_bind_group,
+                horizontal,
+            ),
+            (
+                &textures.source_view,
+                &textures.intermediate_bind_group,
+                vertical,
+            ),
+        ];
+        for (pass, (view, source, [x, y, width, height])) in passes.into_iter().enumerate() {
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Backdrop blur pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            rpass.set_pipeline(&self.blur_pipeline);
+            rpass.set_bind_group(0, globals_bind_group, &[]);
+            rpass.set_bind_group(1, source, &[]);
+            rpass.set_vertex_buffer(0, self.blur_passes.slice());
+            rpass.set_scissor_rect(x, y, width, height);
+
+            // A single triangle covering the whole target
+            let instance = (index * 2 + pass) as u32;
+            rpass.draw(0..3, instance..instance + 1);
+        }
+    }
+
+    /// Records the batches into `rpass`, after [`BackdropPipeline::blur`] blurred what is behind them.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        batches: &[QuadBatch],
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        let Some(textures) = &self.textures else {
+            return;
+        };
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals_bind_group, &[]);
+        rpass.set_bind_group(1, &textures.source_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.instances.slice());
+
+        for batch in batches {
+            let start = batch.range.start;
+            let end = batch.range.end.min(self.instances.capacity());
+            if start >= end {
+                continue;
+            }
+
+            // Restrict drawing to the clip rect of the batch
+            let [x, y, width, height] = match batch.clip {
+                Some(clip) => match clip.to_scissor(target_size.0, target_size.1) {
+                    Some(scissor) => scissor,
+                    None => continue,
+                },
+                None => [0, 0, target_size.0, target_size.1],
+            };
+            rpass.set_scissor_rect(x, y, width, height);
+
+            rpass.draw(0..4, start as u32..end as u32);
+        }
+    }
+
+    fn create_textures(&self, device: &Device, width: u32, height: u32) -> BackdropTextures {
+        let create = |label| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.format,
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::TEXTURE_BINDING
+                    | wgpu::TextureUsages::COPY_DST,
+            })
+        };
+        let bind_group = |view| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Backdrop bind group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+            })
+        };
+
+        let source = create("Backdrop texture");
+        let source_view = source.create_view(&wgpu::TextureViewDescriptor::default());
+        let intermediate_view = create("Backdrop intermediate texture")
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        BackdropTextures {
+            width,
+            height,
+            source_bind_group: bind_group(&source_view),
+            intermediate_bind_group: bind_group(&intermediate_view),
+            source,
+            source_view,
+            intermediate_view,
+        }
+    }
+}
+
+// ====< BRUSH >====
+/// Every backdrop is a batch of its own, as each one blurs what was drawn right before it.
+pub struct BackdropBrush {
+    pub pipeline: BackdropPipeline,
+    pub queue: Vec<Backdrop>,
+    pub batches: Vec<QuadBatch>,
+
+    clip: Option<ClipRect>,
+    transform: Transform,
+}
+
+impl BackdropBrush {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        Self {
+            pipeline: BackdropPipeline::new(
+                device,
+                format,
+                sample_count,
+                globals_bind_group_layout,
+            ),
+            queue: Vec::new(),
+            batches: Vec::new(),
+            clip: None,
+            transform: Transform::IDENTITY,
+        }
+    }
+
+    /// Creates the pipeline again on a new device, e.g. after the old one was lost.
+    /// Everything queued so far is kept.
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        self.pipeline =
+            BackdropPipeline::new(device, format, sample_count, globals_bind_group_layout);
+    }
+
+    /// Sets the clip rect for all backdrops queued from now on.
+    pub fn set_clip(&mut self, clip: Option<ClipRect>) {
+        self.clip = clip;
+    }
+
+    /// Sets the transform for all backdrops queued from now on, applied after their own transform.
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    pub fn queue_backdrop(&mut self, mut backdrop: Backdrop) {
+        backdrop.transform = backdrop.transform.then(&self.transform);
+        let index = self.queue.len();
+        self.queue.push(backdrop);
+        self.batches.push(QuadBatch {
+            clip: self.clip,
+            range: index..index + 1,
+        });
+    }
+
+    /// Draws every backdrop into `target`, each one blurring everything drawn into it before.
+    pub fn draw_queued(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        target: &mut FrameTarget,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        let (width, height) = (target.width, target.height);
+        if self.prepare(device, staging_belt, encoder, width, height) {
+            for index in 0..self.batches.len() {
+                let copied = match self.pipeline.texture() {
+                    Some(texture) => target.copy_to(encoder, texture),
+                    None => false,
+                };
+                if !copied {
+                    break;
+                }
+                self.blur(encoder, width, height, index, globals_bind_group);
+
+                let mut rpass = target.begin_render_pass(
+                    encoder,
+                    "Backdrop render pass",
+                    index + 1 == self.batches.len(),
+                );
+                self.draw_prepared(
+                    &mut rpass,
+                    width,
+                    height,
+                    index..index + 1,
+                    globals_bind_group,
+                );
+            }
+        }
+        self.clear();
+        Ok(())
+    }
+
+    /// Uploads everything queued so far, so backdrops can be blurred with [`BackdropBrush::blur`]
+    /// and drawn with [`BackdropBrush::draw_prepared`]. Returns `false` if the queue is too big for that.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        width: u32,
+        height: u32,
+    ) -> bool {
+        self.pipeline
+            .prepare(device, staging_belt, encoder, width, height, &self.queue)
+    }
+
+    /// The texture the target has to be copied into before [`BackdropBrush::blur`], once prepared.
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        self.pipeline.texture()
+    }
+
+    /// Blurs the copy of the target behind the backdrop of the batch at `index`.
+    pub fn blur(
+        &self,
+        encoder: &mut CommandEncoder,
+        width: u32,
+        height: u32,
+        index: usize,
+        globals_bind_group: &wgpu::BindGroup,
+    ) {
+        let batch = &self.batches[index];
+        let Some(backdrop) = self.queue.get(batch.range.start) else {
+            return;
+        };
+
+        // Only the part of the copy behind the backdrop is blurred. The horizontal pass also
+        // covers everything the vertical one reads, up to 3 sigma above and below.
+        let (min, max) = backdrop
+            .transform
+            .transform_rect(backdrop.top_left, backdrop.bottom_right);
+        let mut bounds = ClipRect::new(min[0], min[1], max[0] - min[0], max[1] - min[1]);
+        if let Some(clip) = batch.clip {
+            bounds = bounds.intersect(&clip);
+        }
+        let extent = (backdrop.sigma() * 3.0).ceil();
+        let expanded = ClipRect::new(
+            bounds.x,
+            bounds.y - extent,
+            bounds.width,
+            bounds.height + extent * 2.0,
+        );
+
+        if let (Some(horizontal), Some(vertical)) = (
+            expanded.to_scissor(width, height),
+            bounds.to_scissor(width, height),
+        ) {
+            self.pipeline.blur(
+                encoder,
+                batch.range.start,
+                horizontal,
+                vertical,
+                globals_bind_group,
+            );
+        }
+    }
+
+    /// Records the given range of batches into `rpass`, after [`BackdropBrush::blur`] blurred
+    /// what is behind them.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.pipeline.draw_prepared(
+            rpass,
+            (width, height),
+            &self.batches[batches],
+            globals_bind_group,
+        );
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.batches.clear();
+    }
+}
@@ -0,0 +1,702 @@
+use std::{any::Any, borrow::Cow, ops::Range};
+
+use bytemuck::Pod;
+use wgpu::{CommandEncoder, Device, TextureFormat, TextureView};
+
+use crate::{Brush, ClipRect, FrameTarget, RenderError};
+
+use super::{instances::InstanceBuffer, QuadBatch};
+
+const INITIAL_CUSTOM_CAPACITY: usize = 1_000;
+
+/// Per-instance data of a [`CustomBrush`].
+pub trait CustomInstance: Pod {
+    /// Vertex attributes of one instance, e.g. from `wgpu::vertex_attr_array!`.
+    const ATTRIBUTES: &'static [wgpu::VertexAttribute];
+}
+
+/// A WGSL shader for a [`CustomBrush`].
+///
+/// `common.wgsl` is prepended like for the built-in shaders, so `globals` is bound at
+/// group 0 and helpers like `screen_to_ndc` and `box_dist` can be used. The uniforms of
+/// the brush are bound at group 1, binding 0, unless they are `()`.
+///
+/// `vs_main` is called `vertex_count` times per instance, with the instance attributes
+/// at their locations and `@builtin(vertex_index)` counting up from 0. `fs_main` writes
+/// a single color target in the format of the renderer.
+#[derive(Debug, Clone)]
+pub struct CustomShader {
+    pub label: Cow<'static, str>,
+    pub source: Cow<'static, str>,
+    pub vertex_count: u32,
+    pub topology: wgpu::PrimitiveTopology,
+    pub blend: Option<wgpu::BlendState>,
+}
+
+impl CustomShader {
+    /// A shader drawing each instance as a 4 vertex triangle strip, with alpha blending.
+    pub fn new(label: impl Into<Cow<'static, str>>, source: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            label: label.into(),
+            source: source.into(),
+            vertex_count: 4,
+            topology: wgpu::PrimitiveTopology::TriangleStrip,
+            blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+        }
+    }
+
+    pub fn with_vertex_count(mut self, vertex_count: u32) -> Self {
+        self.vertex_count = vertex_count;
+        self
+    }
+
+    pub fn with_topology(mut self, topology: wgpu::PrimitiveTopology) -> Self {
+        self.topology = topology;
+        self
+    }
+
+    pub fn with_blend(mut self, blend: Option<wgpu::BlendState>) -> Self {
+        self.blend = blend;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct CustomPipeline<I: CustomInstance, U: Pod> {
+    instances: InstanceBuffer<I>,
+    // `None` if the brush has no uniforms
+    uniforms: Option<(wgpu::Buffer, wgpu::BindGroup)>,
+    pipeline: wgpu::RenderPipeline,
+    vertex_count: u32,
+    _uniforms: std::marker::PhantomData<U>,
+}
+
+impl<I: CustomInstance, U: Pod> CustomPipeline<I, U> {
+    /// Fails with [`RenderError::Shader`] if the shader doesn't compile or doesn't fit the layout.
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+        shader: &CustomShader,
+    ) -> Result<Self, RenderError> {
+        assert!(
+            std::mem::size_of::<I>() > 0,
+            "Custom instances can't be zero sized"
+        );
+
+        // Invalid shaders are reported instead of panicking in the error handler of the device
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipeline = Self::create(
+            device,
+            format,
+            sample_count,
+            globals_bind_group_layout,
+            shader,
+        );
+        match pollster::block_on(device.pop_error_scope()) {
+            Some(error) => Err(RenderError::Shader(error.to_string())),
+            None => Ok(pipeline),
+        }
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+        shader: &CustomShader,
+    ) -> Self {
+        // Create shader
+        let module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some(&shader.label),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(format!(
+                "{}\n{}",
+                include_str!("shaders/common.wgsl"),
+                shader.source
+            ))),
+        });
+
+        // Create buffers
+        let instance_buffer =
+            InstanceBuffer::new(device, "Custom instance buffer", INITIAL_CUSTOM_CAPACITY);
+
+        // Uniform buffers have to be a multiple of 16 bytes
+        let uniforms_size = std::mem::size_of::<U>();
+        let uniforms_layout = (uniforms_size > 0).then(|| {
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Custom uniforms bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            })
+        });
+        let uniforms = uniforms_layout.as_ref().map(|layout| {
+            let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some("Custom uniforms buffer"),
+                size: uniforms_size.next_multiple_of(16) as u64,
+                usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Custom uniforms bind group"),
+                layout,
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            });
+            (buffer, bind_group)
+        });
+
+        // Create pipeline
+        let mut bind_group_layouts = vec![globals_bind_group_layout];
+        bind_group_layouts.extend(uniforms_layout.as_ref());
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Custom Pipeline Layout"),
+                bind_group_layouts: &bind_group_layouts,
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&shader.label),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: std::mem::size_of::<I>() as wgpu::BufferAddress,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: I::ATTRIBUTES,
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: shader.blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: shader.topology,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self {
+            instances: instance_buffer,
+            uniforms,
+            pipeline: render_pipeline,
+            vertex_count: shader.vertex_count,
+            _uniforms: std::marker::PhantomData,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        target_size: (u32, u32),
+        instances: &[I],
+        batches: &[QuadBatch],
+        globals_bind_group: &wgpu::BindGroup,
+    ) {
+        // Only the instances covered by the batches have to be uploaded
+        let (span_start, span_end) = match (batches.first(), batches.last()) {
+            (Some(first), Some(last)) => (first.range.start, last.range.end),
+            _ => return,
+        };
+        if span_start >= span_end {
+            return;
+        }
+        self.instances.reserve(device, span_end - span_start);
+        let capacity = self.instances.capacity();
+
+        // Anything that still doesn't fit is drawn in chunks
+        for (chunk_index, chunk) in instances[span_start..span_end].chunks(capacity).enumerate() {
+            let chunk_start = span_start + chunk_index * capacity;
+            let chunk_end = chunk_start + chunk.len();
+
+            // Set buffer
+            self.instances.write(device, staging_belt, encoder, chunk);
+
+            // Render pass
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Custom render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            self.record(
+                &mut rpass,
+                target_size,
+                batches,
+                chunk_start..chunk_end,
+                globals_bind_group,
+            );
+        }
+    }
+
+    /// Uploads all `instances` at once, so they can be drawn with [`CustomPipeline::draw_prepared`]
+    /// inside of a render pass shared with other pipelines.
+    /// Returns `false` if they don't fit into a single buffer, use [`CustomPipeline::draw`] then.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        instances: &[I],
+    ) -> bool {
+        self.instances.reserve(device, instances.len());
+        if instances.len() > self.instances.capacity() {
+            return false;
+        }
+        self.instances.write(device, staging_belt, encoder, instances);
+        true
+    }
+
+    /// Records the batches into `rpass`, after [`CustomPipeline::prepare`] uploaded their instances.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        batches: &[QuadBatch],
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.record(
+            rpass,
+            target_size,
+            batches,
+            0..self.instances.capacity(),
+            globals_bind_group,
+        );
+    }
+
+    // Draws the part of the batches within `uploaded`, the instances at the start of the buffer
+    fn record<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        batches: &[QuadBatch],
+        uploaded: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals_bind_group, &[]);
+        if let Some((_, bind_group)) = &self.uniforms {
+            rpass.set_bind_group(1, bind_group, &[]);
+        }
+        rpass.set_vertex_buffer(0, self.instances.slice());
+
+        for batch in batches {
+            let start = batch.range.start.max(uploaded.start);
+            let end = batch.range.end.min(uploaded.end);
+            if start >= end {
+                continue;
+            }
+
+            // Restrict drawing to the clip rect of the batch
+            let [x, y, width, height] = match batch.clip {
+                Some(clip) => match clip.to_scissor(target_size.0, target_size.1) {
+                    Some(scissor) => scissor,
+                    None => continue,
+                },
+                None => [0, 0, target_size.0, target_size.1],
+            };
+            rpass.set_scissor_rect(x, y, width, height);
+
+            rpass.draw(
+                0..self.vertex_count,
+                (start - uploaded.start) as u32..(end - uploaded.start) as u32,
+            );
+        }
+    }
+
+    fn write_uniforms(
+        &self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        uniforms: &U,
+    ) {
+        if let Some((buffer, _)) = &self.uniforms {
+            // Copies have to be a multiple of 4 bytes, so the whole padded buffer is written
+            let bytes = bytemuck::bytes_of(uniforms);
+            let mut view = staging_belt.write_buffer(
+                encoder,
+                buffer,
+                0,
+                wgpu::BufferSize::new(buffer.size()).unwrap(),
+                device,
+            );
+            view[..bytes.len()].copy_from_slice(bytes);
+            view[bytes.len()..].fill(0);
+        }
+    }
+}
+
+// ====< BRUSH >====
+/// Draws instances of `I` with a user supplied [`CustomShader`], e.g. for noise backgrounds,
+/// waveforms or heatmaps. `U` is a uniform struct shared by all instances of a frame.
+pub struct CustomBrush<I: CustomInstance, U: Pod = ()> {
+    pub pipeline: CustomPipeline<I, U>,
+    pub queue: Vec<I>,
+    pub batches: Vec<QuadBatch>,
+
+    shader: CustomShader,
+    uniforms: U,
+    uniforms_dirty: bool,
+    clip: Option<ClipRect>,
+    split_batch: bool,
+}
+
+impl<I: CustomInstance, U: Pod> CustomBrush<I, U> {
+    /// Fails with [`RenderError::Shader`] if the shader doesn't compile or doesn't fit the layout.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+        shader: CustomShader,
+        uniforms: U,
+    ) -> Result<Self, RenderError> {
+        Ok(Self {
+            pipeline: CustomPipeline::new(
+                device,
+                format,
+                sample_count,
+                globals_bind_group_layout,
+                &shader,
+            )?,
+            queue: Vec::new(),
+            batches: Vec::new(),
+            shader,
+            uniforms,
+            uniforms_dirty: true,
+            clip: None,
+            split_batch: false,
+        })
+    }
+
+    pub fn shader(&self) -> &CustomShader {
+        &self.shader
+    }
+
+    pub fn uniforms(&self) -> &U {
+        &self.uniforms
+    }
+
+    /// Sets the uniforms for the next frame. All instances of a frame share the same uniforms.
+    pub fn set_uniforms(&mut self, uniforms: U) {
+        self.uniforms = uniforms;
+        self.uniforms_dirty = true;
+    }
+
+    /// Sets the clip rect for all instances queued from now on.
+    pub fn set_clip(&mut self, clip: Option<ClipRect>) {
+        self.clip = clip;
+    }
+
+    /// Makes the next queued instance start a new batch, e.g. when something else was drawn in between.
+    pub fn split_batch(&mut self) {
+        self.split_batch = true;
+    }
+
+    pub fn queue_instance(&mut self, instance: I) {
+        let index = self.queue.len();
+        self.queue.push(instance);
+
+        // Extend the last batch if it uses the same clip rect
+        let split_batch = std::mem::take(&mut self.split_batch);
+        match self.batches.last_mut() {
+            Some(batch) if !split_batch && batch.clip == self.clip && batch.range.end == index => {
+                batch.range.end = index + 1;
+            }
+            _ => self.batches.push(QuadBatch {
+                clip: self.clip,
+                range: index..index + 1,
+            }),
+        }
+    }
+
+    /// Draws only the given range of batches, leaving the queue untouched.
+    /// Used to interleave custom instances with other brushes in submission order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_batches(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        if std::mem::take(&mut self.uniforms_dirty) {
+            self.pipeline
+                .write_uniforms(device, staging_belt, encoder, &self.uniforms);
+        }
+        self.pipeline.draw(
+            device,
+            staging_belt,
+            encoder,
+            view,
+            (width, height),
+            &self.queue,
+            &self.batches[batches],
+            globals_bind_group,
+        );
+        Ok(())
+    }
+
+    /// Uploads the uniforms and everything queued so far, so batches can be drawn with
+    /// [`CustomBrush::draw_prepared`] inside of a render pass shared with other brushes.
+    /// Returns `false` if the queue is too big for that, use [`CustomBrush::draw_batches`] then.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+    ) -> bool {
+        if std::mem::take(&mut self.uniforms_dirty) {
+            self.pipeline
+                .write_uniforms(device, staging_belt, encoder, &self.uniforms);
+        }
+        self.pipeline
+            .prepare(device, staging_belt, encoder, &self.queue)
+    }
+
+    /// Records the given range of batches into `rpass`, after [`CustomBrush::prepare`] returned `true`.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.pipeline.draw_prepared(
+            rpass,
+            (width, height),
+            &self.batches[batches],
+            globals_bind_group,
+        );
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.batches.clear();
+        self.split_batch = false;
+    }
+}
+
+impl<I: CustomInstance, U: Pod> Brush for CustomBrush<I, U> {
+    fn draw_queued(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        target: &mut FrameTarget,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        let batches = 0..self.batches.len();
+        let result = if self.prepare(device, staging_belt, encoder) {
+            let mut rpass = target.begin_render_pass(encoder, "Custom render pass", true);
+            self.draw_prepared(
+                &mut rpass,
+                target.width,
+                target.height,
+                batches,
+                globals_bind_group,
+            );
+            Ok(())
+        } else {
+            let view = target.load_view(encoder);
+            self.draw_batches(
+                device,
+                staging_belt,
+                encoder,
+                view,
+                target.width,
+                target.height,
+                batches,
+                globals_bind_group,
+            )
+        };
+        self.clear();
+        result
+    }
+
+    /// The shader was already checked when the brush was created, so it is expected to compile again.
+    fn recreate(
+        &mut self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        self.pipeline = CustomPipeline::create(
+            device,
+            format,
+            sample_count,
+            globals_bind_group_layout,
+            &self.shader,
+        );
+        self.uniforms_dirty = true;
+    }
+}
+
+/// Object safe part of a [`CustomBrush`], so brushes with different instance and uniform
+/// types can be kept together, like in the universal brush of `bobsics-gui`.
+pub trait AnyCustomBrush: Brush + Any {
+    fn batch_count(&self) -> usize;
+    fn set_clip(&mut self, clip: Option<ClipRect>);
+    fn split_batch(&mut self);
+    /// Queues an instance given as its raw bytes, e.g. from a recorded draw list.
+    /// Fails if `bytes` isn't exactly one instance long.
+    fn queue_instance_bytes(&mut self, bytes: &[u8]) -> Result<(), RenderError>;
+    #[allow(clippy::too_many_arguments)]
+    fn draw_batches(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError>;
+    fn prepare(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+    ) -> bool;
+    fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    );
+    fn clear(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<I: CustomInstance, U: Pod> AnyCustomBrush for CustomBrush<I, U> {
+    fn batch_count(&self) -> usize {
+        self.batches.len()
+    }
+
+    fn set_clip(&mut self, clip: Option<ClipRect>) {
+        CustomBrush::set_clip(self, clip);
+    }
+
+    fn split_batch(&mut self) {
+        CustomBrush::split_batch(self);
+    }
+
+    fn queue_instance_bytes(&mut self, bytes: &[u8]) -> Result<(), RenderError> {
+        if bytes.len() != std::mem::size_of::<I>() {
+            return Err(RenderError::Brush(format!(
+                "custom instance has {} bytes, expected {}",
+                bytes.len(),
+                std::mem::size_of::<I>()
+            )));
+        }
+        self.queue_instance(bytemuck::pod_read_unaligned(bytes));
+        Ok(())
+    }
+
+    fn draw_batches(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        CustomBrush::draw_batches(
+            self,
+            device,
+            staging_belt,
+            encoder,
+            view,
+            width,
+            height,
+            batches,
+            globals_bind_group,
+        )
+    }
+
+    fn prepare(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+    ) -> bool {
+        CustomBrush::prepare(self, device, staging_belt, encoder)
+    }
+
+    fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        CustomBrush::draw_prepared(self, rpass, width, height, batches, globals_bind_group);
+    }
+
+    fn clear(&mut self) {
+        CustomBrush::clear(self);
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
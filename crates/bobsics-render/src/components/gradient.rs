@@ -0,0 +1,125 @@
+use bytemuck::{Pod, Zeroable};
+
+use crate::Color;
+
+pub const MAX_GRADIENT_STOPS: usize = 8;
+
+const GRADIENT_LINEAR: f32 = 0.0;
+const GRADIENT_RADIAL: f32 = 1.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GradientKind {
+    /// Angle in degrees, like CSS `linear-gradient`: 0 goes from bottom to top, 90 from left to right.
+    Linear { angle: f32 },
+    /// Center relative to the quad (0..1 on both axes) and radius in pixels.
+    Radial { center: [f32; 2], radius: f32 },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GradientStop {
+    pub offset: f32,
+    pub color: Color,
+}
+
+/// Gradient fill for the body or the border of a [`crate::Quad`].
+/// Stops past [`MAX_GRADIENT_STOPS`] are ignored.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Gradient {
+    pub kind: GradientKind,
+    pub stops: Vec<GradientStop>,
+}
+
+impl Gradient {
+    pub fn linear(angle: f32) -> Self {
+        Self {
+            kind: GradientKind::Linear { angle },
+            stops: Vec::new(),
+        }
+    }
+
+    pub fn radial(center: [f32; 2], radius: f32) -> Self {
+        Self {
+            kind: GradientKind::Radial { center, radius },
+            stops: Vec::new(),
+        }
+    }
+
+    pub fn with_stop(mut self, offset: f32, color: Color) -> Self {
+        self.stops.push(GradientStop { offset, color });
+        self
+    }
+}
+
+// Layout of a gradient in the gradient storage buffer of the quad shader
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub(crate) struct GpuGradient {
+    // kind, angle (radians), radius, stop count
+    params: [f32; 4],
+    // center, padding
+    center: [f32; 4],
+    colors: [[f32; 4]; MAX_GRADIENT_STOPS],
+    offsets: [[f32; 4]; MAX_GRADIENT_STOPS / 4],
+}
+
+impl From<&Gradient> for GpuGradient {
+    fn from(gradient: &Gradient) -> Self {
+        let mut gpu = GpuGradient::zeroed();
+
+        let (kind, angle, center, radius) = match gradient.kind {
+            GradientKind::Linear { angle } => (GRADIENT_LINEAR, angle.to_radians(), [0.5, 0.5], 0.0),
+            GradientKind::Radial { center, radius } => (GRADIENT_RADIAL, 0.0, center, radius),
+        };
+
+        let mut stops = gradient.stops.clone();
+        stops.truncate(MAX_GRADIENT_STOPS);
+        stops.sort_by(|a, b| a.offset.total_cmp(&b.offset));
+
+        gpu.params = [kind, angle, radius, stops.len() as f32];
+        gpu.center = [center[0], center[1], 0.0, 0.0];
+        for (i, stop) in stops.iter().enumerate() {
+            gpu.colors[i] = stop.color.into();
+            gpu.offsets[i / 4][i % 4] = stop.offset;
+        }
+        gpu
+    }
+}
+
+impl GpuGradient {
+    // Same as `sample_gradient` in quad.wgsl, at `p` relative to the top left of a rect of the given size
+    pub(crate) fn sample(&self, p: [f32; 2], size: [f32; 2]) -> [f32; 4] {
+        let [kind, angle, radius, count] = self.params;
+        let center = [self.center[0] * size[0], self.center[1] * size[1]];
+        let d = [p[0] - center[0], p[1] - center[1]];
+
+        let t = if kind < 0.5 {
+            // Linear, with the gradient line spanning the rect like in CSS
+            let dir = [angle.sin(), -angle.cos()];
+            let half_length = ((size[0] * dir[0]).abs() + (size[1] * dir[1]).abs()) / 2.0;
+            (d[0] * dir[0] + d[1] * dir[1]) / half_length.max(0.0001) * 0.5 + 0.5
+        } else {
+            // Radial
+            (d[0] * d[0] + d[1] * d[1]).sqrt() / radius.max(0.0001)
+        };
+
+        let count = count as usize;
+        if count == 0 {
+            return [0.0; 4];
+        }
+
+        let offset = |stop: usize| self.offsets[stop / 4][stop % 4];
+        let mut color = self.colors[0];
+        for i in 1..count {
+            let (start, end) = (offset(i - 1), offset(i));
+            if t > start {
+                let local_t = ((t - start) / (end - start).max(0.0001)).clamp(0.0, 1.0);
+                let (a, b) = (self.colors[i - 1], self.colors[i]);
+                color = std::array::from_fn(|c| a[c] + (b[c] - a[c]) * local_t);
+            }
+        }
+        color
+    }
+}
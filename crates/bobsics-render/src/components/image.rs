@@ -0,0 +1,1127 @@
+use std::ops::Range;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{util::DeviceExt, CommandEncoder, Device, TextureFormat, TextureView};
+
+use crate::{ClipRect, Color, RenderError, Transform};
+
+use super::instances::InstanceBuffer;
+
+const INITIAL_IMAGE_CAPACITY: usize = 1_000;
+const ATLAS_PAGE_SIZE: u32 = 1024;
+// Border around every image so linear filtering doesn't bleed between them.
+// It repeats the edge pixels, so magnified images don't fade out at their edges.
+const ATLAS_PADDING: u32 = 1;
+
+const INDICES: &[u16] = &[0, 1, 2, 0, 2, 3];
+
+/// Handle to an image stored in a [`TextureAtlas`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImageId(usize);
+
+/// Image drawn into a destination rect.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImageQuad {
+    pub image: ImageId,
+    pub top_left: [f32; 2],
+    pub bottom_right: [f32; 2],
+    // Sub-rect of the image, in uv space of the image (0..1)
+    pub uv_min: [f32; 2],
+    pub uv_max: [f32; 2],
+    pub tint: [f32; 4],
+    // Applied to the image before the transform of the brush, like `Quad::transform`
+    pub transform: Transform,
+}
+
+impl ImageQuad {
+    pub fn new(image: ImageId, top_left: [f32; 2], bottom_right: [f32; 2]) -> Self {
+        Self {
+            image,
+            top_left,
+            bottom_right,
+            uv_min: [0.0, 0.0],
+            uv_max: [1.0, 1.0],
+            tint: Color::WHITE.into(),
+            transform: Transform::IDENTITY,
+        }
+    }
+
+    pub fn with_uv(mut self, uv_min: [f32; 2], uv_max: [f32; 2]) -> Self {
+        self.uv_min = uv_min;
+        self.uv_max = uv_max;
+        self
+    }
+
+    pub fn with_tint(mut self, tint: Color) -> Self {
+        self.tint = tint.into();
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+}
+
+/// How the edges or the center of a [`NineSlice`] fill their part of the destination rect.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SliceMode {
+    #[default]
+    Stretch,
+    /// Repeats the slice at its scaled size from the top left, cropping the last tile.
+    Tile,
+    /// Repeats the slice a whole number of times, stretched a little to fit.
+    Round,
+}
+
+/// Image split into nine slices by insets, drawn into a destination rect of any size.
+/// The corners keep their size, while the edges and the center stretch or tile in between,
+/// e.g. for skinnable buttons and panels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NineSlice {
+    pub image: ImageId,
+    pub top_left: [f32; 2],
+    pub bottom_right: [f32; 2],
+    // Top, right, bottom and left inset into the image in pixels, like `Quad::border_width`
+    pub insets: [f32; 4],
+    // Size of the corners and edges in the destination rect, relative to the image
+    pub scale: f32,
+    pub edges: SliceMode,
+    pub center: SliceMode,
+    pub tint: [f32; 4],
+    // Applied to the image before the transform of the brush, like `Quad::transform`
+    pub transform: Transform,
+}
+
+impl NineSlice {
+    pub fn new(
+        image: ImageId,
+        top_left: [f32; 2],
+        bottom_right: [f32; 2],
+        insets: [f32; 4],
+    ) -> Self {
+        Self {
+            image,
+            top_left,
+            bottom_right,
+            insets,
+            scale: 1.0,
+            edges: SliceMode::Stretch,
+            center: SliceMode::Stretch,
+            tint: Color::WHITE.into(),
+            transform: Transform::IDENTITY,
+        }
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn with_edges(mut self, edges: SliceMode) -> Self {
+        self.edges = edges;
+        self
+    }
+
+    pub fn with_center(mut self, center: SliceMode) -> Self {
+        self.center = center;
+        self
+    }
+
+    pub fn with_tint(mut self, tint: Color) -> Self {
+        self.tint = tint.into();
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    // An instance for each slice, with uv rects in uv space of the image.
+    // Slices without any area are left out.
+    fn instances(&self, image_size: (u32, u32), transform: Transform) -> Vec<ImageInstance> {
+        let image_size = [image_size.0 as f32, image_size.1 as f32];
+        let top_left = [
+            self.top_left[0].min(self.bottom_right[0]),
+            self.top_left[1].min(self.bottom_right[1]),
+        ];
+        let bottom_right = [
+            self.top_left[0].max(self.bottom_right[0]),
+            self.top_left[1].max(self.bottom_right[1]),
+        ];
+        let [top, right, bottom, left] = self.insets.map(|inset| inset.max(0.0));
+        let scale = self.scale.max(0.0);
+
+        // Per axis: the insets into the image, and their size in the destination rect.
+        // Like in CSS, insets that don't fit are shrunk.
+        let axis = |axis: usize, start: f32, end: f32| {
+            let size = image_size[axis];
+            let shrink = (size / (start + end)).min(1.0);
+            let (start, end) = (start * shrink, end * shrink);
+            let dest_size = bottom_right[axis] - top_left[axis];
+            let fit = (dest_size / ((start + end) * scale)).min(1.0);
+            let dest = [
+                top_left[axis],
+                top_left[axis] + start * scale * fit,
+                bottom_right[axis] - end * scale * fit,
+                bottom_right[axis],
+            ];
+            let uv = [0.0, start / size, 1.0 - end / size, 1.0];
+            (dest, uv, scale * fit)
+        };
+        let (x, u, scale_x) = axis(0, left, right);
+        let (y, v, scale_y) = axis(1, top, bottom);
+        // Tiles keep the aspect ratio of the slice
+        let tile_scale = scale_x.min(scale_y);
+
+        let repeat = |mode: SliceMode, dest: f32, source: f32| {
+            let tile = source * tile_scale;
+            if tile <= 0.0 {
+                return 1.0;
+            }
+            match mode {
+                SliceMode::Stretch => 1.0,
+                SliceMode::Tile => dest / tile,
+                SliceMode::Round => (dest / tile).round().max(1.0),
+            }
+        };
+
+        let mut instances = Vec::with_capacity(9);
+        for row in 0..3 {
+            for column in 0..3 {
+                let dest_min = [x[column], y[row]];
+                let dest_max = [x[column + 1], y[row + 1]];
+                let uv_min = [u[column], v[row]];
+                let uv_max = [u[column + 1], v[row + 1]];
+                if dest_max[0] <= dest_min[0]
+                    || dest_max[1] <= dest_min[1]
+                    || uv_max[0] <= uv_min[0]
+                    || uv_max[1] <= uv_min[1]
+                {
+                    continue;
+                }
+
+                // Corners never repeat, edges only along their length
+                let mode_x = match (row, column) {
+                    (1, 1) => self.center,
+                    (_, 1) => self.edges,
+                    _ => SliceMode::Stretch,
+                };
+                let mode_y = match (row, column) {
+                    (1, 1) => self.center,
+                    (1, _) => self.edges,
+                    _ => SliceMode::Stretch,
+                };
+                let uv_repeat = [
+                    repeat(
+                        mode_x,
+                        dest_max[0] - dest_min[0],
+                        (uv_max[0] - uv_min[0]) * image_size[0],
+                    ),
+                    repeat(
+                        mode_y,
+                        dest_max[1] - dest_min[1],
+                        (uv_max[1] - uv_min[1]) * image_size[1],
+                    ),
+                ];
+                instances.push(ImageInstance {
+                    top_left: dest_min,
+                    bottom_right: dest_max,
+                    uv_min,
+                    uv_max,
+                    uv_repeat,
+                    tint: self.tint,
+                    transform,
+                });
+            }
+        }
+        instances
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct ImageInstance {
+    top_left: [f32; 2],
+    bottom_right: [f32; 2],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    // How often the uv rect repeats across the image, 1 to stretch it
+    uv_repeat: [f32; 2],
+    tint: [f32; 4],
+    transform: Transform,
+}
+
+impl ImageInstance {
+    const ATTRIBS: [wgpu::VertexAttribute; 9] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x2,
+        3 => Float32x2,
+        4 => Float32x2,
+        5 => Float32x4,
+        6 => Float32x2,
+        7 => Float32x2,
+        8 => Float32x2
+    ];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+// ====< ATLAS >====
+struct Shelf {
+    y: u32,
+    height: u32,
+    x: u32,
+}
+
+struct AtlasPage {
+    size: u32,
+    shelves: Vec<Shelf>,
+    next_y: u32,
+
+    texture: Option<wgpu::Texture>,
+    bind_group: Option<wgpu::BindGroup>,
+}
+
+impl AtlasPage {
+    fn new(size: u32) -> Self {
+        Self {
+            size,
+            shelves: Vec::new(),
+            next_y: 0,
+            texture: None,
+            bind_group: None,
+        }
+    }
+
+    // Simple shelf packing, images are put next to each other in rows
+    fn allocate(&mut self, width: u32, height: u32) -> Option<(u32, u32)> {
+        if width > self.size || height > self.size {
+            return None;
+        }
+
+        for shelf in &mut self.shelves {
+            if height <= shelf.height && shelf.x + width <= self.size {
+                let x = shelf.x;
+                shelf.x += width;
+                return Some((x, shelf.y));
+            }
+        }
+
+        if self.next_y + height > self.size {
+            return None;
+        }
+        let y = self.next_y;
+        self.next_y += height;
+        self.shelves.push(Shelf {
+            y,
+            height,
+            x: width,
+        });
+        Some((0, y))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct AtlasEntry {
+    page: usize,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    // Space reserved for the image, it can be replaced by images up to this size
+    room: (u32, u32),
+}
+
+/// Packs many images into a few shared RGBA textures.
+/// Images are only uploaded to the GPU when they are drawn for the first time.
+/// A CPU copy of every image is kept, so it can be uploaded again when the device is lost.
+pub struct TextureAtlas {
+    pages: Vec<AtlasPage>,
+    entries: Vec<AtlasEntry>,
+    // Including the padding
+    pixels: Vec<Vec<u8>>,
+    pending: Vec<ImageId>,
+}
+
+impl TextureAtlas {
+    pub fn new() -> Self {
+        Self {
+            pages: Vec::new(),
+            entries: Vec::new(),
+            pixels: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Adds an image given as tightly packed sRGB RGBA8 pixels.
+    pub fn add_image(
+        &mut self,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Result<ImageId, RenderError> {
+        self.add_image_with_room(width, height, rgba, (width, height))
+    }
+
+    // Like `add_image`, but reserves space for images up to the size of `room`,
+    // so `replace_image` can reuse it for images of a different size
+    pub(crate) fn add_image_with_room(
+        &mut self,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+        room: (u32, u32),
+    ) -> Result<ImageId, RenderError> {
+        check_image_size(width, height, rgba)?;
+        let room = (room.0.max(width), room.1.max(height));
+        let (page, x, y) = self.allocate(room)?;
+
+        let id = ImageId(self.entries.len());
+        self.entries.push(AtlasEntry {
+            page,
+            x,
+            y,
+            width,
+            height,
+            room,
+        });
+        self.pixels.push(pad_image(width, height, rgba));
+        self.pending.push(id);
+        Ok(id)
+    }
+
+    // Replaces the pixels of an image, which keeps its id.
+    // It stays in place if it fits into the room of the old image, otherwise it is moved.
+    pub(crate) fn replace_image(
+        &mut self,
+        image: ImageId,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Result<(), RenderError> {
+        check_image_size(width, height, rgba)?;
+        let mut entry = self.entries[image.0];
+        if width > entry.room.0 || height > entry.room.1 {
+            entry.room = (width, height);
+            (entry.page, entry.x, entry.y) = self.allocate(entry.room)?;
+        }
+        entry.width = width;
+        entry.height = height;
+
+        self.entries[image.0] = entry;
+        self.pixels[image.0] = pad_image(width, height, rgba);
+        self.pending.push(image);
+        Ok(())
+    }
+
+    // Size of the space reserved for an image in the atlas, which can be larger than the image
+    pub(crate) fn image_room(&self, image: ImageId) -> (u32, u32) {
+        self.entries[image.0].room
+    }
+
+    // Finds a page with enough space for an image of size `room` and its padding,
+    // or creates a new one. Returns the page and the top left of the image, inside of the padding.
+    fn allocate(&mut self, room: (u32, u32)) -> Result<(usize, u32, u32), RenderError> {
+        let max = u32::MAX - ATLAS_PADDING * 2;
+        if room.0 > max || room.1 > max {
+            return Err(RenderError::TextureTooLarge {
+                size: room.0.max(room.1),
+                max,
+            });
+        }
+        let padded_width = room.0 + ATLAS_PADDING * 2;
+        let padded_height = room.1 + ATLAS_PADDING * 2;
+
+        // Images bigger than a page get a page of their own
+        let allocation = self.pages.iter_mut().enumerate().find_map(|(index, page)| {
+            page.allocate(padded_width, padded_height)
+                .map(|(x, y)| (index, x, y))
+        });
+        let (page, x, y) = allocation.unwrap_or_else(|| {
+            let mut page =
+                AtlasPage::new(ATLAS_PAGE_SIZE.max(padded_width).max(padded_height));
+            let (x, y) = page.allocate(padded_width, padded_height).unwrap();
+            self.pages.push(page);
+            (self.pages.len() - 1, x, y)
+        });
+        Ok((page, x + ATLAS_PADDING, y + ATLAS_PADDING))
+    }
+
+    // Drops all textures, so every image is uploaded again to a new device
+    fn reset_textures(&mut self) {
+        for page in &mut self.pages {
+            page.texture = None;
+            page.bind_group = None;
+        }
+        self.pending = (0..self.entries.len()).map(ImageId).collect();
+    }
+
+    pub fn image_size(&self, image: ImageId) -> (u32, u32) {
+        let entry = &self.entries[image.0];
+        (entry.width, entry.height)
+    }
+
+    fn page_of(&self, image: ImageId) -> usize {
+        self.entries[image.0].page
+    }
+
+    // Maps a uv rect of the image to uv coordinates of its atlas page
+    fn atlas_uv(&self, image: ImageId, uv_min: [f32; 2], uv_max: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        let entry = &self.entries[image.0];
+        let page_size = self.pages[entry.page].size as f32;
+        let map = |uv: [f32; 2]| {
+            [
+                (entry.x as f32 + uv[0] * entry.width as f32) / page_size,
+                (entry.y as f32 + uv[1] * entry.height as f32) / page_size,
+            ]
+        };
+        (map(uv_min), map(uv_max))
+    }
+
+    // Creates missing page textures and uploads all pending images
+    fn upload(
+        &mut self,
+        device: &Device,
+        encoder: &mut CommandEncoder,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+    ) -> Result<(), RenderError> {
+        // Pages that are too big for the device are reported, but don't stop the others
+        let mut result = Ok(());
+        let max = device.limits().max_texture_dimension_2d;
+        for page in &mut self.pages {
+            if page.texture.is_some() {
+                continue;
+            }
+            if page.size > max {
+                result = Err(RenderError::TextureTooLarge {
+                    size: page.size,
+                    max,
+                });
+                continue;
+            }
+            let texture = device.create_texture(&wgpu::TextureDescriptor {
+                label: Some("Image atlas page"),
+                size: wgpu::Extent3d {
+                    width: page.size,
+                    height: page.size,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            });
+            let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+            page.bind_group = Some(device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("Image atlas bind group"),
+                layout: bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::Sampler(sampler),
+                    },
+                ],
+            }));
+            page.texture = Some(texture);
+        }
+
+        for image in self.pending.drain(..) {
+            let entry = self.entries[image.0];
+            let pixels = &self.pixels[image.0];
+            let Some(texture) = self.pages[entry.page].texture.as_ref() else {
+                continue;
+            };
+
+            // Images are uploaded together with their padding
+            let width = entry.width + ATLAS_PADDING * 2;
+            let height = entry.height + ATLAS_PADDING * 2;
+
+            // Rows have to be padded to COPY_BYTES_PER_ROW_ALIGNMENT
+            let unpadded_bytes_per_row = width * 4;
+            let padded_bytes_per_row = unpadded_bytes_per_row
+                .div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+                * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+            let mut padded = vec![0u8; padded_bytes_per_row as usize * height as usize];
+            for (row, source) in padded
+                .chunks_mut(padded_bytes_per_row as usize)
+                .zip(pixels.chunks(unpadded_bytes_per_row as usize))
+            {
+                row[..unpadded_bytes_per_row as usize].copy_from_slice(source);
+            }
+
+            let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("Image upload buffer"),
+                contents: &padded,
+                usage: wgpu::BufferUsages::COPY_SRC,
+            });
+            encoder.copy_buffer_to_texture(
+                wgpu::ImageCopyBuffer {
+                    buffer: &buffer,
+                    layout: wgpu::ImageDataLayout {
+                        offset: 0,
+                        bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row),
+                        rows_per_image: None,
+                    },
+                },
+                wgpu::ImageCopyTexture {
+                    texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: entry.x - ATLAS_PADDING,
+                        y: entry.y - ATLAS_PADDING,
+                        z: 0,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+        result
+    }
+}
+
+fn check_image_size(width: u32, height: u32, rgba: &[u8]) -> Result<(), RenderError> {
+    if rgba.len() as u64 != width as u64 * height as u64 * 4 {
+        return Err(RenderError::ImageSize {
+            width,
+            height,
+            len: rgba.len(),
+        });
+    }
+    Ok(())
+}
+
+// Surrounds the image with `ATLAS_PADDING` copies of its edge pixels
+fn pad_image(width: u32, height: u32, rgba: &[u8]) -> Vec<u8> {
+    let padded_width = width + ATLAS_PADDING * 2;
+    let padded_height = height + ATLAS_PADDING * 2;
+    let mut padded = vec![0u8; padded_width as usize * padded_height as usize * 4];
+    if width == 0 || height == 0 {
+        return padded;
+    }
+
+    for (y, row) in padded.chunks_mut((padded_width * 4) as usize).enumerate() {
+        let source_y = (y as u32).saturating_sub(ATLAS_PADDING).min(height - 1);
+        for (x, pixel) in row.chunks_mut(4).enumerate() {
+            let source_x = (x as u32).saturating_sub(ATLAS_PADDING).min(width - 1);
+            let index = (source_y as usize * width as usize + source_x as usize) * 4;
+            pixel.copy_from_slice(&rgba[index..index + 4]);
+        }
+    }
+    padded
+}
+
+impl Default for TextureAtlas {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// ====< PIPELINE >====
+/// Range of queued images that share the same atlas page and clip rect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageBatch {
+    pub page: usize,
+    pub clip: Option<ClipRect>,
+    pub range: Range<usize>,
+}
+
+#[derive(Debug)]
+pub struct ImagePipeline {
+    instances: InstanceBuffer<ImageInstance>,
+    index_buffer: wgpu::Buffer,
+
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ImagePipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        // Create shader
+        let shader = device.create_shader_module(include_shader!("image.wgsl"));
+
+        // Create buffers
+        let instance_buffer =
+            InstanceBuffer::new(device, "Image instance buffer", INITIAL_IMAGE_CAPACITY);
+
+        // Create index buffer
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Image index buffer"),
+            contents: bytemuck::cast_slice(INDICES),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        // Create atlas bindings
+        let texture_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Image atlas bind group layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Image atlas sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        // Create pipeline
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Image Pipeline Layout"),
+                bind_group_layouts: &[globals_bind_group_layout, &texture_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Image Render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[ImageInstance::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self {
+            instances: instance_buffer,
+            index_buffer,
+
+            texture_bind_group_layout,
+            sampler,
+            pipeline: render_pipeline,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        target_size: (u32, u32),
+        instances: &[ImageInstance],
+        batches: &[ImageBatch],
+        atlas: &TextureAtlas,
+        globals_bind_group: &wgpu::BindGroup,
+    ) {
+        // Only the images covered by the batches have to be uploaded
+        let (span_start, span_end) = match (batches.first(), batches.last()) {
+            (Some(first), Some(last)) => (first.range.start, last.range.end),
+            _ => return,
+        };
+        if span_start >= span_end {
+            return;
+        }
+        self.instances.reserve(device, span_end - span_start);
+        let capacity = self.instances.capacity();
+
+        // Anything that still doesn't fit is drawn in chunks
+        for (chunk_index, chunk) in instances[span_start..span_end]
+            .chunks(capacity)
+            .enumerate()
+        {
+            let chunk_start = span_start + chunk_index * capacity;
+            let chunk_end = chunk_start + chunk.len();
+
+            // Set buffer
+            self.instances.write(device, staging_belt, encoder, chunk);
+
+            // Render pass
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Image render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            self.record(
+                &mut rpass,
+                target_size,
+                batches,
+                chunk_start..chunk_end,
+                atlas,
+                globals_bind_group,
+            );
+        }
+    }
+
+    // Uploads all instances at once, returns `false` if they don't fit into a single buffer
+    fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        instances: &[ImageInstance],
+    ) -> bool {
+        self.instances.reserve(device, instances.len());
+        if instances.len() > self.instances.capacity() {
+            return false;
+        }
+        self.instances.write(device, staging_belt, encoder, instances);
+        true
+    }
+
+    // Draws the part of the batches within `uploaded`, the instances at the start of the buffer
+    #[allow(clippy::too_many_arguments)]
+    fn record<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        batches: &[ImageBatch],
+        uploaded: Range<usize>,
+        atlas: &'a TextureAtlas,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.instances.slice());
+        rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+
+        for batch in batches {
+            let start = batch.range.start.max(uploaded.start);
+            let end = batch.range.end.min(uploaded.end);
+            if start >= end {
+                continue;
+            }
+            let bind_group = match &atlas.pages[batch.page].bind_group {
+                Some(bind_group) => bind_group,
+                None => continue,
+            };
+
+            // Restrict drawing to the clip rect of the batch
+            let [x, y, width, height] = match batch.clip {
+                Some(clip) => match clip.to_scissor(target_size.0, target_size.1) {
+                    Some(scissor) => scissor,
+                    None => continue,
+                },
+                None => [0, 0, target_size.0, target_size.1],
+            };
+            rpass.set_scissor_rect(x, y, width, height);
+
+            rpass.set_bind_group(1, bind_group, &[]);
+            rpass.draw_indexed(
+                0..INDICES.len() as u32,
+                0,
+                (start - uploaded.start) as u32..(end - uploaded.start) as u32,
+            );
+        }
+    }
+}
+
+// ====< BRUSH >====
+pub struct ImageBrush {
+    pub pipeline: ImagePipeline,
+    pub atlas: TextureAtlas,
+    pub batches: Vec<ImageBatch>,
+
+    queue: Vec<ImageInstance>,
+    clip: Option<ClipRect>,
+    transform: Transform,
+    split_batch: bool,
+}
+
+impl ImageBrush {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        Self {
+            pipeline: ImagePipeline::new(
+                device,
+                format,
+                sample_count,
+                globals_bind_group_layout,
+            ),
+            atlas: TextureAtlas::new(),
+            batches: Vec::new(),
+            queue: Vec::new(),
+            clip: None,
+            transform: Transform::IDENTITY,
+            split_batch: false,
+        }
+    }
+
+    /// Adds an image given as tightly packed sRGB RGBA8 pixels to the atlas.
+    pub fn load_image(
+        &mut self,
+        width: u32,
+        height: u32,
+        rgba: &[u8],
+    ) -> Result<ImageId, RenderError> {
+        self.atlas.add_image(width, height, rgba)
+    }
+
+    /// Creates the pipeline again on a new device, e.g. after the old one was lost.
+    /// Everything queued so far is kept.
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        self.pipeline = ImagePipeline::new(
+            device,
+            format,
+            sample_count,
+            globals_bind_group_layout,
+        );
+        self.atlas.reset_textures();
+    }
+
+    /// Sets the clip rect for all images queued from now on.
+    pub fn set_clip(&mut self, clip: Option<ClipRect>) {
+        self.clip = clip;
+    }
+
+    /// Sets the transform for all images queued from now on, applied after their own transform.
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    /// Makes the next queued image start a new batch, e.g. when something else was drawn in between.
+    pub fn split_batch(&mut self) {
+        self.split_batch = true;
+    }
+
+    pub fn queue_image(&mut self, image: ImageQuad) {
+        let (uv_min, uv_max) = self.atlas.atlas_uv(image.image, image.uv_min, image.uv_max);
+        let page = self.atlas.page_of(image.image);
+        self.push_instance(
+            page,
+            ImageInstance {
+                top_left: image.top_left,
+                bottom_right: image.bottom_right,
+                uv_min,
+                uv_max,
+                uv_repeat: [1.0, 1.0],
+                tint: image.tint,
+                transform: image.transform.then(&self.transform),
+            },
+        );
+    }
+
+    /// Queues each slice of `slice` as an image of its own, all in the same batch.
+    pub fn queue_nine_slice(&mut self, slice: NineSlice) {
+        let page = self.atlas.page_of(slice.image);
+        let transform = slice.transform.then(&self.transform);
+        for mut instance in slice.instances(self.atlas.image_size(slice.image), transform) {
+            (instance.uv_min, instance.uv_max) =
+                self.atlas
+                    .atlas_uv(slice.image, instance.uv_min, instance.uv_max);
+            self.push_instance(page, instance);
+        }
+    }
+
+    fn push_instance(&mut self, page: usize, instance: ImageInstance) {
+        let index = self.queue.len();
+        self.queue.push(instance);
+
+        // Extend the last batch if it uses the same page and clip rect
+        let split_batch = std::mem::take(&mut self.split_batch);
+        match self.batches.last_mut() {
+            Some(batch)
+                if !split_batch
+                    && batch.page == page
+                    && batch.clip == self.clip
+                    && batch.range.end == index =>
+            {
+                batch.range.end = index + 1;
+            }
+            _ => self.batches.push(ImageBatch {
+                page,
+                clip: self.clip,
+                range: index..index + 1,
+            }),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_queued(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        let result = self.draw_batches(
+            device,
+            staging_belt,
+            encoder,
+            view,
+            width,
+            height,
+            0..self.batches.len(),
+            globals_bind_group,
+        );
+        self.clear();
+        result
+    }
+
+    /// Draws only the given range of batches, leaving the queue untouched.
+    /// Used to interleave images with other brushes in submission order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_batches(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        self.atlas.upload(
+            device,
+            encoder,
+            &self.pipeline.texture_bind_group_layout,
+            &self.pipeline.sampler,
+        )?;
+        self.pipeline.draw(
+            device,
+            staging_belt,
+            encoder,
+            view,
+            (width, height),
+            &self.queue,
+            &self.batches[batches],
+            &self.atlas,
+            globals_bind_group,
+        );
+        Ok(())
+    }
+
+    /// Uploads pending images and everything queued so far, so batches can be drawn with
+    /// [`ImageBrush::draw_prepared`] inside of a render pass shared with other brushes.
+    /// Returns `false` if the queue is too big for that, use [`ImageBrush::draw_batches`] then.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+    ) -> Result<bool, RenderError> {
+        self.atlas.upload(
+            device,
+            encoder,
+            &self.pipeline.texture_bind_group_layout,
+            &self.pipeline.sampler,
+        )?;
+        Ok(self
+            .pipeline
+            .prepare(device, staging_belt, encoder, &self.queue))
+    }
+
+    /// Records the given range of batches into `rpass`, after [`ImageBrush::prepare`] returned `true`.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.pipeline.record(
+            rpass,
+            (width, height),
+            &self.batches[batches],
+            0..self.queue.len(),
+            &self.atlas,
+            globals_bind_group,
+        );
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.batches.clear();
+        self.split_batch = false;
+    }
+}
@@ -0,0 +1,94 @@
+use std::marker::PhantomData;
+
+use bytemuck::Pod;
+
+/// Vertex buffer of per-instance data that grows on demand, up to the device limit.
+/// Also holds the vertices and indices of tessellated paths.
+#[derive(Debug)]
+pub(crate) struct InstanceBuffer<T> {
+    label: &'static str,
+    usage: wgpu::BufferUsages,
+    buffer: wgpu::Buffer,
+    capacity: usize,
+    _instance: PhantomData<T>,
+}
+
+impl<T: Pod> InstanceBuffer<T> {
+    pub fn new(device: &wgpu::Device, label: &'static str, capacity: usize) -> Self {
+        Self::with_usage(device, label, capacity, wgpu::BufferUsages::VERTEX)
+    }
+
+    pub fn with_usage(
+        device: &wgpu::Device,
+        label: &'static str,
+        capacity: usize,
+        usage: wgpu::BufferUsages,
+    ) -> Self {
+        let usage = usage | wgpu::BufferUsages::COPY_DST;
+        Self {
+            label,
+            usage,
+            buffer: Self::create_buffer(device, label, capacity, usage),
+            capacity,
+            _instance: PhantomData,
+        }
+    }
+
+    fn create_buffer(
+        device: &wgpu::Device,
+        label: &'static str,
+        capacity: usize,
+        usage: wgpu::BufferUsages,
+    ) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: (std::mem::size_of::<T>() * capacity) as u64,
+            usage,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Number of instances that fit into the buffer at once.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Grows the buffer so it can hold `count` instances.
+    /// Anything past the device limit has to be drawn in chunks of [`InstanceBuffer::capacity`].
+    pub fn reserve(&mut self, device: &wgpu::Device, count: usize) {
+        if count <= self.capacity {
+            return;
+        }
+        let max_capacity =
+            (device.limits().max_buffer_size as usize / std::mem::size_of::<T>()).max(1);
+        let capacity = count
+            .next_power_of_two()
+            .min(max_capacity)
+            .max(self.capacity);
+        if capacity > self.capacity {
+            self.buffer = Self::create_buffer(device, self.label, capacity, self.usage);
+            self.capacity = capacity;
+        }
+    }
+
+    /// Writes `instances` to the start of the buffer. Must not be longer than the capacity.
+    pub fn write(
+        &self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        instances: &[T],
+    ) {
+        let instance_bytes = bytemuck::cast_slice(instances);
+        let Some(size) = wgpu::BufferSize::new(instance_bytes.len() as u64) else {
+            return;
+        };
+        staging_belt
+            .write_buffer(encoder, &self.buffer, 0, size, device)
+            .copy_from_slice(instance_bytes);
+    }
+
+    pub fn slice(&self) -> wgpu::BufferSlice<'_> {
+        self.buffer.slice(..)
+    }
+}
@@ -0,0 +1,563 @@
+use bytemuck::{Pod, Zeroable};
+use wgpu::{CommandEncoder, Device, TextureFormat, TextureView};
+
+use crate::{ClipRect, FramePass, Transform};
+
+use super::instances::InstanceBuffer;
+
+/// How a layer is combined with what is already drawn below it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BlendMode {
+    #[default]
+    Normal,
+    Add,
+    /// Exact on opaque backgrounds, like everything drawn on top of the clear color.
+    Multiply,
+    Screen,
+}
+
+impl BlendMode {
+    const ALL: [BlendMode; 4] = [Self::Normal, Self::Add, Self::Multiply, Self::Screen];
+
+    // Layers hold premultiplied colors, as that is what alpha blending onto transparent produces
+    fn blend_state(self) -> wgpu::BlendState {
+        use wgpu::{BlendComponent, BlendFactor, BlendOperation};
+
+        let color = |src_factor, dst_factor| BlendComponent {
+            src_factor,
+            dst_factor,
+            operation: BlendOperation::Add,
+        };
+        wgpu::BlendState {
+            color: match self {
+                Self::Normal => color(BlendFactor::One, BlendFactor::OneMinusSrcAlpha),
+                Self::Add => color(BlendFactor::One, BlendFactor::One),
+                Self::Multiply => color(BlendFactor::Dst, BlendFactor::OneMinusSrcAlpha),
+                Self::Screen => color(BlendFactor::One, BlendFactor::OneMinusSrc),
+            },
+            alpha: BlendComponent::OVER,
+        }
+    }
+}
+
+/// Limits a layer to a shape when it is composited, in the coordinates of [`Layer::mask_transform`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LayerMask {
+    /// Keeps the inside of a rounded rect, radii like [`crate::Quad::border_radius`].
+    RoundedRect {
+        top_left: [f32; 2],
+        bottom_right: [f32; 2],
+        border_radius: [f32; 4],
+    },
+    /// Fades from opaque at `start` to transparent at `end`.
+    LinearFade { start: [f32; 2], end: [f32; 2] },
+}
+
+/// Everything drawn between pushing and popping a layer is rendered into its own texture,
+/// which is then drawn as a whole. This way overlapping children don't show through each other when faded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Layer {
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
+    pub mask: Option<LayerMask>,
+    // Applied to the mask before the transform of the brush, like `Quad::transform`
+    pub mask_transform: Transform,
+}
+
+impl Default for Layer {
+    fn default() -> Self {
+        Self {
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            mask: None,
+            mask_transform: Transform::IDENTITY,
+        }
+    }
+}
+
+impl Layer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_opacity(mut self, opacity: f32) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    pub fn with_blend_mode(mut self, blend_mode: BlendMode) -> Self {
+        self.blend_mode = blend_mode;
+        self
+    }
+
+    pub fn with_mask(mut self, mask: LayerMask) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+struct LayerInstance {
+    opacity: f32,
+    // 0 for no mask, 1 for a rounded rect and 2 for a linear fade
+    mask_kind: u32,
+    // Rounded rect corners or fade start and end
+    mask_rect: [f32; 4],
+    mask_radius: [f32; 4],
+    // Maps screen space into the space of the mask
+    screen_to_mask: Transform,
+}
+
+impl LayerInstance {
+    const ATTRIBS: [wgpu::VertexAttribute; 7] = wgpu::vertex_attr_array![
+        0 => Float32,
+        1 => Uint32,
+        2 => Float32x4,
+        3 => Float32x4,
+        4 => Float32x2,
+        5 => Float32x2,
+        6 => Float32x2
+    ];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+impl From<&Layer> for LayerInstance {
+    fn from(layer: &Layer) -> Self {
+        let (mask_kind, mask_rect, mask_radius) = match layer.mask {
+            None => (0, [0.0; 4], [0.0; 4]),
+            Some(LayerMask::RoundedRect {
+                top_left,
+                bottom_right,
+                border_radius,
+            }) => (
+                1,
+                [top_left[0], top_left[1], bottom_right[0], bottom_right[1]],
+                border_radius,
+            ),
+            Some(LayerMask::LinearFade { start, end }) => {
+                (2, [start[0], start[1], end[0], end[1]], [0.0; 4])
+            }
+        };
+        Self {
+            opacity: layer.opacity.clamp(0.0, 1.0),
+            mask_kind,
+            mask_rect,
+            mask_radius,
+            // A collapsed mask hides the layer
+            screen_to_mask: layer
+                .mask_transform
+                .inverse()
+                .unwrap_or(Transform::scale(0.0, 0.0)),
+        }
+    }
+}
+
+// ====< TEXTURES >====
+struct LayerTexture {
+    width: u32,
+    height: u32,
+    // Drawn into, multisampled with MSAA
+    view: TextureView,
+    // Resolve target with MSAA
+    resolve_view: Option<TextureView>,
+    // The single sampled texture, composited and copied from
+    texture: wgpu::Texture,
+    bind_group: wgpu::BindGroup,
+}
+
+#[derive(Debug)]
+pub struct LayerPipeline {
+    instances: InstanceBuffer<LayerInstance>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    // One pipeline per blend mode, in the order of `BlendMode::ALL`
+    pipelines: Vec<wgpu::RenderPipeline>,
+}
+
+impl LayerPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        // Create shader
+        let shader = device.create_shader_module(include_shader!("layer.wgsl"));
+
+        // Create buffers
+        let instance_buffer = InstanceBuffer::new(device, "Layer instance buffer", 1);
+
+        // Layers are read with `textureLoad`, so no sampler is needed
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Layer bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+
+        // Create pipelines
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Layer Pipeline Layout"),
+                bind_group_layouts: &[globals_bind_group_layout, &bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let pipelines = BlendMode::ALL
+            .iter()
+            .map(|blend_mode| {
+                device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some("Layer Render pipeline"),
+                    layout: Some(&render_pipeline_layout),
+                    vertex: wgpu::VertexState {
+                        module: &shader,
+                        entry_point: "vs_main",
+                        buffers: &[LayerInstance::desc()],
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &shader,
+                        entry_point: "fs_main",
+                        targets: &[Some(wgpu::ColorTargetState {
+                            format,
+                            blend: Some(blend_mode.blend_state()),
+                            write_mask: wgpu::ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        strip_index_format: None,
+                        front_face: wgpu::FrontFace::Ccw,
+                        cull_mode: None,
+                        polygon_mode: wgpu::PolygonMode::Fill,
+                        unclipped_depth: false,
+                        conservative: false,
+                    },
+                    depth_stencil: None,
+                    multisample: wgpu::MultisampleState {
+                        count: sample_count,
+                        mask: !0,
+                        alpha_to_coverage_enabled: false,
+                    },
+                    multiview: None,
+                })
+            })
+            .collect();
+
+        Self {
+            instances: instance_buffer,
+            bind_group_layout,
+            pipelines,
+        }
+    }
+
+    // Uploads how each of the layers is composited, in the order they are drawn with `draw_prepared`
+    fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        layers: &[Layer],
+    ) {
+        self.instances.reserve(device, layers.len());
+        let instances: Vec<LayerInstance> = layers
+            .iter()
+            .take(self.instances.capacity())
+            .map(LayerInstance::from)
+            .collect();
+        self.instances
+            .write(device, staging_belt, encoder, &instances);
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        index: usize,
+        layer: &Layer,
+        clip: Option<ClipRect>,
+        texture: &'a wgpu::BindGroup,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        if index >= self.instances.capacity() {
+            return;
+        }
+
+        // Only the clipped part of the layer can contain anything
+        let [x, y, width, height] = match clip {
+            Some(clip) => match clip.to_scissor(target_size.0, target_size.1) {
+                Some(scissor) => scissor,
+                None => return,
+            },
+            None => [0, 0, target_size.0, target_size.1],
+        };
+
+        let pipeline = BlendMode::ALL
+            .iter()
+            .position(|mode| *mode == layer.blend_mode)
+            .unwrap_or_default();
+        rpass.set_pipeline(&self.pipelines[pipeline]);
+        rpass.set_bind_group(0, globals_bind_group, &[]);
+        rpass.set_bind_group(1, texture, &[]);
+        rpass.set_vertex_buffer(0, self.instances.slice());
+        rpass.set_scissor_rect(x, y, width, height);
+
+        // A single triangle covering the whole target
+        rpass.draw(0..3, index as u32..index as u32 + 1);
+    }
+}
+
+// ====< BRUSH >====
+/// Offscreen textures for nested layers, and the pipeline compositing them.
+/// Depth 0 is the target passed to the brushes, layer `n` is drawn at depth `n + 1`.
+pub struct LayerBrush {
+    pub pipeline: LayerPipeline,
+
+    format: TextureFormat,
+    sample_count: u32,
+    textures: Vec<LayerTexture>,
+}
+
+impl LayerBrush {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        Self {
+            pipeline: LayerPipeline::new(device, format, sample_count, globals_bind_group_layout),
+            format,
+            sample_count,
+            textures: Vec::new(),
+        }
+    }
+
+    /// Creates the pipeline again on a new device, e.g. after the old one was lost.
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        *self = Self::new(device, format, sample_count, globals_bind_group_layout);
+    }
+
+    /// View to draw into for the layer at the given depth, counted from 1.
+    pub fn layer_view(&self, depth: usize) -> &TextureView {
+        &self.textures[depth - 1].view
+    }
+
+    /// Creates textures for layers nested up to `depth`, and uploads how each of `layers` is
+    /// composited. Layers are referred to by their position in `layers` when drawing them.
+    #[allow(clippy::too_many_arguments)]
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        width: u32,
+        height: u32,
+        depth: usize,
+        layers: &[Layer],
+    ) {
+        while self.textures.len() < depth {
+            self.textures
+                .push(self.create_texture(device, width, height));
+        }
+        // Textures follow the size of the target
+        for index in 0..depth {
+            if self.textures[index].width != width || self.textures[index].height != height {
+                self.textures[index] = self.create_texture(device, width, height);
+            }
+        }
+        if !layers.is_empty() {
+            self.pipeline
+                .prepare(device, staging_belt, encoder, layers);
+        }
+    }
+
+    /// Begins a render pass drawing into the layer at `depth` (at least 1).
+    /// The first pass of a layer has to `clear` it to transparent, and with MSAA
+    /// the last one has to `resolve` it before it is composited.
+    pub fn begin_render_pass<'a>(
+        &'a self,
+        encoder: &'a mut CommandEncoder,
+        depth: usize,
+        clear: bool,
+        resolve: bool,
+    ) -> FramePass<'a> {
+        let texture = &self.textures[depth - 1];
+        let resolve_target = texture.resolve_view.as_ref().filter(|_| resolve);
+        let pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Layer render pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &texture.view,
+                resolve_target,
+                ops: wgpu::Operations {
+                    load: if clear {
+                        wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
+                    } else {
+                        wgpu::LoadOp::Load
+                    },
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        FramePass::new(
+            pass,
+            resolve_target.map(|_| (texture.width, texture.height)),
+        )
+    }
+
+    /// Copies everything drawn into the layer at `depth` so far into `destination`, like
+    /// [`crate::FrameTarget::copy_to`]. Pass `clear` if nothing was drawn into the layer yet.
+    /// With MSAA this resolves the layer into `destination` only, so it still has to be resolved
+    /// before it is composited.
+    pub fn copy_to(
+        &self,
+        encoder: &mut CommandEncoder,
+        depth: usize,
+        clear: bool,
+        destination: &wgpu::Texture,
+    ) {
+        let texture = &self.textures[depth - 1];
+        if self.sample_count > 1 {
+            let destination = destination.create_view(&wgpu::TextureViewDescriptor::default());
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Layer copy pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &texture.view,
+                    resolve_target: Some(&destination),
+                    ops: wgpu::Operations {
+                        load: if clear {
+                            wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT)
+                        } else {
+                            wgpu::LoadOp::Load
+                        },
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            return;
+        }
+
+        if clear {
+            self.begin_render_pass(encoder, depth, true, false);
+        }
+        encoder.copy_texture_to_texture(
+            texture.texture.as_image_copy(),
+            destination.as_image_copy(),
+            wgpu::Extent3d {
+                width: texture.width,
+                height: texture.height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Records compositing the layer at `depth` onto the one below it into `rpass`, restricted to `clip`.
+    /// `index` is the position of the layer in the layers given to [`LayerBrush::prepare`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        width: u32,
+        height: u32,
+        depth: usize,
+        index: usize,
+        layer: &Layer,
+        clip: Option<ClipRect>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.pipeline.draw_prepared(
+            rpass,
+            (width, height),
+            index,
+            layer,
+            clip,
+            &self.textures[depth - 1].bind_group,
+            globals_bind_group,
+        );
+    }
+
+    fn create_texture(&self, device: &Device, width: u32, height: u32) -> LayerTexture {
+        let create = |label, sample_count, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count,
+                dimension: wgpu::TextureDimension::D2,
+                format: self.format,
+                usage,
+            })
+        };
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT
+            | wgpu::TextureUsages::TEXTURE_BINDING
+            | wgpu::TextureUsages::COPY_SRC;
+        let texture = create("Layer texture", 1, usage);
+        let single_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // With MSAA the layer is drawn multisampled and read from its resolve target
+        let (view, resolve_view) = if self.sample_count > 1 {
+            (
+                create(
+                    "Layer multisampled texture",
+                    self.sample_count,
+                    wgpu::TextureUsages::RENDER_ATTACHMENT,
+                )
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+                Some(single_view),
+            )
+        } else {
+            (single_view, None)
+        };
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Layer bind group"),
+            layout: &self.pipeline.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(
+                    resolve_view.as_ref().unwrap_or(&view),
+                ),
+            }],
+        });
+
+        LayerTexture {
+            width,
+            height,
+            view,
+            resolve_view,
+            texture,
+            bind_group,
+        }
+    }
+}
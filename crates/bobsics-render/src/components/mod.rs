@@ -1,3 +1,38 @@
+// Builds a shader module from `shaders/common.wgsl` followed by the given shader
+macro_rules! include_shader {
+    ($name:literal) => {
+        wgpu::ShaderModuleDescriptor {
+            label: Some($name),
+            source: wgpu::ShaderSource::Wgsl(std::borrow::Cow::Borrowed(concat!(
+                include_str!("shaders/common.wgsl"),
+                "\n",
+                include_str!(concat!("shaders/", $name))
+            ))),
+        }
+    };
+}
+
+mod backdrop;
+mod custom;
+mod gradient;
+mod image;
+mod instances;
+mod layer;
+mod path;
 mod quad;
+mod shadow;
+mod shape;
+mod svg;
+mod text;
 
+pub use backdrop::*;
+pub use custom::*;
+pub use gradient::*;
+pub use image::*;
+pub use layer::*;
+pub use path::*;
 pub use quad::*;
+pub use shadow::*;
+pub use shape::*;
+pub use svg::*;
+pub use text::*;
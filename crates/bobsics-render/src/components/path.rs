@@ -0,0 +1,951 @@
+use std::{borrow::Cow, ops::Range};
+
+use bytemuck::{Pod, Zeroable};
+use lyon::{
+    algorithms::hit_test::hit_test_path,
+    geom::Arc,
+    math::{point, vector, Angle, Point},
+    tessellation::{
+        BuffersBuilder, FillOptions, FillTessellator, FillVertex, FillVertexConstructor, Side,
+        StrokeOptions, StrokeTessellator, StrokeVertex, StrokeVertexConstructor, TessellationResult,
+        VertexBuffers,
+    },
+};
+use wgpu::{CommandEncoder, Device, TextureFormat, TextureView};
+
+use crate::{ClipRect, Color, RenderError, Transform};
+
+use super::instances::InstanceBuffer;
+
+const INITIAL_VERTEX_CAPACITY: usize = 10_000;
+const INITIAL_INDEX_CAPACITY: usize = 30_000;
+
+// Max distance between a curve and its tessellation, in pixels
+const TOLERANCE: f32 = 0.1;
+// Width of the anti-aliased edge, in pixels
+const AA_WIDTH: f32 = 1.0;
+
+// ====< PATHS >====
+/// Vector shape made of lines, Bézier curves and arcs, in screen space.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(into = "Vec<lyon::path::PathEvent>", from = "Vec<lyon::path::PathEvent>")
+)]
+pub struct Path {
+    inner: lyon::path::Path,
+}
+
+impl Path {
+    pub fn builder() -> PathBuilder {
+        PathBuilder {
+            inner: lyon::path::Path::builder(),
+            open: false,
+        }
+    }
+
+    pub fn line(from: [f32; 2], to: [f32; 2]) -> Self {
+        Self::builder().move_to(from).line_to(to).build()
+    }
+
+    pub fn polyline(points: &[[f32; 2]]) -> Self {
+        points
+            .iter()
+            .fold(Self::builder(), |builder, &p| builder.line_to(p))
+            .build()
+    }
+
+    pub fn polygon(points: &[[f32; 2]]) -> Self {
+        points
+            .iter()
+            .fold(Self::builder(), |builder, &p| builder.line_to(p))
+            .close()
+            .build()
+    }
+
+    pub fn circle(center: [f32; 2], radius: f32) -> Self {
+        Self::builder()
+            .arc(center, [radius, radius], 0.0, 360.0)
+            .close()
+            .build()
+    }
+
+    pub fn transformed(&self, transform: &Transform) -> Self {
+        Self {
+            inner: self.inner.clone().transformed(&lyon::math::Transform::from(*transform)),
+        }
+    }
+}
+
+impl PartialEq for Path {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner.iter().eq(other.inner.iter())
+    }
+}
+
+// Paths are serialized as their events
+#[cfg(feature = "serde")]
+impl From<Path> for Vec<lyon::path::PathEvent> {
+    fn from(path: Path) -> Self {
+        path.inner.iter().collect()
+    }
+}
+
+#[cfg(feature = "serde")]
+impl From<Vec<lyon::path::PathEvent>> for Path {
+    fn from(events: Vec<lyon::path::PathEvent>) -> Self {
+        let mut builder = lyon::path::Path::builder();
+        for event in events {
+            builder.path_event(event);
+        }
+        Self {
+            inner: builder.build(),
+        }
+    }
+}
+
+/// Builds a [`Path`] out of subpaths. Drawing without a `move_to` starts a subpath at the first point.
+pub struct PathBuilder {
+    inner: lyon::path::path::Builder,
+    // Whether a subpath has been started and not ended yet
+    open: bool,
+}
+
+impl PathBuilder {
+    pub fn move_to(mut self, to: [f32; 2]) -> Self {
+        if self.open {
+            self.inner.end(false);
+        }
+        self.inner.begin(point(to[0], to[1]));
+        self.open = true;
+        self
+    }
+
+    pub fn line_to(mut self, to: [f32; 2]) -> Self {
+        if !self.open {
+            return self.move_to(to);
+        }
+        self.inner.line_to(point(to[0], to[1]));
+        self
+    }
+
+    pub fn quadratic_to(mut self, ctrl: [f32; 2], to: [f32; 2]) -> Self {
+        if !self.open {
+            self = self.move_to(ctrl);
+        }
+        self.inner
+            .quadratic_bezier_to(point(ctrl[0], ctrl[1]), point(to[0], to[1]));
+        self
+    }
+
+    pub fn cubic_to(mut self, ctrl1: [f32; 2], ctrl2: [f32; 2], to: [f32; 2]) -> Self {
+        if !self.open {
+            self = self.move_to(ctrl1);
+        }
+        self.inner.cubic_bezier_to(
+            point(ctrl1[0], ctrl1[1]),
+            point(ctrl2[0], ctrl2[1]),
+            point(to[0], to[1]),
+        );
+        self
+    }
+
+    /// Adds an elliptic arc, connected to the current subpath with a line.
+    /// Angles are in degrees, clockwise from the positive x axis.
+    pub fn arc(
+        mut self,
+        center: [f32; 2],
+        radii: [f32; 2],
+        start_angle: f32,
+        sweep_angle: f32,
+    ) -> Self {
+        let arc = Arc {
+            center: point(center[0], center[1]),
+            radii: vector(radii[0], radii[1]),
+            start_angle: Angle::degrees(start_angle),
+            sweep_angle: Angle::degrees(sweep_angle),
+            x_rotation: Angle::zero(),
+        };
+        let from = arc.from();
+        self = self.line_to([from.x, from.y]);
+        arc.for_each_cubic_bezier(&mut |curve| {
+            self.inner
+                .cubic_bezier_to(curve.ctrl1, curve.ctrl2, curve.to);
+        });
+        self
+    }
+
+    /// Closes the current subpath with a line back to its start.
+    pub fn close(mut self) -> Self {
+        if self.open {
+            self.inner.end(true);
+            self.open = false;
+        }
+        self
+    }
+
+    pub fn build(mut self) -> Path {
+        if self.open {
+            self.inner.end(false);
+        }
+        Path {
+            inner: self.inner.build(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
+impl From<FillRule> for lyon::tessellation::FillRule {
+    fn from(rule: FillRule) -> Self {
+        match rule {
+            FillRule::NonZero => Self::NonZero,
+            FillRule::EvenOdd => Self::EvenOdd,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineJoin {
+    Miter,
+    Round,
+    Bevel,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineCap {
+    Butt,
+    Round,
+    Square,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fill {
+    pub color: Color,
+    pub rule: FillRule,
+}
+
+impl Fill {
+    pub fn new(color: Color) -> Self {
+        Self {
+            color,
+            rule: FillRule::NonZero,
+        }
+    }
+
+    pub fn with_rule(mut self, rule: FillRule) -> Self {
+        self.rule = rule;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stroke {
+    pub width: f32,
+    pub color: Color,
+    pub join: LineJoin,
+    pub cap: LineCap,
+}
+
+impl Stroke {
+    pub fn new(width: f32, color: Color) -> Self {
+        Self {
+            width,
+            color,
+            join: LineJoin::Miter,
+            cap: LineCap::Butt,
+        }
+    }
+
+    pub fn with_join(mut self, join: LineJoin) -> Self {
+        self.join = join;
+        self
+    }
+
+    pub fn with_cap(mut self, cap: LineCap) -> Self {
+        self.cap = cap;
+        self
+    }
+}
+
+// ====< TESSELLATION >====
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+pub struct PathVertex {
+    pub position: [f32; 2],
+    pub color: [f32; 4],
+    // Signed distance from the middle of the edge ramp and distance at which coverage drops to 0
+    pub coverage: [f32; 2],
+}
+
+impl PathVertex {
+    const ATTRIBS: [wgpu::VertexAttribute; 3] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x4,
+        2 => Float32x2
+    ];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+struct FillVertexBuilder {
+    color: [f32; 4],
+}
+
+impl FillVertexConstructor<PathVertex> for FillVertexBuilder {
+    fn new_vertex(&mut self, vertex: FillVertex) -> PathVertex {
+        PathVertex {
+            position: vertex.position().to_array(),
+            color: self.color,
+            coverage: [0.0, 1.0],
+        }
+    }
+}
+
+// Fades the coverage out towards both sides of a stroke
+struct StrokeVertexBuilder {
+    color: [f32; 4],
+    half_width: f32,
+}
+
+impl StrokeVertexConstructor<PathVertex> for StrokeVertexBuilder {
+    fn new_vertex(&mut self, vertex: StrokeVertex) -> PathVertex {
+        let side = match vertex.side() {
+            Side::Positive => 1.0,
+            Side::Negative => -1.0,
+        };
+        // Miter normals are longer, but the vertex is still only half a width away from the edges
+        let distance = side * self.half_width * vertex.normal().length().min(1.0);
+        PathVertex {
+            position: vertex.position().to_array(),
+            color: self.color,
+            coverage: [distance, self.half_width],
+        }
+    }
+}
+
+// Outer fringe of a fill: vertices inside of the fill stay on the path, the others are
+// pushed out by the width of the anti-aliased edge
+struct FringeVertexBuilder<'a> {
+    path: &'a lyon::path::Path,
+    rule: lyon::tessellation::FillRule,
+    color: [f32; 4],
+    // Whether the positive and negative side of the subpath are inside of the fill
+    inside: [Option<bool>; 2],
+}
+
+impl StrokeVertexConstructor<PathVertex> for FringeVertexBuilder<'_> {
+    fn new_vertex(&mut self, vertex: StrokeVertex) -> PathVertex {
+        let on_path = vertex.position_on_path();
+        let normal = vertex.normal();
+        let inner = PathVertex {
+            position: on_path.to_array(),
+            color: self.color,
+            coverage: [0.0, AA_WIDTH],
+        };
+        if normal.square_length() < 1e-6 {
+            return inner;
+        }
+
+        let side = match vertex.side() {
+            Side::Positive => 0,
+            Side::Negative => 1,
+        };
+        let (path, rule) = (self.path, self.rule);
+        let inside = *self.inside[side].get_or_insert_with(|| {
+            let probe: Point = on_path + normal.normalize() * AA_WIDTH * 0.5;
+            hit_test_path(&probe, path.iter(), rule, TOLERANCE * 0.1)
+        });
+
+        if inside {
+            inner
+        } else {
+            PathVertex {
+                position: (on_path + normal * AA_WIDTH).to_array(),
+                color: self.color,
+                coverage: [AA_WIDTH, AA_WIDTH],
+            }
+        }
+    }
+}
+
+// ====< PIPELINE >====
+/// Vertices and indices of tessellated paths that share the same clip rect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathBatch {
+    pub clip: Option<ClipRect>,
+    pub vertices: Range<usize>,
+    pub indices: Range<usize>,
+}
+
+#[derive(Debug)]
+pub struct PathPipeline {
+    vertices: InstanceBuffer<PathVertex>,
+    indices: InstanceBuffer<u32>,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl PathPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        // Create shader
+        let shader = device.create_shader_module(include_shader!("path.wgsl"));
+
+        // Create buffers
+        let vertex_buffer =
+            InstanceBuffer::new(device, "Path vertex buffer", INITIAL_VERTEX_CAPACITY);
+        let index_buffer = InstanceBuffer::with_usage(
+            device,
+            "Path index buffer",
+            INITIAL_INDEX_CAPACITY,
+            wgpu::BufferUsages::INDEX,
+        );
+
+        // Create pipeline
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Path Pipeline Layout"),
+                bind_group_layouts: &[globals_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Path Render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[PathVertex::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // Tessellated triangles don't have a consistent winding
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self {
+            vertices: vertex_buffer,
+            indices: index_buffer,
+            pipeline: render_pipeline,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        target_size: (u32, u32),
+        mesh: &VertexBuffers<PathVertex, u32>,
+        batches: &[PathBatch],
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        let (first, last) = match (batches.first(), batches.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Ok(()),
+        };
+        self.vertices
+            .reserve(device, last.vertices.end - first.vertices.start);
+        self.indices
+            .reserve(device, last.indices.end - first.indices.start);
+
+        // Upload as many batches at once as fit into the buffers
+        let mut result = Ok(());
+        let mut group_start = 0;
+        while group_start < batches.len() {
+            let vertex_start = batches[group_start].vertices.start;
+            let index_start = batches[group_start].indices.start;
+            let fits = |batch: &PathBatch| {
+                batch.vertices.end - vertex_start <= self.vertices.capacity()
+                    && batch.indices.end - index_start <= self.indices.capacity()
+            };
+
+            let mut group_end = group_start;
+            while group_end < batches.len() && fits(&batches[group_end]) {
+                group_end += 1;
+            }
+            // A single batch past the device limit can't be drawn at all
+            if group_end == group_start {
+                let batch = &batches[group_start];
+                let vertex_size = batch.vertices.len() * std::mem::size_of::<PathVertex>();
+                let index_size = batch.indices.len() * std::mem::size_of::<u32>();
+                let (buffer, size) = if batch.vertices.len() > self.vertices.capacity() {
+                    ("Path vertex buffer", vertex_size)
+                } else {
+                    ("Path index buffer", index_size)
+                };
+                result = Err(RenderError::BufferOverflow {
+                    buffer,
+                    size: size as u64,
+                    max: device.limits().max_buffer_size,
+                });
+                group_start += 1;
+                continue;
+            }
+            let group = &batches[group_start..group_end];
+            group_start = group_end;
+
+            let vertex_end = group[group.len() - 1].vertices.end;
+            let index_end = group[group.len() - 1].indices.end;
+            self.vertices.write(
+                device,
+                staging_belt,
+                encoder,
+                &mesh.vertices[vertex_start..vertex_end],
+            );
+            self.indices.write(
+                device,
+                staging_belt,
+                encoder,
+                &mesh.indices[index_start..index_end],
+            );
+
+            // Render pass
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Path render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            self.record(
+                &mut rpass,
+                target_size,
+                group,
+                (vertex_start, index_start),
+                globals_bind_group,
+            );
+        }
+        result
+    }
+
+    /// Uploads the whole `mesh` at once, so it can be drawn with [`PathPipeline::draw_prepared`]
+    /// inside of a render pass shared with other pipelines.
+    /// Returns `false` if it doesn't fit into a single buffer, use [`PathPipeline::draw`] then.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        mesh: &VertexBuffers<PathVertex, u32>,
+    ) -> bool {
+        self.vertices.reserve(device, mesh.vertices.len());
+        self.indices.reserve(device, mesh.indices.len());
+        if mesh.vertices.len() > self.vertices.capacity()
+            || mesh.indices.len() > self.indices.capacity()
+        {
+            return false;
+        }
+        self.vertices
+            .write(device, staging_belt, encoder, &mesh.vertices);
+        self.indices.write(device, staging_belt, encoder, &mesh.indices);
+        true
+    }
+
+    /// Records the batches into `rpass`, after [`PathPipeline::prepare`] uploaded their mesh.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        batches: &[PathBatch],
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.record(rpass, target_size, batches, (0, 0), globals_bind_group);
+    }
+
+    // Draws batches whose vertices and indices were uploaded starting at `(vertex_start, index_start)`
+    fn record<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        batches: &[PathBatch],
+        (vertex_start, index_start): (usize, usize),
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.vertices.slice());
+        rpass.set_index_buffer(self.indices.slice(), wgpu::IndexFormat::Uint32);
+
+        for batch in batches {
+            if batch.indices.is_empty() {
+                continue;
+            }
+
+            // Restrict drawing to the clip rect of the batch
+            let [x, y, width, height] = match batch.clip {
+                Some(clip) => match clip.to_scissor(target_size.0, target_size.1) {
+                    Some(scissor) => scissor,
+                    None => continue,
+                },
+                None => [0, 0, target_size.0, target_size.1],
+            };
+            rpass.set_scissor_rect(x, y, width, height);
+
+            // Indices point into the whole mesh, not just the uploaded part
+            rpass.draw_indexed(
+                (batch.indices.start - index_start) as u32..(batch.indices.end - index_start) as u32,
+                -(vertex_start as i32),
+                0..1,
+            );
+        }
+    }
+}
+
+// ====< BRUSH >====
+pub struct PathBrush {
+    pub pipeline: PathPipeline,
+    pub mesh: VertexBuffers<PathVertex, u32>,
+    pub batches: Vec<PathBatch>,
+
+    fill_tessellator: FillTessellator,
+    stroke_tessellator: StrokeTessellator,
+    clip: Option<ClipRect>,
+    transform: Transform,
+    split_batch: bool,
+}
+
+impl PathBrush {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        Self {
+            pipeline: PathPipeline::new(
+                device,
+                format,
+                sample_count,
+                globals_bind_group_layout,
+            ),
+            mesh: VertexBuffers::new(),
+            batches: Vec::new(),
+            fill_tessellator: FillTessellator::new(),
+            stroke_tessellator: StrokeTessellator::new(),
+            clip: None,
+            transform: Transform::IDENTITY,
+            split_batch: false,
+        }
+    }
+
+    /// Creates the pipeline again on a new device, e.g. after the old one was lost.
+    /// Everything queued so far is kept.
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        self.pipeline = PathPipeline::new(
+            device,
+            format,
+            sample_count,
+            globals_bind_group_layout,
+        );
+    }
+
+    /// Sets the clip rect for all paths queued from now on.
+    pub fn set_clip(&mut self, clip: Option<ClipRect>) {
+        self.clip = clip;
+    }
+
+    /// Sets the transform for all paths queued from now on.
+    /// Paths are transformed before tessellation, so anti-aliasing stays one pixel wide.
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    /// Makes the next queued path start a new batch, e.g. when something else was drawn in between.
+    pub fn split_batch(&mut self) {
+        self.split_batch = true;
+    }
+
+    pub fn fill_path(&mut self, path: &Path, fill: &Fill) -> Result<(), RenderError> {
+        let path = self.apply_transform(path);
+        let (vertex_start, index_start) = (self.mesh.vertices.len(), self.mesh.indices.len());
+        let color: [f32; 4] = fill.color.into();
+        let rule = fill.rule.into();
+
+        let result = self
+            .fill_tessellator
+            .tessellate_path(
+                &path.inner,
+                &FillOptions::tolerance(TOLERANCE).with_fill_rule(rule),
+                &mut BuffersBuilder::new(&mut self.mesh, FillVertexBuilder { color }),
+            )
+            .and_then(|_| {
+                // Every subpath gets its own fringe, as the inside may be on either side of it
+                let fringe_options = StrokeOptions::tolerance(TOLERANCE)
+                    .with_line_width(2.0 * AA_WIDTH)
+                    .with_line_join(lyon::tessellation::LineJoin::Miter);
+                for subpath in subpaths(&path.inner) {
+                    self.stroke_tessellator.tessellate_path(
+                        &subpath,
+                        &fringe_options,
+                        &mut BuffersBuilder::new(
+                            &mut self.mesh,
+                            FringeVertexBuilder {
+                                path: &path.inner,
+                                rule,
+                                color,
+                                inside: [None, None],
+                            },
+                        ),
+                    )?;
+                }
+                Ok(())
+            });
+
+        self.finish_path(vertex_start, index_start, result)
+    }
+
+    pub fn stroke_path(&mut self, path: &Path, stroke: &Stroke) -> Result<(), RenderError> {
+        let path = self.apply_transform(path);
+        let (vertex_start, index_start) = (self.mesh.vertices.len(), self.mesh.indices.len());
+
+        // Hairlines are drawn one pixel wide, but fainter
+        let stroke_width = stroke.width * self.transform.scale_factor();
+        let mut color: [f32; 4] = stroke.color.into();
+        color[3] *= stroke_width.clamp(0.0, 1.0);
+        let width = stroke_width.max(1.0) + AA_WIDTH;
+
+        let options = StrokeOptions::tolerance(TOLERANCE)
+            .with_line_width(width)
+            .with_line_join(match stroke.join {
+                LineJoin::Miter => lyon::tessellation::LineJoin::Miter,
+                LineJoin::Round => lyon::tessellation::LineJoin::Round,
+                LineJoin::Bevel => lyon::tessellation::LineJoin::Bevel,
+            })
+            .with_line_cap(match stroke.cap {
+                LineCap::Butt => lyon::tessellation::LineCap::Butt,
+                LineCap::Round => lyon::tessellation::LineCap::Round,
+                LineCap::Square => lyon::tessellation::LineCap::Square,
+            });
+        let result = self.stroke_tessellator.tessellate_path(
+            &path.inner,
+            &options,
+            &mut BuffersBuilder::new(
+                &mut self.mesh,
+                StrokeVertexBuilder {
+                    color,
+                    half_width: width / 2.0,
+                },
+            ),
+        );
+
+        self.finish_path(vertex_start, index_start, result)
+    }
+
+    fn apply_transform<'a>(&self, path: &'a Path) -> Cow<'a, Path> {
+        if self.transform.is_identity() {
+            Cow::Borrowed(path)
+        } else {
+            Cow::Owned(path.transformed(&self.transform))
+        }
+    }
+
+    // Adds the freshly tessellated vertices to the current batch, or drops them if tessellation failed
+    fn finish_path(
+        &mut self,
+        vertex_start: usize,
+        index_start: usize,
+        result: TessellationResult,
+    ) -> Result<(), RenderError> {
+        if let Err(error) = result {
+            self.mesh.vertices.truncate(vertex_start);
+            self.mesh.indices.truncate(index_start);
+            return Err(error.into());
+        }
+        let (vertex_end, index_end) = (self.mesh.vertices.len(), self.mesh.indices.len());
+
+        // Extend the last batch if it uses the same clip rect
+        let split_batch = std::mem::take(&mut self.split_batch);
+        match self.batches.last_mut() {
+            Some(batch)
+                if !split_batch && batch.clip == self.clip && batch.indices.end == index_start =>
+            {
+                batch.vertices.end = vertex_end;
+                batch.indices.end = index_end;
+            }
+            _ => self.batches.push(PathBatch {
+                clip: self.clip,
+                vertices: vertex_start..vertex_end,
+                indices: index_start..index_end,
+            }),
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_queued(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        let result = self.draw_batches(
+            device,
+            staging_belt,
+            encoder,
+            view,
+            width,
+            height,
+            0..self.batches.len(),
+            globals_bind_group,
+        );
+        self.clear();
+        result
+    }
+
+    /// Draws only the given range of batches, leaving the queue untouched.
+    /// Used to interleave paths with other brushes in submission order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_batches(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        self.pipeline.draw(
+            device,
+            staging_belt,
+            encoder,
+            view,
+            (width, height),
+            &self.mesh,
+            &self.batches[batches],
+            globals_bind_group,
+        )
+    }
+
+    /// Uploads everything queued so far, so batches can be drawn with [`PathBrush::draw_prepared`]
+    /// inside of a render pass shared with other brushes.
+    /// Returns `false` if the mesh is too big for that, use [`PathBrush::draw_batches`] then.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+    ) -> bool {
+        self.pipeline
+            .prepare(device, staging_belt, encoder, &self.mesh)
+    }
+
+    /// Records the given range of batches into `rpass`, after [`PathBrush::prepare`] returned `true`.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.pipeline.draw_prepared(
+            rpass,
+            (width, height),
+            &self.batches[batches],
+            globals_bind_group,
+        );
+    }
+
+    pub fn clear(&mut self) {
+        self.mesh.vertices.clear();
+        self.mesh.indices.clear();
+        self.batches.clear();
+        self.split_batch = false;
+    }
+}
+
+// Splits a path into closed subpaths, the way they are filled
+fn subpaths(path: &lyon::path::Path) -> Vec<lyon::path::Path> {
+    use lyon::path::PathEvent;
+
+    let mut subpaths = Vec::new();
+    let mut builder = lyon::path::Path::builder();
+    for event in path.iter() {
+        match event {
+            PathEvent::Begin { at } => {
+                builder.begin(at);
+            }
+            PathEvent::Line { to, .. } => {
+                builder.line_to(to);
+            }
+            PathEvent::Quadratic { ctrl, to, .. } => {
+                builder.quadratic_bezier_to(ctrl, to);
+            }
+            PathEvent::Cubic {
+                ctrl1, ctrl2, to, ..
+            } => {
+                builder.cubic_bezier_to(ctrl1, ctrl2, to);
+            }
+            PathEvent::End { .. } => {
+                builder.end(true);
+                subpaths.push(std::mem::replace(&mut builder, lyon::path::Path::builder()).build());
+            }
+        }
+    }
+    subpaths
+}
@@ -1,29 +1,85 @@
+use std::ops::Range;
+
 use bytemuck::{Pod, Zeroable};
 use wgpu::{util::DeviceExt, CommandEncoder, Device, TextureFormat, TextureView};
 
-const DEFAULT_MAX_QUADS: usize = 10_000;
+use crate::{ClipRect, Gradient, RenderError, Transform};
+
+use super::{gradient::GpuGradient, instances::InstanceBuffer};
+
+const INITIAL_QUAD_CAPACITY: usize = 10_000;
+const INITIAL_GRADIENT_CAPACITY: usize = 64;
 
 const INDICES: &[u16] = &[0, 1, 2, 0, 2, 3];
 
 #[repr(C)]
-#[derive(Debug, Clone, Copy, Pod, Zeroable)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Quad {
     pub top_left: [f32; 2],
     pub bottom_right: [f32; 2],
     pub color: [f32; 4],
-    pub border_radius: f32,
-    pub border_color: [f32; 4],
-    pub border_width: f32,
+    // Top left, top right, bottom right and bottom left corner
+    pub border_radius: [f32; 4],
+    // Top, right, bottom and left side, like CSS
+    pub border_color: [[f32; 4]; 4],
+    pub border_width: [f32; 4],
+    // Gradients returned by `QuadBrush::queue_gradient`, 0 for a solid color
+    pub fill_gradient: u32,
+    pub border_gradient: u32,
+    // Set with `Quad::with_border_style`, 0 for solid, 1 for dashed and 2 for dotted
+    pub border_style: u32,
+    // Dash and gap length
+    pub border_dash: [f32; 2],
+    // Applied to the quad before the transform of the brush
+    pub transform: Transform,
+}
+
+/// How the border of a [`Quad`] is drawn. Dashes and dots follow the rounded corners, and are spaced
+/// out so they repeat evenly around the whole border: dashes and their gaps are scaled together,
+/// while dots keep their size and only their gaps change. The fill shows through the gaps.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BorderStyle {
+    #[default]
+    Solid,
+    Dashed {
+        dash: f32,
+        gap: f32,
+    },
+    /// Round dots as wide as the border.
+    Dotted {
+        gap: f32,
+    },
+}
+
+impl Default for Quad {
+    fn default() -> Self {
+        Self {
+            transform: Transform::IDENTITY,
+            ..Self::zeroed()
+        }
+    }
 }
 
 impl Quad {
-    const ATTRIBS: [wgpu::VertexAttribute; 6] = wgpu::vertex_attr_array![
+    const ATTRIBS: [wgpu::VertexAttribute; 16] = wgpu::vertex_attr_array![
         0 => Float32x2,
         1 => Float32x2,
         2 => Float32x4,
-        3 => Float32,
+        3 => Float32x4,
         4 => Float32x4,
-        5 => Float32
+        5 => Float32x4,
+        6 => Float32x4,
+        7 => Float32x4,
+        8 => Float32x4,
+        9 => Uint32,
+        10 => Uint32,
+        11 => Uint32,
+        12 => Float32x2,
+        13 => Float32x2,
+        14 => Float32x2,
+        15 => Float32x2
     ];
 
     fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
@@ -35,13 +91,43 @@ impl Quad {
             attributes: &Self::ATTRIBS,
         }
     }
+
+    pub fn with_border_style(mut self, style: BorderStyle) -> Self {
+        (self.border_style, self.border_dash) = match style {
+            BorderStyle::Solid => (0, [0.0; 2]),
+            BorderStyle::Dashed { dash, gap } => (1, [dash.max(0.0), gap.max(0.0)]),
+            BorderStyle::Dotted { gap } => (2, [0.0, gap.max(0.0)]),
+        };
+        self
+    }
+
+    pub fn border_style(&self) -> BorderStyle {
+        let [dash, gap] = self.border_dash;
+        match self.border_style {
+            1 => BorderStyle::Dashed { dash, gap },
+            2 => BorderStyle::Dotted { gap },
+            _ => BorderStyle::Solid,
+        }
+    }
+}
+
+/// Range of queued instances that share the same clip rect.
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuadBatch {
+    pub clip: Option<ClipRect>,
+    pub range: Range<usize>,
 }
 
 #[derive(Debug)]
 pub struct QuadPipeline {
-    instances: wgpu::Buffer,
+    instances: InstanceBuffer<Quad>,
     index_buffer: wgpu::Buffer,
 
+    gradients: wgpu::Buffer,
+    gradient_capacity: usize,
+    gradient_bind_group_layout: wgpu::BindGroupLayout,
+    gradient_bind_group: wgpu::BindGroup,
+
     pipeline: wgpu::RenderPipeline,
 }
 
@@ -49,18 +135,15 @@ impl QuadPipeline {
     pub fn new(
         device: &wgpu::Device,
         format: wgpu::TextureFormat,
+        sample_count: u32,
         globals_bind_group_layout: &wgpu::BindGroupLayout,
     ) -> Self {
         // Create shader
-        let shader = device.create_shader_module(wgpu::include_wgsl!("shaders/quad.wgsl"));
+        let shader = device.create_shader_module(include_shader!("quad.wgsl"));
 
         // Create buffers
-        let instance_buffer = device.create_buffer(&wgpu::BufferDescriptor {
-            label: Some("Quad instance buffer"),
-            size: (std::mem::size_of::<Quad>() * DEFAULT_MAX_QUADS) as u64,
-            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+        let instance_buffer =
+            InstanceBuffer::new(device, "Quad instance buffer", INITIAL_QUAD_CAPACITY);
 
         // Create index buffer
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
@@ -69,11 +152,32 @@ impl QuadPipeline {
             usage: wgpu::BufferUsages::INDEX,
         });
 
+        // Create gradient storage
+        let gradient_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Quad gradients bind group layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Storage { read_only: true },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                }],
+            });
+        let (gradients, gradient_bind_group) = Self::create_gradient_buffer(
+            device,
+            &gradient_bind_group_layout,
+            INITIAL_GRADIENT_CAPACITY,
+        );
+
         // Create pipeline
         let render_pipeline_layout =
             device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
                 label: Some("Quad Pipeline Layout"),
-                bind_group_layouts: &[globals_bind_group_layout],
+                bind_group_layouts: &[globals_bind_group_layout, &gradient_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
@@ -95,17 +199,18 @@ impl QuadPipeline {
                 })],
             }),
             primitive: wgpu::PrimitiveState {
-                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                topology: wgpu::PrimitiveTopology::TriangleList,
                 strip_index_format: None,
                 front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
+                // Mirroring transforms flip the winding order
+                cull_mode: None,
                 polygon_mode: wgpu::PolygonMode::Fill,
                 unclipped_depth: false,
                 conservative: false,
             },
             depth_stencil: None,
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
@@ -116,52 +221,210 @@ impl QuadPipeline {
             instances: instance_buffer,
             index_buffer,
 
+            gradients,
+            gradient_capacity: INITIAL_GRADIENT_CAPACITY,
+            gradient_bind_group_layout,
+            gradient_bind_group,
+
             pipeline: render_pipeline,
         }
     }
 
+    fn create_gradient_buffer(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        capacity: usize,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Quad gradient buffer"),
+            size: (std::mem::size_of::<GpuGradient>() * capacity) as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Quad gradients bind group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+        (buffer, bind_group)
+    }
+
+    /// Uploads the gradient table used by the quads drawn afterwards.
+    pub(crate) fn upload_gradients(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        gradients: &[GpuGradient],
+    ) -> Result<(), RenderError> {
+        if gradients.is_empty() {
+            return Ok(());
+        }
+        let size = std::mem::size_of_val(gradients) as u64;
+        let max = device.limits().max_storage_buffer_binding_size as u64;
+        if size > max {
+            return Err(RenderError::BufferOverflow {
+                buffer: "Quad gradient buffer",
+                size,
+                max,
+            });
+        }
+        if gradients.len() > self.gradient_capacity {
+            let max_capacity = max as usize / std::mem::size_of::<GpuGradient>();
+            let capacity = gradients.len().next_power_of_two().min(max_capacity);
+            (self.gradients, self.gradient_bind_group) = Self::create_gradient_buffer(
+                device,
+                &self.gradient_bind_group_layout,
+                capacity,
+            );
+            self.gradient_capacity = capacity;
+        }
+
+        let gradient_bytes = bytemuck::cast_slice(gradients);
+        let mut gradient_buffer = staging_belt.write_buffer(
+            encoder,
+            &self.gradients,
+            0,
+            wgpu::BufferSize::new(gradient_bytes.len() as u64).unwrap(),
+            device,
+        );
+        gradient_buffer.copy_from_slice(gradient_bytes);
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     pub fn draw(
         &mut self,
         device: &wgpu::Device,
         staging_belt: &mut wgpu::util::StagingBelt,
         encoder: &mut wgpu::CommandEncoder,
         view: &wgpu::TextureView,
-        instances: Vec<Quad>,
+        target_size: (u32, u32),
+        instances: &[Quad],
+        batches: &[QuadBatch],
         globals_bind_group: &wgpu::BindGroup,
     ) {
-        if instances.is_empty() {
+        // Only the quads covered by the batches have to be uploaded
+        let (span_start, span_end) = match (batches.first(), batches.last()) {
+            (Some(first), Some(last)) => (first.range.start, last.range.end),
+            _ => return,
+        };
+        if span_start >= span_end {
             return;
         }
-        // Set buffer
-        let instance_bytes = bytemuck::cast_slice(&instances);
-        let mut instance_buffer = staging_belt.write_buffer(
-            encoder,
-            &self.instances,
-            0,
-            wgpu::BufferSize::new(instance_bytes.len() as u64).unwrap(),
-            device,
+        self.instances.reserve(device, span_end - span_start);
+        let capacity = self.instances.capacity();
+
+        // Anything that still doesn't fit is drawn in chunks
+        for (chunk_index, chunk) in instances[span_start..span_end]
+            .chunks(capacity)
+            .enumerate()
+        {
+            let chunk_start = span_start + chunk_index * capacity;
+            let chunk_end = chunk_start + chunk.len();
+
+            // Set buffer
+            self.instances.write(device, staging_belt, encoder, chunk);
+
+            // Render pass
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Quad render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            self.record(
+                &mut rpass,
+                target_size,
+                batches,
+                chunk_start..chunk_end,
+                globals_bind_group,
+            );
+        }
+    }
+
+    /// Uploads all `instances` at once, so they can be drawn with [`QuadPipeline::draw_prepared`]
+    /// inside of a render pass shared with other pipelines.
+    /// Returns `false` if they don't fit into a single buffer, use [`QuadPipeline::draw`] then.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        instances: &[Quad],
+    ) -> bool {
+        self.instances.reserve(device, instances.len());
+        if instances.len() > self.instances.capacity() {
+            return false;
+        }
+        self.instances.write(device, staging_belt, encoder, instances);
+        true
+    }
+
+    /// Records the batches into `rpass`, after [`QuadPipeline::prepare`] uploaded their instances.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        batches: &[QuadBatch],
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.record(
+            rpass,
+            target_size,
+            batches,
+            0..self.instances.capacity(),
+            globals_bind_group,
         );
-        instance_buffer.copy_from_slice(instance_bytes);
-
-        // Render pass
-        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-            label: Some("Quad render pass"),
-            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                view,
-                resolve_target: None,
-                ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Load,
-                    store: true,
-                },
-            })],
-            depth_stencil_attachment: None,
-        });
+    }
 
+    // Draws the part of the batches within `uploaded`, the instances at the start of the buffer
+    fn record<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        batches: &[QuadBatch],
+        uploaded: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
         rpass.set_pipeline(&self.pipeline);
         rpass.set_bind_group(0, globals_bind_group, &[]);
-        rpass.set_vertex_buffer(0, self.instances.slice(..));
+        rpass.set_bind_group(1, &self.gradient_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.instances.slice());
         rpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-        rpass.draw(0..INDICES.len() as u32, 0..instances.len() as u32);
+
+        for batch in batches {
+            let start = batch.range.start.max(uploaded.start);
+            let end = batch.range.end.min(uploaded.end);
+            if start >= end {
+                continue;
+            }
+
+            // Restrict drawing to the clip rect of the batch
+            let [x, y, width, height] = match batch.clip {
+                Some(clip) => match clip.to_scissor(target_size.0, target_size.1) {
+                    Some(scissor) => scissor,
+                    None => continue,
+                },
+                None => [0, 0, target_size.0, target_size.1],
+            };
+            rpass.set_scissor_rect(x, y, width, height);
+
+            rpass.draw(
+                0..INDICES.len() as u32,
+                (start - uploaded.start) as u32..(end - uploaded.start) as u32,
+            );
+        }
     }
 }
 
@@ -169,40 +432,194 @@ impl QuadPipeline {
 pub struct QuadBrush {
     pub pipeline: QuadPipeline,
     pub queue: Vec<Quad>,
+    pub batches: Vec<QuadBatch>,
+
+    gradients: Vec<GpuGradient>,
+    gradients_dirty: bool,
+    clip: Option<ClipRect>,
+    transform: Transform,
+    split_batch: bool,
 }
 
 impl QuadBrush {
     pub fn new(
         device: &Device,
         format: TextureFormat,
+        sample_count: u32,
         globals_bind_group_layout: &wgpu::BindGroupLayout,
     ) -> Self {
         Self {
-            pipeline: QuadPipeline::new(device, format, globals_bind_group_layout),
+            pipeline: QuadPipeline::new(
+                device,
+                format,
+                sample_count,
+                globals_bind_group_layout,
+            ),
             queue: Vec::new(),
+            batches: Vec::new(),
+            gradients: Vec::new(),
+            gradients_dirty: false,
+            clip: None,
+            transform: Transform::IDENTITY,
+            split_batch: false,
         }
     }
 
-    pub fn queue_quad(&mut self, quad: Quad) {
+    /// Adds a gradient for this frame and returns the value to use in
+    /// [`Quad::fill_gradient`] or [`Quad::border_gradient`].
+    pub fn queue_gradient(&mut self, gradient: &Gradient) -> u32 {
+        self.gradients.push(gradient.into());
+        self.gradients_dirty = true;
+        self.gradients.len() as u32
+    }
+
+    /// Creates the pipeline again on a new device, e.g. after the old one was lost.
+    /// Everything queued so far is kept.
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        self.pipeline = QuadPipeline::new(
+            device,
+            format,
+            sample_count,
+            globals_bind_group_layout,
+        );
+        self.gradients_dirty = !self.gradients.is_empty();
+    }
+
+    /// Sets the clip rect for all quads queued from now on.
+    pub fn set_clip(&mut self, clip: Option<ClipRect>) {
+        self.clip = clip;
+    }
+
+    /// Sets the transform for all quads queued from now on, applied after their own transform.
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    /// Makes the next queued quad start a new batch, e.g. when something else was drawn in between.
+    pub fn split_batch(&mut self) {
+        self.split_batch = true;
+    }
+
+    pub fn queue_quad(&mut self, mut quad: Quad) {
+        quad.transform = quad.transform.then(&self.transform);
+        let index = self.queue.len();
         self.queue.push(quad);
+
+        // Extend the last batch if it uses the same clip rect
+        let split_batch = std::mem::take(&mut self.split_batch);
+        match self.batches.last_mut() {
+            Some(batch) if !split_batch && batch.clip == self.clip && batch.range.end == index => {
+                batch.range.end = index + 1;
+            }
+            _ => self.batches.push(QuadBatch {
+                clip: self.clip,
+                range: index..index + 1,
+            }),
+        }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn draw_queued(
         &mut self,
         device: &Device,
         staging_belt: &mut wgpu::util::StagingBelt,
         encoder: &mut CommandEncoder,
         view: &TextureView,
+        width: u32,
+        height: u32,
         globals_bind_group: &wgpu::BindGroup,
-    ) {
+    ) -> Result<(), RenderError> {
+        let result = self.draw_batches(
+            device,
+            staging_belt,
+            encoder,
+            view,
+            width,
+            height,
+            0..self.batches.len(),
+            globals_bind_group,
+        );
+        self.clear();
+        result
+    }
+
+    /// Draws only the given range of batches, leaving the queue untouched.
+    /// Used to interleave quads with other brushes in submission order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_batches(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        if std::mem::take(&mut self.gradients_dirty) {
+            self.pipeline
+                .upload_gradients(device, staging_belt, encoder, &self.gradients)?;
+        }
         self.pipeline.draw(
             device,
             staging_belt,
             encoder,
             view,
-            self.queue.clone(),
+            (width, height),
+            &self.queue,
+            &self.batches[batches],
             globals_bind_group,
         );
+        Ok(())
+    }
+
+    /// Uploads everything queued so far, so batches can be drawn with [`QuadBrush::draw_prepared`]
+    /// inside of a render pass shared with other brushes.
+    /// Returns `false` if the queue is too big for that, use [`QuadBrush::draw_batches`] then.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+    ) -> Result<bool, RenderError> {
+        if std::mem::take(&mut self.gradients_dirty) {
+            self.pipeline
+                .upload_gradients(device, staging_belt, encoder, &self.gradients)?;
+        }
+        Ok(self
+            .pipeline
+            .prepare(device, staging_belt, encoder, &self.queue))
+    }
+
+    /// Records the given range of batches into `rpass`, after [`QuadBrush::prepare`] returned `true`.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.pipeline.draw_prepared(
+            rpass,
+            (width, height),
+            &self.batches[batches],
+            globals_bind_group,
+        );
+    }
+
+    pub fn clear(&mut self) {
         self.queue.clear();
+        self.batches.clear();
+        self.gradients.clear();
+        self.gradients_dirty = false;
+        self.split_batch = false;
     }
 }
@@ -0,0 +1,132 @@
+// Copy of everything drawn before the backdrop, blurred in place by `fs_blur`
+@group(1) @binding(0)
+var backdrop_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var backdrop_sampler: sampler;
+
+// ====< BLUR >====
+struct BlurInput {
+    @builtin(vertex_index) vertex_index: u32,
+    // One pixel along the direction of the pass, in uv space
+    @location(0) step: vec2<f32>,
+    // In pixels, 0 to copy the texture unchanged
+    @location(1) sigma: f32,
+}
+
+struct BlurOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) step: vec2<f32>,
+    @location(2) sigma: f32,
+}
+
+@vertex
+fn vs_blur(input: BlurInput) -> BlurOutput {
+    var out: BlurOutput;
+
+    // A triangle covering the whole target, the scissor rect limits it to the backdrop
+    let uv = vec2<f32>(f32((input.vertex_index << 1u) & 2u), f32(input.vertex_index & 2u));
+    out.position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    out.uv = uv;
+    out.step = input.step;
+    out.sigma = input.sigma;
+
+    return out;
+}
+
+// One direction of a separable gaussian blur
+@fragment
+fn fs_blur(input: BlurOutput) -> @location(0) vec4<f32> {
+    var color = textureSampleLevel(backdrop_texture, backdrop_sampler, input.uv, 0.0);
+    if (input.sigma <= 0.0) {
+        return color;
+    }
+
+    // Weights fade out after about 3 sigma, sampled in at most 32 steps on each side
+    let extent = 3.0 * input.sigma;
+    let steps = min(ceil(extent), 32.0);
+    let spacing = extent / steps;
+
+    var total = 1.0;
+    for (var i = 1.0; i <= steps; i = i + 1.0) {
+        let x = i * spacing;
+        let weight = exp(-x * x / (2.0 * input.sigma * input.sigma));
+        let offset = input.step * x;
+        color = color + weight * (
+            textureSampleLevel(backdrop_texture, backdrop_sampler, input.uv + offset, 0.0)
+            + textureSampleLevel(backdrop_texture, backdrop_sampler, input.uv - offset, 0.0)
+        );
+        total = total + 2.0 * weight;
+    }
+    return color / total;
+}
+
+// ====< BACKDROP >====
+struct VertexInput {
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) top_left: vec2<f32>,
+    @location(1) bottom_right: vec2<f32>,
+    @location(2) border_radius: vec4<f32>,
+    @location(3) blur_radius: f32,
+    @location(4) tint: vec4<f32>,
+    // Columns of the transform
+    @location(5) transform_x: vec2<f32>,
+    @location(6) transform_y: vec2<f32>,
+    @location(7) translation: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // Position relative to the center of the untransformed rectangle, and its half size
+    @location(0) local: vec2<f32>,
+    @location(1) half_size: vec2<f32>,
+    @location(2) border_radius: vec4<f32>,
+    @location(3) tint: vec4<f32>,
+    // Screen pixels per local unit
+    @location(4) scale: f32,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    let top_left = min(input.top_left, input.bottom_right);
+    let bottom_right = max(input.top_left, input.bottom_right);
+
+    // Corners in triangle strip order
+    var corner: vec2<f32>;
+    switch input.vertex_index {
+        case 0u: { corner = top_left; }
+        case 1u: { corner = vec2<f32>(top_left.x, bottom_right.y); }
+        case 2u: { corner = vec2<f32>(bottom_right.x, top_left.y); }
+        default: { corner = bottom_right; }
+    }
+    let position = transform_point(corner, input.transform_x, input.transform_y, input.translation);
+    out.position = vec4<f32>(screen_to_ndc(position), 0.0, 1.0);
+
+    out.local = corner - (top_left + bottom_right) / 2.0;
+    out.half_size = (bottom_right - top_left) / 2.0;
+    // Corners can't be rounder than half of the rectangle
+    out.border_radius = min(input.border_radius, vec4<f32>(min(out.half_size.x, out.half_size.y)));
+    out.tint = input.tint;
+    out.scale = transform_scale(input.transform_x, input.transform_y);
+
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // Anti-aliased like quads
+    let dist = box_dist(input.local, input.half_size, input.border_radius) * input.scale;
+    let alpha = 1.0 - smoothstep(-0.75, -0.1, dist);
+
+    // The blurred copy lines up with the target pixel for pixel
+    let uv = input.position.xy / globals.u_resolution;
+    let backdrop = textureSampleLevel(backdrop_texture, backdrop_sampler, uv, 0.0);
+
+    // Tint over the blurred backdrop. Layers hold premultiplied colors,
+    // which makes no difference for the opaque frame below them.
+    let tint = input.tint;
+    let color = mix(backdrop.rgb / max(backdrop.a, 0.0001), tint.rgb, tint.a);
+    return vec4<f32>(color, alpha * (tint.a + backdrop.a * (1.0 - tint.a)));
+}
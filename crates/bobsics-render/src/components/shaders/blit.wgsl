@@ -0,0 +1,19 @@
+// Copies a texture of the same size onto the target, pixel by pixel
+
+@group(0) @binding(0)
+var frame: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // A single triangle covering the whole target
+    switch vertex_index {
+        case 0u: { return vec4<f32>(-1.0, -1.0, 0.0, 1.0); }
+        case 1u: { return vec4<f32>(3.0, -1.0, 0.0, 1.0); }
+        default: { return vec4<f32>(-1.0, 3.0, 0.0, 1.0); }
+    }
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    return textureLoad(frame, vec2<i32>(position.xy), 0);
+}
@@ -0,0 +1,52 @@
+// Shared by all bobsics shaders, prepended to each of them by `include_shader!`
+
+struct Globals {
+    u_resolution: vec2<f32>,
+}
+
+@group(0) @binding(0)
+var<uniform> globals: Globals;
+
+fn screen_to_ndc(screen: vec2<f32>) -> vec2<f32> {
+    // scale to ndc
+    let ndc = screen * 2.0 / globals.u_resolution - 1.0;
+
+    // flip y
+    let ndc = vec2<f32>(ndc.x, -ndc.y);
+
+    return ndc;
+}
+
+fn ndc_to_screen(ndc: vec2<f32>) -> vec2<f32> {
+    // flip y
+    let ndc = vec2<f32>(ndc.x, -ndc.y);
+
+    // translate
+    let ndc = ndc + vec2<f32>(1.0, 1.0);
+
+    // scale to screen
+    let screen = ndc * globals.u_resolution / 2.0;
+
+    return screen;
+}
+
+// Signed distance from `p` to a rounded box centered at the origin, with the radii
+// of the top left, top right, bottom right and bottom left corner (y points down)
+fn box_dist(p: vec2<f32>, size: vec2<f32>, radii: vec4<f32>) -> f32 {
+    // Pick the radius of the corner closest to `p`
+    let side = select(radii.xw, radii.yz, p.x > 0.0);
+    let r = select(side.x, side.y, p.y > 0.0);
+
+    let d = abs(p) - size + vec2<f32>(r, r);
+    return length(max(d, vec2<f32>(0.0))) + min(max(d.x, d.y), 0.0) - r;
+}
+
+// Applies a 2D affine transform, given as its columns like `Transform` on the CPU
+fn transform_point(p: vec2<f32>, x_axis: vec2<f32>, y_axis: vec2<f32>, translation: vec2<f32>) -> vec2<f32> {
+    return x_axis * p.x + y_axis * p.y + translation;
+}
+
+// Average scale of a transform, to keep anti-aliasing about one pixel wide
+fn transform_scale(x_axis: vec2<f32>, y_axis: vec2<f32>) -> f32 {
+    return sqrt(abs(x_axis.x * y_axis.y - x_axis.y * y_axis.x));
+}
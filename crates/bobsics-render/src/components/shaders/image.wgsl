@@ -0,0 +1,73 @@
+@group(1) @binding(0)
+var atlas_texture: texture_2d<f32>;
+@group(1) @binding(1)
+var atlas_sampler: sampler;
+
+struct VertexInput {
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) top_left: vec2<f32>,
+    @location(1) bottom_right: vec2<f32>,
+    @location(2) uv_min: vec2<f32>,
+    @location(3) uv_max: vec2<f32>,
+    // How often the uv rect repeats across the image, 1 to stretch it
+    @location(4) uv_repeat: vec2<f32>,
+    @location(5) tint: vec4<f32>,
+    // Columns of the transform
+    @location(6) transform_x: vec2<f32>,
+    @location(7) transform_y: vec2<f32>,
+    @location(8) translation: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) tint: vec4<f32>,
+    // Position within the destination rect (0..1), for repeating uv rects
+    @location(2) local: vec2<f32>,
+    @location(3) uv_min: vec2<f32>,
+    @location(4) uv_max: vec2<f32>,
+    @location(5) uv_repeat: vec2<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    // Pick the corner of the destination rect and the matching uv
+    var corner: vec2<f32>;
+    switch input.vertex_index {
+        case 0u: { corner = vec2<f32>(0.0, 0.0); }
+        case 1u: { corner = vec2<f32>(0.0, 1.0); }
+        case 2u: { corner = vec2<f32>(1.0, 1.0); }
+        default: { corner = vec2<f32>(1.0, 0.0); }
+    }
+
+    let position = transform_point(
+        mix(input.top_left, input.bottom_right, corner),
+        input.transform_x,
+        input.transform_y,
+        input.translation,
+    );
+    out.position = vec4<f32>(screen_to_ndc(position), 0.0, 1.0);
+    out.uv = mix(input.uv_min, input.uv_max, corner);
+    out.tint = input.tint;
+    out.local = corner;
+    out.uv_min = input.uv_min;
+    out.uv_max = input.uv_max;
+    out.uv_repeat = input.uv_repeat;
+
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // Repeating axes wrap around the uv rect. They stay half a texel inside of it,
+    // so filtering doesn't pick up the neighbouring part of the atlas at the seams.
+    let tiled = mix(input.uv_min, input.uv_max, fract(input.local * input.uv_repeat));
+    let half_texel = 0.5 / vec2<f32>(textureDimensions(atlas_texture));
+    let low = input.uv_min + half_texel;
+    let clamped = clamp(tiled, low, max(input.uv_max - half_texel, low));
+    let uv = select(input.uv, clamped, input.uv_repeat != vec2<f32>(1.0));
+
+    return textureSample(atlas_texture, atlas_sampler, uv) * input.tint;
+}
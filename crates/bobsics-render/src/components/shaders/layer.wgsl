@@ -0,0 +1,81 @@
+@group(1) @binding(0)
+var layer_texture: texture_2d<f32>;
+
+struct VertexInput {
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) opacity: f32,
+    // 0 for no mask, 1 for a rounded rect and 2 for a linear fade
+    @location(1) mask_kind: u32,
+    @location(2) mask_rect: vec4<f32>,
+    @location(3) mask_radius: vec4<f32>,
+    // Columns of the transform from screen space into the space of the mask
+    @location(4) screen_to_mask_x: vec2<f32>,
+    @location(5) screen_to_mask_y: vec2<f32>,
+    @location(6) screen_to_mask_translation: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) opacity: f32,
+    @location(1) @interpolate(flat) mask_kind: u32,
+    @location(2) mask_rect: vec4<f32>,
+    @location(3) mask_radius: vec4<f32>,
+    @location(4) screen_to_mask_x: vec2<f32>,
+    @location(5) screen_to_mask_y: vec2<f32>,
+    @location(6) screen_to_mask_translation: vec2<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    // A single triangle covering the whole target
+    switch input.vertex_index {
+        case 0u: { out.position = vec4<f32>(-1.0, -1.0, 0.0, 1.0); }
+        case 1u: { out.position = vec4<f32>(3.0, -1.0, 0.0, 1.0); }
+        default: { out.position = vec4<f32>(-1.0, 3.0, 0.0, 1.0); }
+    }
+
+    out.opacity = input.opacity;
+    out.mask_kind = input.mask_kind;
+    out.mask_rect = input.mask_rect;
+    out.mask_radius = input.mask_radius;
+    out.screen_to_mask_x = input.screen_to_mask_x;
+    out.screen_to_mask_y = input.screen_to_mask_y;
+    out.screen_to_mask_translation = input.screen_to_mask_translation;
+
+    return out;
+}
+
+fn mask_alpha(input: VertexOutput) -> f32 {
+    let p = transform_point(
+        input.position.xy,
+        input.screen_to_mask_x,
+        input.screen_to_mask_y,
+        input.screen_to_mask_translation,
+    );
+
+    if (input.mask_kind == 1u) {
+        let top_left = min(input.mask_rect.xy, input.mask_rect.zw);
+        let bottom_right = max(input.mask_rect.xy, input.mask_rect.zw);
+        let half_size = (bottom_right - top_left) / 2.0;
+        let radii = min(input.mask_radius, vec4<f32>(min(half_size.x, half_size.y)));
+
+        // Distance in screen pixels, for one pixel of anti-aliasing
+        let scale = transform_scale(input.screen_to_mask_x, input.screen_to_mask_y);
+        let dist = box_dist(p - top_left - half_size, half_size, radii) / max(scale, 0.0001);
+        return 1.0 - smoothstep(-0.5, 0.5, dist);
+    } else if (input.mask_kind == 2u) {
+        let dir = input.mask_rect.zw - input.mask_rect.xy;
+        let t = dot(p - input.mask_rect.xy, dir) / max(dot(dir, dir), 0.0001);
+        return 1.0 - clamp(t, 0.0, 1.0);
+    }
+    return 1.0;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // Layers hold premultiplied colors, so all channels are scaled
+    let color = textureLoad(layer_texture, vec2<i32>(input.position.xy), 0);
+    return color * (input.opacity * mask_alpha(input));
+}
@@ -0,0 +1,30 @@
+struct VertexInput {
+    @location(0) position: vec2<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) coverage: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) coverage: vec2<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    out.position = vec4<f32>(screen_to_ndc(input.position), 0.0, 1.0);
+    out.color = input.color;
+    out.coverage = input.coverage;
+
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // Fade out over the last pixel before the coverage drops to 0
+    let alpha = clamp(input.coverage.y - abs(input.coverage.x), 0.0, 1.0);
+
+    return vec4<f32>(input.color.rgb, alpha * input.color.a);
+}
@@ -1,128 +1,308 @@
-struct Globals {
-    u_resolution: vec2<f32>,
-}
-
-@group(0) @binding(0)
-var<uniform> globals: Globals;
-
-struct VertexInput {
-    @builtin(vertex_index) vertex_index: u32,
-    @location(0) top_left: vec2<f32>,
-    @location(1) bottom_right: vec2<f32>,
-    @location(2) color: vec4<f32>,
-    @location(3) border_radius: f32,
-    @location(4) border_color: vec4<f32>,
-    @location(5) border_width: f32,
-}
-
-struct VertexOutput {
-    @builtin(position) position: vec4<f32>,
-    @location(0) color: vec4<f32>,
-    @location(1) border_radius: f32,
-    @location(2) top_left: vec2<f32>,
-    @location(3) bottom_right: vec2<f32>,
-    @location(4) border_color: vec4<f32>,
-    @location(5) border_width: f32,
-}
-
-fn screen_to_ndc(screen: vec2<f32>) -> vec2<f32> {
-    // scale to ndc
-    let ndc = screen * 2.0 / globals.u_resolution - 1.0;
-
-    // flip y
-    let ndc = vec2<f32>(ndc.x, -ndc.y);
-
-    return ndc;
-}
-
-@vertex
-fn vs_main(input: VertexInput) -> VertexOutput {
-    var out: VertexOutput;
-
-    var top_left: vec2<f32> = screen_to_ndc(input.top_left);
-    var bottom_right: vec2<f32> = screen_to_ndc(input.bottom_right);
-
-    // Calculate the top left and bottom right coordinates of the rectangle
-    if (top_left.x <= bottom_right.x) {
-        top_left.x = top_left.x;
-        bottom_right.x = bottom_right.x;
-    } else {
-        top_left.x = bottom_right.x;
-        bottom_right.x = top_left.x;
-    }
-    if (top_left.y > bottom_right.y) {
-        top_left.y = top_left.y;
-        bottom_right.y = bottom_right.y;
-    } else {
-        top_left.y = bottom_right.y;
-        bottom_right.y = top_left.y;
-    }
-
-    // Draw the rectangle (CCW winding order)
-    switch input.vertex_index {
-        case 0u, 4u: { out.position = vec4<f32>(top_left, 0.0, 1.0); }
-        case 1u: { out.position = vec4<f32>(top_left.x, bottom_right.y, 0.0, 1.0); }
-        case 2u, 5u: { out.position = vec4<f32>(bottom_right, 0.0, 1.0); }
-        case 3u: { out.position = vec4<f32>(bottom_right.x, top_left.y, 0.0, 1.0); }
-        default: { out.position = vec4<f32>(0.0, 0.0, 0.0, 0.0); }
-    }
-
-    out.color = input.color;
-    out.border_radius = input.border_radius;
-    out.border_color = input.border_color;
-    out.border_width = input.border_width;
-
-    out.top_left = top_left;
-    out.bottom_right = bottom_right;
-
-    return out;
-}
-
-
-fn ndc_to_screen(ndc: vec2<f32>) -> vec2<f32> {
-    // flip y
-    let ndc = vec2<f32>(ndc.x, -ndc.y);
-
-    // translate
-    let ndc = ndc + vec2<f32>(1.0, 1.0);
-
-    // scale to screen
-    let screen = ndc * globals.u_resolution / 2.0;
-
-    return screen;
-}
-
-fn box_dist(p: vec2<f32>, size: vec2<f32>, r: f32) -> f32 {
-    let size = size - vec2<f32>(r, r);
-    let d = abs(p) - size;
-    return length(max(d, vec2<f32>(0.0))) + min(max(d.x, d.y), 0.0) - r;
-}
-
-@fragment
-fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
-    // Translate 
-    let top_left = ndc_to_screen(input.top_left);
-    let bottom_right = ndc_to_screen(input.bottom_right);
-
-    let size = bottom_right - top_left;
-    let center = top_left + size / 2.0;
-
-    var color = input.color;
-
-    // Move p relative to the center of the rectangle
-    let p = input.position.xy - center;
-
-    // calculate distance to the rectangle
-    let dist = box_dist(p, size/2.0, input.border_radius);
-
-    // Calculate the alpha
-    let alpha = 1.0 - smoothstep(-0.75, -0.1, dist);
-
-    // Draw the border if inner distance is less than the border width
-    if (dist > -input.border_width && input.border_width > 0.0) {
-        color = input.border_color;
-    }
-
-    // Return the color with the alpha
-    return vec4<f32>(color.rgb, alpha * color.a);
-}
\ No newline at end of file
+let QUARTER_TURN: f32 = 1.57079633;
+
+struct Gradient {
+    // kind, angle, radius, stop count
+    params: vec4<f32>,
+    center: vec4<f32>,
+    colors: array<vec4<f32>, 8>,
+    offsets: array<vec4<f32>, 2>,
+}
+
+@group(1) @binding(0)
+var<storage, read> gradients: array<Gradient>;
+
+struct VertexInput {
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) top_left: vec2<f32>,
+    @location(1) bottom_right: vec2<f32>,
+    @location(2) color: vec4<f32>,
+    // Top left, top right, bottom right, bottom left
+    @location(3) border_radius: vec4<f32>,
+    // Top, right, bottom, left
+    @location(4) border_color_top: vec4<f32>,
+    @location(5) border_color_right: vec4<f32>,
+    @location(6) border_color_bottom: vec4<f32>,
+    @location(7) border_color_left: vec4<f32>,
+    @location(8) border_width: vec4<f32>,
+    @location(9) fill_gradient: u32,
+    @location(10) border_gradient: u32,
+    // 0 for solid, 1 for dashed and 2 for dotted
+    @location(11) border_style: u32,
+    // Dash and gap length
+    @location(12) border_dash: vec2<f32>,
+    // Columns of the transform
+    @location(13) transform_x: vec2<f32>,
+    @location(14) transform_y: vec2<f32>,
+    @location(15) translation: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) border_radius: vec4<f32>,
+    // Position relative to the top left of the untransformed rectangle, and its size
+    @location(2) local: vec2<f32>,
+    @location(3) size: vec2<f32>,
+    @location(4) border_color_top: vec4<f32>,
+    @location(5) border_color_right: vec4<f32>,
+    @location(6) border_color_bottom: vec4<f32>,
+    @location(7) border_color_left: vec4<f32>,
+    @location(8) border_width: vec4<f32>,
+    @location(9) @interpolate(flat) fill_gradient: u32,
+    @location(10) @interpolate(flat) border_gradient: u32,
+    // Screen pixels per local unit
+    @location(11) scale: f32,
+    @location(12) @interpolate(flat) border_style: u32,
+    @location(13) border_dash: vec2<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    let top_left = min(input.top_left, input.bottom_right);
+    let bottom_right = max(input.top_left, input.bottom_right);
+
+    // Draw the rectangle (CCW winding order)
+    var corner: vec2<f32>;
+    switch input.vertex_index {
+        case 0u, 4u: { corner = top_left; }
+        case 1u: { corner = vec2<f32>(top_left.x, bottom_right.y); }
+        case 2u, 5u: { corner = bottom_right; }
+        default: { corner = vec2<f32>(bottom_right.x, top_left.y); }
+    }
+    let position = transform_point(corner, input.transform_x, input.transform_y, input.translation);
+    out.position = vec4<f32>(screen_to_ndc(position), 0.0, 1.0);
+
+    out.color = input.color;
+    out.border_radius = input.border_radius;
+    out.border_color_top = input.border_color_top;
+    out.border_color_right = input.border_color_right;
+    out.border_color_bottom = input.border_color_bottom;
+    out.border_color_left = input.border_color_left;
+    out.border_width = input.border_width;
+    out.fill_gradient = input.fill_gradient;
+    out.border_gradient = input.border_gradient;
+    out.border_style = input.border_style;
+    out.border_dash = input.border_dash;
+
+    out.local = corner - top_left;
+    out.size = bottom_right - top_left;
+    out.scale = transform_scale(input.transform_x, input.transform_y);
+
+    return out;
+}
+
+fn gradient_offset(index: u32, stop: u32) -> f32 {
+    return gradients[index].offsets[stop / 4u][stop % 4u];
+}
+
+// Samples gradient `index` at point `p`, relative to the top left of a rect of the given size
+fn sample_gradient(index: u32, p: vec2<f32>, size: vec2<f32>) -> vec4<f32> {
+    let params = gradients[index].params;
+    let center = gradients[index].center.xy * size;
+
+    var t: f32;
+    if (params.x < 0.5) {
+        // Linear, with the gradient line spanning the rect like in CSS
+        let dir = vec2<f32>(sin(params.y), -cos(params.y));
+        let half_length = (abs(size.x * dir.x) + abs(size.y * dir.y)) / 2.0;
+        t = dot(p - center, dir) / max(half_length, 0.0001) * 0.5 + 0.5;
+    } else {
+        // Radial
+        t = length(p - center) / max(params.z, 0.0001);
+    }
+
+    let count = u32(params.w);
+    if (count == 0u) {
+        return vec4<f32>(0.0);
+    }
+
+    var color = gradients[index].colors[0];
+    for (var i = 1u; i < count; i = i + 1u) {
+        let start = gradient_offset(index, i - 1u);
+        let end = gradient_offset(index, i);
+        if (t > start) {
+            let local_t = clamp((t - start) / max(end - start, 0.0001), 0.0, 1.0);
+            color = mix(gradients[index].colors[i - 1u], gradients[index].colors[i], local_t);
+        }
+    }
+    return color;
+}
+
+// Color of the border side closest to `p`, relative to the top left of a rect of the given size.
+// Distances are scaled by the side widths, so corners are split diagonally like in CSS.
+fn border_side_color(input: VertexOutput, p: vec2<f32>, size: vec2<f32>) -> vec4<f32> {
+    let widths = input.border_width;
+    let scaled = vec4<f32>(p.y, size.x - p.x, size.y - p.y, p.x) / max(widths, vec4<f32>(0.0001));
+    let side_dist = select(vec4<f32>(1e9), scaled, widths > vec4<f32>(0.0));
+
+    let min_dist = min(min(side_dist.x, side_dist.y), min(side_dist.z, side_dist.w));
+    if (side_dist.x == min_dist) {
+        return input.border_color_top;
+    } else if (side_dist.y == min_dist) {
+        return input.border_color_right;
+    } else if (side_dist.z == min_dist) {
+        return input.border_color_bottom;
+    }
+    return input.border_color_left;
+}
+
+// Position of the point closest to `p` along the edge of a rounded box centered at the origin,
+// clockwise from the middle of its top left corner. Returns that position, the length of the
+// whole edge and the border width there, the wider of the two sides in the corners.
+fn perimeter_position(p: vec2<f32>, half_size: vec2<f32>, radii: vec4<f32>, widths: vec4<f32>) -> vec3<f32> {
+    let arcs = radii * QUARTER_TURN;
+    let top = max(2.0 * half_size.x - radii.x - radii.y, 0.0);
+    let right = max(2.0 * half_size.y - radii.y - radii.z, 0.0);
+    let bottom = max(2.0 * half_size.x - radii.z - radii.w, 0.0);
+    let left = max(2.0 * half_size.y - radii.w - radii.x, 0.0);
+    let total = arcs.x + top + arcs.y + right + arcs.z + bottom + arcs.w + left;
+
+    // Where each straight side and the corner after it start
+    let top_start = arcs.x / 2.0;
+    let right_start = top_start + top + arcs.y;
+    let bottom_start = right_start + right + arcs.z;
+    let left_start = bottom_start + bottom + arcs.w;
+
+    // Inside the square of a rounded corner, the position follows its arc
+    let top_left = -half_size + radii.x;
+    let top_right = vec2<f32>(half_size.x - radii.y, -half_size.y + radii.y);
+    let bottom_right = half_size - radii.z;
+    let bottom_left = vec2<f32>(-half_size.x + radii.w, half_size.y - radii.w);
+    if (p.x < top_left.x && p.y < top_left.y) {
+        let d = p - top_left;
+        let angle = clamp(atan2(-d.y, -d.x), 0.0, QUARTER_TURN);
+        return vec3<f32>(angle * radii.x - top_start, total, max(widths.w, widths.x));
+    }
+    if (p.x > top_right.x && p.y < top_right.y) {
+        let d = p - top_right;
+        let angle = clamp(atan2(d.x, -d.y), 0.0, QUARTER_TURN);
+        return vec3<f32>(top_start + top + angle * radii.y, total, max(widths.x, widths.y));
+    }
+    if (p.x > bottom_right.x && p.y > bottom_right.y) {
+        let d = p - bottom_right;
+        let angle = clamp(atan2(d.y, d.x), 0.0, QUARTER_TURN);
+        return vec3<f32>(right_start + right + angle * radii.z, total, max(widths.y, widths.z));
+    }
+    if (p.x < bottom_left.x && p.y > bottom_left.y) {
+        let d = p - bottom_left;
+        let angle = clamp(atan2(-d.x, d.y), 0.0, QUARTER_TURN);
+        return vec3<f32>(bottom_start + bottom + angle * radii.w, total, max(widths.z, widths.w));
+    }
+
+    // Elsewhere it follows the closest straight side, split diagonally at square corners
+    let dists = vec4<f32>(p.y + half_size.y, half_size.x - p.x, half_size.y - p.y, p.x + half_size.x);
+    let min_dist = min(min(dists.x, dists.y), min(dists.z, dists.w));
+    if (dists.x == min_dist) {
+        return vec3<f32>(top_start + clamp(p.x - top_left.x, 0.0, top), total, widths.x);
+    } else if (dists.y == min_dist) {
+        return vec3<f32>(right_start + clamp(p.y - top_right.y, 0.0, right), total, widths.y);
+    } else if (dists.z == min_dist) {
+        return vec3<f32>(bottom_start + clamp(bottom_right.x - p.x, 0.0, bottom), total, widths.z);
+    }
+    return vec3<f32>(left_start + clamp(bottom_left.y - p.y, 0.0, left), total, widths.w);
+}
+
+// Coverage of the dashes or dots of the border at `local`, relative to the top left of a rect of the given size
+fn border_pattern(input: VertexOutput, local: vec2<f32>, size: vec2<f32>, radii: vec4<f32>) -> f32 {
+    // The pattern runs along the middle of the border
+    let widths = input.border_width;
+    let center_top_left = widths.wx / 2.0;
+    let center_bottom_right = max(size - widths.yz / 2.0, center_top_left);
+    let center_radii = max(radii - max(widths.wxyz, widths.xyzw) / 2.0, vec4<f32>(0.0));
+    let half_size = (center_bottom_right - center_top_left) / 2.0;
+    let p = local - (center_top_left + center_bottom_right) / 2.0;
+    let perimeter = perimeter_position(p, half_size, center_radii, widths);
+
+    // Dots are as wide as the border
+    var dash = input.border_dash.x;
+    if (input.border_style == 2u) {
+        dash = max(max(widths.x, widths.y), max(widths.z, widths.w));
+    }
+    let period = dash + input.border_dash.y;
+    if (period <= 0.0 || perimeter.y <= 0.0) {
+        return 1.0;
+    }
+
+    // Scale the pattern so it repeats a whole number of times around the border,
+    // with a dash or dot centered on the top left corner.
+    // Dashes scale along with their gaps, dots keep their size
+    let count = max(round(perimeter.y / period), 1.0);
+    let spacing = perimeter.y / count;
+    let along = abs(perimeter.x - spacing * round(perimeter.x / spacing));
+
+    // Anti-aliased over about one pixel
+    var dist: f32;
+    if (input.border_style == 2u) {
+        let across = box_dist(p, half_size, center_radii);
+        dist = length(vec2<f32>(along, across)) - perimeter.z / 2.0;
+    } else {
+        dist = along - dash * spacing / period / 2.0;
+    }
+    return clamp(0.5 - dist * input.scale, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let size = input.size;
+    let local = input.local;
+
+    var color = input.color;
+    if (input.fill_gradient > 0u) {
+        color = sample_gradient(input.fill_gradient - 1u, local, size);
+    }
+
+    // Corners can't be rounder than half of the rectangle
+    let radii = min(input.border_radius, vec4<f32>(min(size.x, size.y) / 2.0));
+
+    // Move p relative to the center of the rectangle
+    let p = local - size / 2.0;
+
+    // calculate distance to the rectangle, in screen pixels
+    let dist = box_dist(p, size / 2.0, radii) * input.scale;
+
+    // Calculate the alpha
+    let alpha = 1.0 - smoothstep(-0.75, -0.1, dist);
+
+    let widths = input.border_width;
+    if (any(widths > vec4<f32>(0.0))) {
+        // The inner edge of the border is the rectangle shrunk by the width of each side,
+        // with each corner shrunk by the widest of its two sides
+        let inner_top_left = vec2<f32>(widths.w, widths.x);
+        let inner_bottom_right = max(size - widths.yz, inner_top_left);
+        let inner_radii = max(
+            radii - max(widths.wxyz, widths.xyzw),
+            vec4<f32>(0.0),
+        );
+        let inner_dist = box_dist(
+            local - (inner_top_left + inner_bottom_right) / 2.0,
+            (inner_bottom_right - inner_top_left) / 2.0,
+            inner_radii,
+        );
+
+        // Draw the border outside of the inner edge
+        if (inner_dist > 0.0) {
+            var border: vec4<f32>;
+            if (input.border_gradient > 0u) {
+                border = sample_gradient(input.border_gradient - 1u, local, size);
+            } else {
+                border = border_side_color(input, local, size);
+            }
+
+            // The fill shows through the gaps of dashed and dotted borders,
+            // blended with premultiplied colors like the strokes of shapes
+            if (input.border_style != 0u) {
+                let coverage = border_pattern(input, local, size, radii);
+                let fill = vec4<f32>(color.rgb * color.a, color.a);
+                let stroke = vec4<f32>(border.rgb * border.a, border.a);
+                let mixed = mix(fill, stroke, coverage);
+                border = vec4<f32>(mixed.rgb / max(mixed.a, 0.0001), mixed.a);
+            }
+            color = border;
+        }
+    }
+
+    // Return the color with the alpha
+    return vec4<f32>(color.rgb, alpha * color.a);
+}
@@ -0,0 +1,90 @@
+struct VertexInput {
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) top_left: vec2<f32>,
+    @location(1) bottom_right: vec2<f32>,
+    @location(2) border_radius: vec4<f32>,
+    @location(3) offset: vec2<f32>,
+    @location(4) blur_radius: f32,
+    @location(5) spread: f32,
+    @location(6) color: vec4<f32>,
+    // Columns of the transform
+    @location(7) transform_x: vec2<f32>,
+    @location(8) transform_y: vec2<f32>,
+    @location(9) translation: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    // Position relative to the center of the untransformed shadow box, and its half size
+    @location(1) local: vec2<f32>,
+    @location(2) half_size: vec2<f32>,
+    @location(3) radius: vec4<f32>,
+    @location(4) blur_radius: f32,
+    // Screen pixels per local unit
+    @location(5) scale: f32,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    let top_left = min(input.top_left, input.bottom_right) + input.offset;
+    let bottom_right = max(input.top_left, input.bottom_right) + input.offset;
+
+    // The blur fades out after about 3 sigma, sigma being half of the blur radius,
+    // plus a pixel for anti-aliasing
+    let scale = transform_scale(input.transform_x, input.transform_y);
+    let extent = vec2<f32>(
+        max(input.spread, 0.0) + input.blur_radius * 1.5 + 1.0 / max(scale, 0.0001),
+    );
+
+    // Corners in triangle strip order
+    var corner: vec2<f32>;
+    switch input.vertex_index {
+        case 0u: { corner = top_left - extent; }
+        case 1u: { corner = vec2<f32>(top_left.x - extent.x, bottom_right.y + extent.y); }
+        case 2u: { corner = vec2<f32>(bottom_right.x + extent.x, top_left.y - extent.y); }
+        default: { corner = bottom_right + extent; }
+    }
+    let position = transform_point(corner, input.transform_x, input.transform_y, input.translation);
+    out.position = vec4<f32>(screen_to_ndc(position), 0.0, 1.0);
+
+    out.color = input.color;
+    out.local = corner - (top_left + bottom_right) / 2.0;
+    out.scale = scale;
+    out.half_size = max((bottom_right - top_left) / 2.0 + input.spread, vec2<f32>(0.0));
+    out.radius = clamp(
+        input.border_radius + input.spread,
+        vec4<f32>(0.0),
+        vec4<f32>(min(out.half_size.x, out.half_size.y)),
+    );
+    out.blur_radius = input.blur_radius;
+
+    return out;
+}
+
+// Abramowitz and Stegun approximation of the error function
+fn erf(x: f32) -> f32 {
+    let s = sign(x);
+    let a = abs(x);
+    var r = 1.0 + (0.278393 + (0.230389 + 0.078108 * (a * a)) * a) * a;
+    r = r * r;
+    return s - s / (r * r);
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let dist = box_dist(input.local, input.half_size, input.radius);
+
+    // Coverage of a gaussian blurred edge at the given distance
+    var alpha: f32;
+    if (input.blur_radius > 0.0) {
+        let sigma = input.blur_radius / 2.0;
+        alpha = 0.5 - 0.5 * erf(dist / (sigma * sqrt(2.0)));
+    } else {
+        alpha = 1.0 - smoothstep(-0.5, 0.5, dist * input.scale);
+    }
+
+    return vec4<f32>(input.color.rgb, alpha * input.color.a);
+}
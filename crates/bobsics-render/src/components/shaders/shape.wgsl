@@ -0,0 +1,151 @@
+let PI: f32 = 3.14159265;
+
+struct VertexInput {
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) center: vec2<f32>,
+    @location(1) radii: vec2<f32>,
+    // Degrees, clockwise from the positive x axis
+    @location(2) start_angle: f32,
+    @location(3) sweep_angle: f32,
+    @location(4) thickness: f32,
+    @location(5) round_caps: u32,
+    @location(6) color: vec4<f32>,
+    @location(7) stroke_color: vec4<f32>,
+    @location(8) stroke_width: f32,
+    // Columns of the transform
+    @location(9) transform_x: vec2<f32>,
+    @location(10) transform_y: vec2<f32>,
+    @location(11) translation: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    // Position relative to the center of the untransformed shape
+    @location(0) local: vec2<f32>,
+    @location(1) radii: vec2<f32>,
+    // Start and end of the sweep in radians, the end being the larger one
+    @location(2) angles: vec2<f32>,
+    @location(3) thickness: f32,
+    @location(4) @interpolate(flat) round_caps: u32,
+    @location(5) color: vec4<f32>,
+    @location(6) stroke_color: vec4<f32>,
+    @location(7) stroke_width: f32,
+    // Screen pixels per local unit
+    @location(8) scale: f32,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    let radii = abs(input.radii);
+
+    // Corners of the bounding box in triangle strip order
+    var corner: vec2<f32>;
+    switch input.vertex_index {
+        case 0u: { corner = -radii; }
+        case 1u: { corner = vec2<f32>(-radii.x, radii.y); }
+        case 2u: { corner = vec2<f32>(radii.x, -radii.y); }
+        default: { corner = radii; }
+    }
+    let position = transform_point(input.center + corner, input.transform_x, input.transform_y, input.translation);
+    out.position = vec4<f32>(screen_to_ndc(position), 0.0, 1.0);
+
+    // Negative sweeps go counterclockwise
+    var start = input.start_angle;
+    var sweep = input.sweep_angle;
+    if (sweep < 0.0) {
+        start = start + sweep;
+        sweep = -sweep;
+    }
+    out.angles = radians(vec2<f32>(start, start + min(sweep, 360.0)));
+
+    out.local = corner;
+    out.radii = radii;
+    out.thickness = clamp(input.thickness, 0.0, min(radii.x, radii.y));
+    out.round_caps = input.round_caps;
+    out.color = input.color;
+    out.stroke_color = input.stroke_color;
+    out.stroke_width = input.stroke_width;
+    out.scale = transform_scale(input.transform_x, input.transform_y);
+
+    return out;
+}
+
+// Signed distance to an ellipse centered at the origin.
+// Exact for circles, and a close approximation near the edge of ellipses.
+fn ellipse_dist(p: vec2<f32>, radii: vec2<f32>) -> f32 {
+    if (radii.x == radii.y) {
+        return length(p) - radii.x;
+    }
+    let k0 = length(p / radii);
+    let k1 = length(p / (radii * radii));
+    if (k1 < 0.0001) {
+        return -min(radii.x, radii.y);
+    }
+    return k0 * (k0 - 1.0) / k1;
+}
+
+// Signed distance to the wedge from the ray along `a` clockwise to the ray along `b`
+fn wedge_dist(p: vec2<f32>, a: vec2<f32>, b: vec2<f32>, sweep: f32) -> f32 {
+    let dist_a = length(p - a * max(dot(p, a), 0.0));
+    let dist_b = length(p - b * max(dot(p, b), 0.0));
+
+    // Wedges wider than half a turn contain everything on either side of the two rays
+    let after_a = a.x * p.y - a.y * p.x >= 0.0;
+    let before_b = p.x * b.y - p.y * b.x >= 0.0;
+    var inside = after_a && before_b;
+    if (sweep > PI) {
+        inside = after_a || before_b;
+    }
+    return select(1.0, -1.0, inside) * min(dist_a, dist_b);
+}
+
+fn shape_dist(input: VertexOutput) -> f32 {
+    let p = input.local;
+    let radii = input.radii;
+
+    // Only the band along the edge for rings and arcs
+    var dist = ellipse_dist(p, radii);
+    let half_thickness = input.thickness / 2.0;
+    if (half_thickness > 0.0) {
+        dist = abs(dist + half_thickness) - half_thickness;
+    }
+
+    let sweep = input.angles.y - input.angles.x;
+    if (sweep >= 2.0 * PI) {
+        return dist;
+    }
+
+    // Angles are parametric like in `PathBuilder::arc`, so the rays are stretched with the ellipse
+    let start = vec2<f32>(cos(input.angles.x), sin(input.angles.x));
+    let end = vec2<f32>(cos(input.angles.y), sin(input.angles.y));
+    let wedge = wedge_dist(p, normalize(start * radii), normalize(end * radii), sweep);
+
+    // Past the ends, round caps are circles centered on the middle of the band
+    if (input.round_caps == 1u && half_thickness > 0.0 && wedge > 0.0) {
+        let middle = radii - half_thickness;
+        return min(length(p - start * middle), length(p - end * middle)) - half_thickness;
+    }
+    return max(dist, wedge);
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    // Distance to the shape in screen pixels, anti-aliased like quads
+    let dist = shape_dist(input) * input.scale;
+    let alpha = 1.0 - smoothstep(-0.75, -0.1, dist);
+
+    var color = input.color;
+    if (input.stroke_width > 0.0) {
+        // Blend between fill and stroke with premultiplied colors,
+        // so a transparent fill doesn't darken the inner edge of the stroke
+        let t = clamp(dist + input.stroke_width * input.scale + 0.5, 0.0, 1.0);
+        let fill = vec4<f32>(color.rgb * color.a, color.a);
+        let stroke = vec4<f32>(input.stroke_color.rgb * input.stroke_color.a, input.stroke_color.a);
+        let mixed = mix(fill, stroke, t);
+        color = vec4<f32>(mixed.rgb / max(mixed.a, 0.0001), mixed.a);
+    }
+
+    return vec4<f32>(color.rgb, alpha * color.a);
+}
@@ -0,0 +1,53 @@
+// Glyphs rasterized into the coverage cache by `glyph_brush`
+@group(1) @binding(0)
+var glyph_cache: texture_2d<f32>;
+@group(1) @binding(1)
+var glyph_sampler: sampler;
+
+struct VertexInput {
+    @builtin(vertex_index) vertex_index: u32,
+    @location(0) top_left: vec2<f32>,
+    @location(1) bottom_right: vec2<f32>,
+    @location(2) uv_min: vec2<f32>,
+    @location(3) uv_max: vec2<f32>,
+    @location(4) color: vec4<f32>,
+    // Columns of the transform of the text batch
+    @location(5) transform_x: vec2<f32>,
+    @location(6) transform_y: vec2<f32>,
+    @location(7) translation: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(input: VertexInput) -> VertexOutput {
+    var out: VertexOutput;
+
+    // Corners of the triangle strip
+    let corner = vec2<f32>(f32(input.vertex_index & 1u), f32(input.vertex_index >> 1u));
+
+    let position = transform_point(
+        mix(input.top_left, input.bottom_right, corner),
+        input.transform_x,
+        input.transform_y,
+        input.translation,
+    );
+    out.position = vec4<f32>(screen_to_ndc(position), 0.0, 1.0);
+    out.uv = mix(input.uv_min, input.uv_max, corner);
+    out.color = input.color;
+
+    return out;
+}
+
+@fragment
+fn fs_main(input: VertexOutput) -> @location(0) vec4<f32> {
+    let alpha = textureSample(glyph_cache, glyph_sampler, input.uv).r;
+    if alpha <= 0.0 {
+        discard;
+    }
+    return input.color * vec4<f32>(1.0, 1.0, 1.0, alpha);
+}
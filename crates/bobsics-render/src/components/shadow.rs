@@ -0,0 +1,436 @@
+use std::ops::Range;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{CommandEncoder, Device, TextureFormat, TextureView};
+
+use crate::{ClipRect, RenderError, Transform};
+
+use super::{instances::InstanceBuffer, QuadBatch};
+
+const INITIAL_SHADOW_CAPACITY: usize = 1_000;
+
+/// Blurred shadow of a rounded box, usually queued right before the [`crate::Quad`] casting it.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Shadow {
+    // Box casting the shadow
+    pub top_left: [f32; 2],
+    pub bottom_right: [f32; 2],
+    // Top left, top right, bottom right and bottom left corner, like `Quad::border_radius`
+    pub border_radius: [f32; 4],
+
+    pub offset: [f32; 2],
+    pub blur_radius: f32,
+    // Grows (or shrinks, if negative) the shadow box on every side
+    pub spread: f32,
+    pub color: [f32; 4],
+    // Applied to the shadow before the transform of the brush, like `Quad::transform`
+    pub transform: Transform,
+}
+
+impl Default for Shadow {
+    fn default() -> Self {
+        Self {
+            transform: Transform::IDENTITY,
+            ..Self::zeroed()
+        }
+    }
+}
+
+impl Shadow {
+    const ATTRIBS: [wgpu::VertexAttribute; 10] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x4,
+        3 => Float32x2,
+        4 => Float32,
+        5 => Float32,
+        6 => Float32x4,
+        7 => Float32x2,
+        8 => Float32x2,
+        9 => Float32x2
+    ];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ShadowPipeline {
+    instances: InstanceBuffer<Shadow>,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ShadowPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        // Create shader
+        let shader = device.create_shader_module(include_shader!("shadow.wgsl"));
+
+        // Create buffers
+        let instance_buffer =
+            InstanceBuffer::new(device, "Shadow instance buffer", INITIAL_SHADOW_CAPACITY);
+
+        // Create pipeline
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shadow Pipeline Layout"),
+                bind_group_layouts: &[globals_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shadow Render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Shadow::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self {
+            instances: instance_buffer,
+            pipeline: render_pipeline,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        target_size: (u32, u32),
+        instances: &[Shadow],
+        batches: &[QuadBatch],
+        globals_bind_group: &wgpu::BindGroup,
+    ) {
+        // Only the shadows covered by the batches have to be uploaded
+        let (span_start, span_end) = match (batches.first(), batches.last()) {
+            (Some(first), Some(last)) => (first.range.start, last.range.end),
+            _ => return,
+        };
+        if span_start >= span_end {
+            return;
+        }
+        self.instances.reserve(device, span_end - span_start);
+        let capacity = self.instances.capacity();
+
+        // Anything that still doesn't fit is drawn in chunks
+        for (chunk_index, chunk) in instances[span_start..span_end]
+            .chunks(capacity)
+            .enumerate()
+        {
+            let chunk_start = span_start + chunk_index * capacity;
+            let chunk_end = chunk_start + chunk.len();
+
+            // Set buffer
+            self.instances.write(device, staging_belt, encoder, chunk);
+
+            // Render pass
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            self.record(
+                &mut rpass,
+                target_size,
+                batches,
+                chunk_start..chunk_end,
+                globals_bind_group,
+            );
+        }
+    }
+
+    /// Uploads all `instances` at once, so they can be drawn with [`ShadowPipeline::draw_prepared`]
+    /// inside of a render pass shared with other pipelines.
+    /// Returns `false` if they don't fit into a single buffer, use [`ShadowPipeline::draw`] then.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        instances: &[Shadow],
+    ) -> bool {
+        self.instances.reserve(device, instances.len());
+        if instances.len() > self.instances.capacity() {
+            return false;
+        }
+        self.instances.write(device, staging_belt, encoder, instances);
+        true
+    }
+
+    /// Records the batches into `rpass`, after [`ShadowPipeline::prepare`] uploaded their instances.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        batches: &[QuadBatch],
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.record(
+            rpass,
+            target_size,
+            batches,
+            0..self.instances.capacity(),
+            globals_bind_group,
+        );
+    }
+
+    // Draws the part of the batches within `uploaded`, the instances at the start of the buffer
+    fn record<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        batches: &[QuadBatch],
+        uploaded: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.instances.slice());
+
+        for batch in batches {
+            let start = batch.range.start.max(uploaded.start);
+            let end = batch.range.end.min(uploaded.end);
+            if start >= end {
+                continue;
+            }
+
+            // Restrict drawing to the clip rect of the batch
+            let [x, y, width, height] = match batch.clip {
+                Some(clip) => match clip.to_scissor(target_size.0, target_size.1) {
+                    Some(scissor) => scissor,
+                    None => continue,
+                },
+                None => [0, 0, target_size.0, target_size.1],
+            };
+            rpass.set_scissor_rect(x, y, width, height);
+
+            rpass.draw(
+                0..4,
+                (start - uploaded.start) as u32..(end - uploaded.start) as u32,
+            );
+        }
+    }
+}
+
+// ====< BRUSH >====
+pub struct ShadowBrush {
+    pub pipeline: ShadowPipeline,
+    pub queue: Vec<Shadow>,
+    pub batches: Vec<QuadBatch>,
+
+    clip: Option<ClipRect>,
+    transform: Transform,
+    split_batch: bool,
+}
+
+impl ShadowBrush {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        Self {
+            pipeline: ShadowPipeline::new(
+                device,
+                format,
+                sample_count,
+                globals_bind_group_layout,
+            ),
+            queue: Vec::new(),
+            batches: Vec::new(),
+            clip: None,
+            transform: Transform::IDENTITY,
+            split_batch: false,
+        }
+    }
+
+    /// Creates the pipeline again on a new device, e.g. after the old one was lost.
+    /// Everything queued so far is kept.
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        self.pipeline = ShadowPipeline::new(
+            device,
+            format,
+            sample_count,
+            globals_bind_group_layout,
+        );
+    }
+
+    /// Sets the clip rect for all shadows queued from now on.
+    pub fn set_clip(&mut self, clip: Option<ClipRect>) {
+        self.clip = clip;
+    }
+
+    /// Sets the transform for all shadows queued from now on, applied after their own transform.
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    /// Makes the next queued shadow start a new batch, e.g. when something else was drawn in between.
+    pub fn split_batch(&mut self) {
+        self.split_batch = true;
+    }
+
+    pub fn queue_shadow(&mut self, mut shadow: Shadow) {
+        shadow.transform = shadow.transform.then(&self.transform);
+        let index = self.queue.len();
+        self.queue.push(shadow);
+
+        // Extend the last batch if it uses the same clip rect
+        let split_batch = std::mem::take(&mut self.split_batch);
+        match self.batches.last_mut() {
+            Some(batch) if !split_batch && batch.clip == self.clip && batch.range.end == index => {
+                batch.range.end = index + 1;
+            }
+            _ => self.batches.push(QuadBatch {
+                clip: self.clip,
+                range: index..index + 1,
+            }),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_queued(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        let result = self.draw_batches(
+            device,
+            staging_belt,
+            encoder,
+            view,
+            width,
+            height,
+            0..self.batches.len(),
+            globals_bind_group,
+        );
+        self.clear();
+        result
+    }
+
+    /// Draws only the given range of batches, leaving the queue untouched.
+    /// Used to interleave shadows with other brushes in submission order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_batches(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        self.pipeline.draw(
+            device,
+            staging_belt,
+            encoder,
+            view,
+            (width, height),
+            &self.queue,
+            &self.batches[batches],
+            globals_bind_group,
+        );
+        Ok(())
+    }
+
+    /// Uploads everything queued so far, so batches can be drawn with [`ShadowBrush::draw_prepared`]
+    /// inside of a render pass shared with other brushes.
+    /// Returns `false` if the queue is too big for that, use [`ShadowBrush::draw_batches`] then.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+    ) -> bool {
+        self.pipeline
+            .prepare(device, staging_belt, encoder, &self.queue)
+    }
+
+    /// Records the given range of batches into `rpass`, after [`ShadowBrush::prepare`] returned `true`.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.pipeline.draw_prepared(
+            rpass,
+            (width, height),
+            &self.batches[batches],
+            globals_bind_group,
+        );
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.batches.clear();
+        self.split_batch = false;
+    }
+}
@@ -0,0 +1,508 @@
+use std::ops::Range;
+
+use bytemuck::{Pod, Zeroable};
+use wgpu::{CommandEncoder, Device, TextureFormat, TextureView};
+
+use crate::{ClipRect, Color, RenderError, Transform};
+
+use super::{instances::InstanceBuffer, QuadBatch};
+
+const INITIAL_SHAPE_CAPACITY: usize = 1_000;
+
+/// Circle, ellipse, pie slice, arc or ring, drawn from its signed distance like a rounded [`crate::Quad`].
+///
+/// The part of the ellipse between the angles is filled, or only a band of `thickness` along its
+/// edge. A whole ellipse with a band is a ring, a partial one an arc, and a partial one without a
+/// band a pie slice.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Shape {
+    pub center: [f32; 2],
+    pub radii: [f32; 2],
+    // Degrees, clockwise from the positive x axis like `PathBuilder::arc`.
+    // A sweep of 360 degrees or more is the whole ellipse.
+    pub start_angle: f32,
+    pub sweep_angle: f32,
+    // Width of the band along the edge, measured inwards. 0 fills the shape.
+    pub thickness: f32,
+    // 1 to round the ends of arcs, 0 to cut them off along the radius
+    pub round_caps: u32,
+    pub color: [f32; 4],
+    // Outline along the inside of the edge, like the border of a quad
+    pub stroke_color: [f32; 4],
+    pub stroke_width: f32,
+    // Applied to the shape before the transform of the brush, like `Quad::transform`
+    pub transform: Transform,
+}
+
+impl Default for Shape {
+    fn default() -> Self {
+        Self {
+            sweep_angle: 360.0,
+            transform: Transform::IDENTITY,
+            ..Self::zeroed()
+        }
+    }
+}
+
+impl Shape {
+    const ATTRIBS: [wgpu::VertexAttribute; 12] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32,
+        3 => Float32,
+        4 => Float32,
+        5 => Uint32,
+        6 => Float32x4,
+        7 => Float32x4,
+        8 => Float32,
+        9 => Float32x2,
+        10 => Float32x2,
+        11 => Float32x2
+    ];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    pub fn circle(center: [f32; 2], radius: f32, color: Color) -> Self {
+        Self::ellipse(center, [radius, radius], color)
+    }
+
+    pub fn ellipse(center: [f32; 2], radii: [f32; 2], color: Color) -> Self {
+        Self {
+            center,
+            radii,
+            color: color.into(),
+            ..Default::default()
+        }
+    }
+
+    /// Slice of a circle between the two angles, in degrees.
+    pub fn pie(
+        center: [f32; 2],
+        radius: f32,
+        start_angle: f32,
+        sweep_angle: f32,
+        color: Color,
+    ) -> Self {
+        Self {
+            start_angle,
+            sweep_angle,
+            ..Self::circle(center, radius, color)
+        }
+    }
+
+    /// Band of `thickness` along the edge of a circle, between the two angles in degrees.
+    pub fn arc(
+        center: [f32; 2],
+        radius: f32,
+        start_angle: f32,
+        sweep_angle: f32,
+        thickness: f32,
+        color: Color,
+    ) -> Self {
+        Self {
+            start_angle,
+            sweep_angle,
+            thickness,
+            ..Self::circle(center, radius, color)
+        }
+    }
+
+    pub fn ring(center: [f32; 2], radius: f32, thickness: f32, color: Color) -> Self {
+        Self {
+            thickness,
+            ..Self::circle(center, radius, color)
+        }
+    }
+
+    /// Makes the shape elliptic, e.g. for elliptic arcs.
+    pub fn with_radii(mut self, radii: [f32; 2]) -> Self {
+        self.radii = radii;
+        self
+    }
+
+    pub fn with_stroke(mut self, width: f32, color: Color) -> Self {
+        self.stroke_width = width;
+        self.stroke_color = color.into();
+        self
+    }
+
+    pub fn with_round_caps(mut self) -> Self {
+        self.round_caps = 1;
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct ShapePipeline {
+    instances: InstanceBuffer<Shape>,
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl ShapePipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        // Create shader
+        let shader = device.create_shader_module(include_shader!("shape.wgsl"));
+
+        // Create buffers
+        let instance_buffer =
+            InstanceBuffer::new(device, "Shape instance buffer", INITIAL_SHAPE_CAPACITY);
+
+        // Create pipeline
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Shape Pipeline Layout"),
+                bind_group_layouts: &[globals_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Shape Render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Shape::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self {
+            instances: instance_buffer,
+            pipeline: render_pipeline,
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        target_size: (u32, u32),
+        instances: &[Shape],
+        batches: &[QuadBatch],
+        globals_bind_group: &wgpu::BindGroup,
+    ) {
+        // Only the shapes covered by the batches have to be uploaded
+        let (span_start, span_end) = match (batches.first(), batches.last()) {
+            (Some(first), Some(last)) => (first.range.start, last.range.end),
+            _ => return,
+        };
+        if span_start >= span_end {
+            return;
+        }
+        self.instances.reserve(device, span_end - span_start);
+        let capacity = self.instances.capacity();
+
+        // Anything that still doesn't fit is drawn in chunks
+        for (chunk_index, chunk) in instances[span_start..span_end].chunks(capacity).enumerate() {
+            let chunk_start = span_start + chunk_index * capacity;
+            let chunk_end = chunk_start + chunk.len();
+
+            // Set buffer
+            self.instances.write(device, staging_belt, encoder, chunk);
+
+            // Render pass
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shape render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            self.record(
+                &mut rpass,
+                target_size,
+                batches,
+                chunk_start..chunk_end,
+                globals_bind_group,
+            );
+        }
+    }
+
+    /// Uploads all `instances` at once, so they can be drawn with [`ShapePipeline::draw_prepared`]
+    /// inside of a render pass shared with other pipelines.
+    /// Returns `false` if they don't fit into a single buffer, use [`ShapePipeline::draw`] then.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        instances: &[Shape],
+    ) -> bool {
+        self.instances.reserve(device, instances.len());
+        if instances.len() > self.instances.capacity() {
+            return false;
+        }
+        self.instances
+            .write(device, staging_belt, encoder, instances);
+        true
+    }
+
+    /// Records the batches into `rpass`, after [`ShapePipeline::prepare`] uploaded their instances.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        batches: &[QuadBatch],
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.record(
+            rpass,
+            target_size,
+            batches,
+            0..self.instances.capacity(),
+            globals_bind_group,
+        );
+    }
+
+    // Draws the part of the batches within `uploaded`, the instances at the start of the buffer
+    fn record<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        batches: &[QuadBatch],
+        uploaded: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.instances.slice());
+
+        for batch in batches {
+            let start = batch.range.start.max(uploaded.start);
+            let end = batch.range.end.min(uploaded.end);
+            if start >= end {
+                continue;
+            }
+
+            // Restrict drawing to the clip rect of the batch
+            let [x, y, width, height] = match batch.clip {
+                Some(clip) => match clip.to_scissor(target_size.0, target_size.1) {
+                    Some(scissor) => scissor,
+                    None => continue,
+                },
+                None => [0, 0, target_size.0, target_size.1],
+            };
+            rpass.set_scissor_rect(x, y, width, height);
+
+            rpass.draw(
+                0..4,
+                (start - uploaded.start) as u32..(end - uploaded.start) as u32,
+            );
+        }
+    }
+}
+
+// ====< BRUSH >====
+pub struct ShapeBrush {
+    pub pipeline: ShapePipeline,
+    pub queue: Vec<Shape>,
+    pub batches: Vec<QuadBatch>,
+
+    clip: Option<ClipRect>,
+    transform: Transform,
+    split_batch: bool,
+}
+
+impl ShapeBrush {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Self {
+        Self {
+            pipeline: ShapePipeline::new(device, format, sample_count, globals_bind_group_layout),
+            queue: Vec::new(),
+            batches: Vec::new(),
+            clip: None,
+            transform: Transform::IDENTITY,
+            split_batch: false,
+        }
+    }
+
+    /// Creates the pipeline again on a new device, e.g. after the old one was lost.
+    /// Everything queued so far is kept.
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        self.pipeline = ShapePipeline::new(device, format, sample_count, globals_bind_group_layout);
+    }
+
+    /// Sets the clip rect for all shapes queued from now on.
+    pub fn set_clip(&mut self, clip: Option<ClipRect>) {
+        self.clip = clip;
+    }
+
+    /// Sets the transform for all shapes queued from now on, applied after their own transform.
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    /// Makes the next queued shape start a new batch, e.g. when something else was drawn in between.
+    pub fn split_batch(&mut self) {
+        self.split_batch = true;
+    }
+
+    pub fn queue_shape(&mut self, mut shape: Shape) {
+        shape.transform = shape.transform.then(&self.transform);
+        let index = self.queue.len();
+        self.queue.push(shape);
+
+        // Extend the last batch if it uses the same clip rect
+        let split_batch = std::mem::take(&mut self.split_batch);
+        match self.batches.last_mut() {
+            Some(batch) if !split_batch && batch.clip == self.clip && batch.range.end == index => {
+                batch.range.end = index + 1;
+            }
+            _ => self.batches.push(QuadBatch {
+                clip: self.clip,
+                range: index..index + 1,
+            }),
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_queued(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        let result = self.draw_batches(
+            device,
+            staging_belt,
+            encoder,
+            view,
+            width,
+            height,
+            0..self.batches.len(),
+            globals_bind_group,
+        );
+        self.clear();
+        result
+    }
+
+    /// Draws only the given range of batches, leaving the queue untouched.
+    /// Used to interleave shapes with other brushes in submission order.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_batches(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        self.pipeline.draw(
+            device,
+            staging_belt,
+            encoder,
+            view,
+            (width, height),
+            &self.queue,
+            &self.batches[batches],
+            globals_bind_group,
+        );
+        Ok(())
+    }
+
+    /// Uploads everything queued so far, so batches can be drawn with [`ShapeBrush::draw_prepared`]
+    /// inside of a render pass shared with other brushes.
+    /// Returns `false` if the queue is too big for that, use [`ShapeBrush::draw_batches`] then.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+    ) -> bool {
+        self.pipeline
+            .prepare(device, staging_belt, encoder, &self.queue)
+    }
+
+    /// Records the given range of batches into `rpass`, after [`ShapeBrush::prepare`] returned `true`.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.pipeline.draw_prepared(
+            rpass,
+            (width, height),
+            &self.batches[batches],
+            globals_bind_group,
+        );
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.batches.clear();
+        self.split_batch = false;
+    }
+}
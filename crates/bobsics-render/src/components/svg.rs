@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use resvg::{tiny_skia, usvg};
+
+use crate::{Color, ImageId, RenderError, TextureAtlas, Transform};
+
+// Largest width and height an SVG is rasterized at, bigger SVGs are scaled up from that
+const MAX_RASTER_SIZE: u32 = 4096;
+// Once more rasters are cached, those that weren't drawn this frame are evicted
+const MAX_RASTERS: usize = 256;
+
+/// Handle to an SVG stored in an [`SvgCache`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SvgId(usize);
+
+/// SVG drawn into a destination rect.
+/// It is rasterized at the size the rect covers on screen, so it stays sharp at any scale.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SvgQuad {
+    pub svg: SvgId,
+    pub top_left: [f32; 2],
+    pub bottom_right: [f32; 2],
+    // Replaces `currentColor` in the SVG, which is black by default
+    pub color: Option<Color>,
+    // Applied to the SVG before the transform of the brush, like `Quad::transform`
+    pub transform: Transform,
+}
+
+impl SvgQuad {
+    pub fn new(svg: SvgId, top_left: [f32; 2], bottom_right: [f32; 2]) -> Self {
+        Self {
+            svg,
+            top_left,
+            bottom_right,
+            color: None,
+            transform: Transform::IDENTITY,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn with_transform(mut self, transform: Transform) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Size of the destination rect in screen pixels once `transform` is applied after
+    /// the transform of the quad, rounded up to whole pixels.
+    pub fn pixel_size(&self, transform: Transform) -> (u32, u32) {
+        let transform = self.transform.then(&transform);
+        let width = self.bottom_right[0] - self.top_left[0];
+        let height = self.bottom_right[1] - self.top_left[1];
+        let length = |v: [f32; 2]| (v[0] * v[0] + v[1] * v[1]).sqrt().ceil() as u32;
+        (
+            length(transform.transform_vector([width, 0.0])),
+            length(transform.transform_vector([0.0, height])),
+        )
+    }
+}
+
+// One rasterized size and color of an SVG, with the color as sRGB RGBA8
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+struct RasterKey {
+    svg: SvgId,
+    width: u32,
+    height: u32,
+    color: Option<[u8; 4]>,
+}
+
+struct Raster {
+    image: ImageId,
+    // Frame the raster was last drawn in
+    last_used: u64,
+}
+
+struct SvgEntry {
+    data: Vec<u8>,
+    tree: usvg::Tree,
+}
+
+/// Keeps loaded SVGs and rasterizes them into a [`TextureAtlas`] on demand.
+/// Rasterized sizes and colors are cached. Once there are more than 256 of them, the least
+/// recently used ones that weren't drawn this frame are evicted, and their space in the atlas
+/// is reused for new rasters. Animating the size of an SVG rasterizes it again every frame,
+/// but doesn't grow the atlas.
+pub struct SvgCache {
+    entries: Vec<SvgEntry>,
+    rasters: HashMap<RasterKey, Raster>,
+    // Images of evicted rasters, whose space in the atlas can be reused
+    free: Vec<ImageId>,
+    frame: u64,
+}
+
+impl SvgCache {
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+            rasters: HashMap::new(),
+            free: Vec::new(),
+            frame: 0,
+        }
+    }
+
+    /// Parses an SVG document, e.g. read from a file or embedded with `include_bytes!`.
+    /// Gzip-compressed SVGZ data is accepted too.
+    pub fn load(&mut self, data: &[u8]) -> Result<SvgId, RenderError> {
+        let tree = usvg::Tree::from_data(data, &usvg::Options::default())?;
+        let id = SvgId(self.entries.len());
+        self.entries.push(SvgEntry {
+            data: data.to_vec(),
+            tree,
+        });
+        Ok(id)
+    }
+
+    /// Size the SVG declares for itself, in pixels.
+    pub fn size(&self, svg: SvgId) -> (f32, f32) {
+        let size = self.entries[svg.0].tree.size();
+        (size.width(), size.height())
+    }
+
+    /// Returns the SVG rasterized at `width`x`height` pixels, rasterizing it into `atlas`
+    /// if this size and color isn't cached. Sizes are clamped to at most 4096 pixels per side.
+    /// The image can be reused for another raster once it is evicted, so only draw it this frame.
+    pub fn image(
+        &mut self,
+        atlas: &mut TextureAtlas,
+        svg: SvgId,
+        width: u32,
+        height: u32,
+        color: Option<Color>,
+    ) -> Result<ImageId, RenderError> {
+        let key = RasterKey {
+            svg,
+            width: width.clamp(1, MAX_RASTER_SIZE),
+            height: height.clamp(1, MAX_RASTER_SIZE),
+            color: color.map(|color| color.to_srgba8()),
+        };
+        if let Some(raster) = self.rasters.get_mut(&key) {
+            raster.last_used = self.frame;
+            return Ok(raster.image);
+        }
+
+        let pixels = self.entries[svg.0].rasterize(key.width, key.height, key.color)?;
+        if self.rasters.len() >= MAX_RASTERS {
+            self.evict();
+        }
+
+        // Reuse the smallest space of an evicted raster that is big enough
+        let free = self
+            .free
+            .iter()
+            .enumerate()
+            .map(|(index, image)| (index, atlas.image_room(*image)))
+            .filter(|(_, (width, height))| *width >= key.width && *height >= key.height)
+            .min_by_key(|(_, (width, height))| *width as u64 * *height as u64);
+        let image = match free {
+            Some((index, _)) => {
+                let image = self.free.swap_remove(index);
+                atlas.replace_image(image, key.width, key.height, &pixels)?;
+                image
+            }
+            None => atlas.add_image_with_room(
+                key.width,
+                key.height,
+                &pixels,
+                (room_size(key.width), room_size(key.height)),
+            )?,
+        };
+        self.rasters.insert(
+            key,
+            Raster {
+                image,
+                last_used: self.frame,
+            },
+        );
+        Ok(image)
+    }
+
+    /// Ends the current frame, rasters that aren't drawn in the next one can be evicted.
+    pub fn end_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    // Evicts the least recently used raster that wasn't drawn this frame, if there is one
+    fn evict(&mut self) {
+        let oldest = self
+            .rasters
+            .iter()
+            .filter(|(_, raster)| raster.last_used < self.frame)
+            .min_by_key(|(_, raster)| raster.last_used)
+            .map(|(key, _)| *key);
+        if let Some(raster) = oldest.and_then(|key| self.rasters.remove(&key)) {
+            self.free.push(raster.image);
+        }
+    }
+}
+
+// Atlas space reserved for a raster, rounded up to one of 8 steps per power of two,
+// so it can be reused for rasters of a similar size once it is evicted
+fn room_size(size: u32) -> u32 {
+    let step = (size.next_power_of_two() / 8).max(1);
+    size.div_ceil(step) * step
+}
+
+impl Default for SvgCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SvgEntry {
+    // Straight alpha sRGB pixels, stretched to fill the whole size like an image
+    fn rasterize(
+        &self,
+        width: u32,
+        height: u32,
+        color: Option<[u8; 4]>,
+    ) -> Result<Vec<u8>, RenderError> {
+        // `currentColor` is resolved while parsing, so a colored SVG is parsed again
+        let colored = color.and_then(|[r, g, b, a]| {
+            let options = usvg::Options {
+                style_sheet: Some(format!(
+                    "svg {{ color: rgba({r}, {g}, {b}, {}) }}",
+                    a as f32 / 255.0
+                )),
+                ..usvg::Options::default()
+            };
+            usvg::Tree::from_data(&self.data, &options).ok()
+        });
+        let tree = colored.as_ref().unwrap_or(&self.tree);
+
+        let mut pixmap =
+            tiny_skia::Pixmap::new(width, height).ok_or(RenderError::TextureTooLarge {
+                size: width.max(height),
+                max: MAX_RASTER_SIZE,
+            })?;
+        let size = tree.size();
+        resvg::render(
+            tree,
+            tiny_skia::Transform::from_scale(
+                width as f32 / size.width(),
+                height as f32 / size.height(),
+            ),
+            &mut pixmap.as_mut(),
+        );
+
+        Ok(pixmap
+            .pixels()
+            .iter()
+            .flat_map(|pixel| {
+                let pixel = pixel.demultiply();
+                [pixel.red(), pixel.green(), pixel.blue(), pixel.alpha()]
+            })
+            .collect())
+    }
+}
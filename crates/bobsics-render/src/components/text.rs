@@ -0,0 +1,683 @@
+use std::ops::Range;
+
+use bytemuck::{Pod, Zeroable};
+use glyph_brush::{
+    ab_glyph::FontArc, BrushAction, BrushError, GlyphBrush, GlyphBrushBuilder, GlyphVertex,
+    Rectangle, Section,
+};
+use wgpu::{util::DeviceExt, CommandEncoder, Device, TextureFormat, TextureView};
+
+use crate::{ClipRect, RenderError, Transform};
+
+use super::instances::InstanceBuffer;
+
+const INITIAL_GLYPH_CAPACITY: usize = 10_000;
+const INITIAL_CACHE_SIZE: u32 = 256;
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+struct GlyphInstance {
+    top_left: [f32; 2],
+    bottom_right: [f32; 2],
+    uv_min: [f32; 2],
+    uv_max: [f32; 2],
+    color: [f32; 4],
+    // Transform of the batch the glyph belongs to
+    transform: Transform,
+}
+
+impl GlyphInstance {
+    const ATTRIBS: [wgpu::VertexAttribute; 8] = wgpu::vertex_attr_array![
+        0 => Float32x2,
+        1 => Float32x2,
+        2 => Float32x2,
+        3 => Float32x2,
+        4 => Float32x4,
+        5 => Float32x2,
+        6 => Float32x2,
+        7 => Float32x2
+    ];
+
+    fn desc<'a>() -> wgpu::VertexBufferLayout<'a> {
+        use std::mem;
+
+        wgpu::VertexBufferLayout {
+            array_stride: mem::size_of::<Self>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBS,
+        }
+    }
+
+    // Cuts the glyph off at the bounds of its section, like `wgpu_glyph` does.
+    // The batch of the glyph is passed along in `Extra::z`.
+    fn from_vertex(vertex: GlyphVertex) -> (usize, Self) {
+        let GlyphVertex {
+            mut tex_coords,
+            pixel_coords,
+            bounds,
+            extra,
+        } = vertex;
+        let mut rect = pixel_coords;
+
+        if rect.max.x > bounds.max.x {
+            let old_width = rect.width();
+            rect.max.x = bounds.max.x;
+            tex_coords.max.x = tex_coords.min.x + tex_coords.width() * rect.width() / old_width;
+        }
+        if rect.min.x < bounds.min.x {
+            let old_width = rect.width();
+            rect.min.x = bounds.min.x;
+            tex_coords.min.x = tex_coords.max.x - tex_coords.width() * rect.width() / old_width;
+        }
+        if rect.max.y > bounds.max.y {
+            let old_height = rect.height();
+            rect.max.y = bounds.max.y;
+            tex_coords.max.y = tex_coords.min.y + tex_coords.height() * rect.height() / old_height;
+        }
+        if rect.min.y < bounds.min.y {
+            let old_height = rect.height();
+            rect.min.y = bounds.min.y;
+            tex_coords.min.y = tex_coords.max.y - tex_coords.height() * rect.height() / old_height;
+        }
+
+        (
+            extra.z as usize,
+            Self {
+                top_left: [rect.min.x, rect.min.y],
+                bottom_right: [rect.max.x, rect.max.y],
+                uv_min: [tex_coords.min.x, tex_coords.min.y],
+                uv_max: [tex_coords.max.x, tex_coords.max.y],
+                color: extra.color,
+                transform: Transform::IDENTITY,
+            },
+        )
+    }
+}
+
+/// Text sections that share the same clip rect and transform.
+/// `range` are their glyphs, known once the brush is prepared.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextBatch {
+    pub clip: Option<ClipRect>,
+    pub transform: Transform,
+    pub range: Range<usize>,
+}
+
+// ====< PIPELINE >====
+#[derive(Debug)]
+pub struct TextPipeline {
+    instances: InstanceBuffer<GlyphInstance>,
+
+    cache: wgpu::Texture,
+    cache_bind_group_layout: wgpu::BindGroupLayout,
+    cache_bind_group: wgpu::BindGroup,
+    sampler: wgpu::Sampler,
+
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl TextPipeline {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+        cache_size: (u32, u32),
+    ) -> Self {
+        // Create shader
+        let shader = device.create_shader_module(include_shader!("text.wgsl"));
+
+        // Create buffers
+        let instance_buffer =
+            InstanceBuffer::new(device, "Text instance buffer", INITIAL_GLYPH_CAPACITY);
+
+        // Create glyph cache
+        let cache_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Text cache bind group layout"),
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Texture {
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            multisampled: false,
+                        },
+                        count: None,
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        binding: 1,
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                        count: None,
+                    },
+                ],
+            });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Text cache sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let (cache, cache_bind_group) =
+            Self::create_cache(device, &cache_bind_group_layout, &sampler, cache_size);
+
+        // Create pipeline
+        let render_pipeline_layout =
+            device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("Text Pipeline Layout"),
+                bind_group_layouts: &[globals_bind_group_layout, &cache_bind_group_layout],
+                push_constant_ranges: &[],
+            });
+
+        let render_pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Text Render pipeline"),
+            layout: Some(&render_pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[GlyphInstance::desc()],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                // Mirroring transforms flip the winding order
+                cull_mode: None,
+                polygon_mode: wgpu::PolygonMode::Fill,
+                unclipped_depth: false,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+
+        Self {
+            instances: instance_buffer,
+
+            cache,
+            cache_bind_group_layout,
+            cache_bind_group,
+            sampler,
+
+            pipeline: render_pipeline,
+        }
+    }
+
+    fn create_cache(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        sampler: &wgpu::Sampler,
+        (width, height): (u32, u32),
+    ) -> (wgpu::Texture, wgpu::BindGroup) {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Text glyph cache"),
+            size: wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::R8Unorm,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Text cache bind group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(sampler),
+                },
+            ],
+        });
+        (texture, bind_group)
+    }
+
+    // Replaces the glyph cache with an empty one of the given size
+    fn resize_cache(&mut self, device: &wgpu::Device, size: (u32, u32)) {
+        (self.cache, self.cache_bind_group) =
+            Self::create_cache(device, &self.cache_bind_group_layout, &self.sampler, size);
+    }
+
+    // Copies freshly rasterized glyphs into the cache
+    fn update_cache(
+        &self,
+        device: &wgpu::Device,
+        encoder: &mut wgpu::CommandEncoder,
+        rect: Rectangle<u32>,
+        pixels: &[u8],
+    ) {
+        let (width, height) = (rect.width(), rect.height());
+        if width == 0 || height == 0 {
+            return;
+        }
+
+        // Rows have to be padded to COPY_BYTES_PER_ROW_ALIGNMENT
+        let padded_bytes_per_row =
+            width.div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT) * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let mut padded = vec![0u8; (padded_bytes_per_row * height) as usize];
+        for (row, source) in padded
+            .chunks_mut(padded_bytes_per_row as usize)
+            .zip(pixels.chunks(width as usize))
+        {
+            row[..width as usize].copy_from_slice(source);
+        }
+
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Text cache upload buffer"),
+            contents: &padded,
+            usage: wgpu::BufferUsages::COPY_SRC,
+        });
+        encoder.copy_buffer_to_texture(
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::ImageCopyTexture {
+                texture: &self.cache,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: rect.min[0],
+                    y: rect.min[1],
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        target_size: (u32, u32),
+        instances: &[GlyphInstance],
+        batches: &[TextBatch],
+        globals_bind_group: &wgpu::BindGroup,
+    ) {
+        // Only the glyphs covered by the batches have to be uploaded
+        let (span_start, span_end) = match (batches.first(), batches.last()) {
+            (Some(first), Some(last)) => (first.range.start, last.range.end),
+            _ => return,
+        };
+        if span_start >= span_end {
+            return;
+        }
+        self.instances.reserve(device, span_end - span_start);
+        let capacity = self.instances.capacity();
+
+        // Anything that still doesn't fit is drawn in chunks
+        for (chunk_index, chunk) in instances[span_start..span_end].chunks(capacity).enumerate() {
+            let chunk_start = span_start + chunk_index * capacity;
+            let chunk_end = chunk_start + chunk.len();
+
+            // Set buffer
+            self.instances.write(device, staging_belt, encoder, chunk);
+
+            // Render pass
+            let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Text render pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            self.record(
+                &mut rpass,
+                target_size,
+                batches,
+                chunk_start..chunk_end,
+                globals_bind_group,
+            );
+        }
+    }
+
+    // Uploads all instances at once, returns `false` if they don't fit into a single buffer
+    fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut wgpu::CommandEncoder,
+        instances: &[GlyphInstance],
+    ) -> bool {
+        self.instances.reserve(device, instances.len());
+        if instances.len() > self.instances.capacity() {
+            return false;
+        }
+        self.instances
+            .write(device, staging_belt, encoder, instances);
+        true
+    }
+
+    // Draws the part of the batches within `uploaded`, the instances at the start of the buffer
+    fn record<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        target_size: (u32, u32),
+        batches: &[TextBatch],
+        uploaded: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        rpass.set_pipeline(&self.pipeline);
+        rpass.set_bind_group(0, globals_bind_group, &[]);
+        rpass.set_bind_group(1, &self.cache_bind_group, &[]);
+        rpass.set_vertex_buffer(0, self.instances.slice());
+
+        for batch in batches {
+            let start = batch.range.start.max(uploaded.start);
+            let end = batch.range.end.min(uploaded.end);
+            if start >= end {
+                continue;
+            }
+
+            // Restrict drawing to the clip rect of the batch
+            let [x, y, width, height] = match batch.clip {
+                Some(clip) => match clip.to_scissor(target_size.0, target_size.1) {
+                    Some(scissor) => scissor,
+                    None => continue,
+                },
+                None => [0, 0, target_size.0, target_size.1],
+            };
+            rpass.set_scissor_rect(x, y, width, height);
+
+            rpass.draw(
+                0..4,
+                (start - uploaded.start) as u32..(end - uploaded.start) as u32,
+            );
+        }
+    }
+}
+
+// ====< BRUSH >====
+/// Lays out text with `glyph_brush` and draws it like the other brushes, in batches
+/// with their own clip rect and transform.
+pub struct TextBrush {
+    pub pipeline: TextPipeline,
+    pub batches: Vec<TextBatch>,
+
+    glyph_brush: GlyphBrush<(usize, GlyphInstance)>,
+    // Laid out glyphs with their batch, kept for frames where no section changed
+    glyphs: Vec<(usize, GlyphInstance)>,
+    queue: Vec<GlyphInstance>,
+    clip: Option<ClipRect>,
+    transform: Transform,
+    split_batch: bool,
+}
+
+impl TextBrush {
+    /// Sections refer to `fonts` by their index, like `FontId(0)` for the first one.
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+        fonts: Vec<FontArc>,
+    ) -> Self {
+        let glyph_brush = GlyphBrushBuilder::using_fonts(fonts)
+            .initial_cache_size((INITIAL_CACHE_SIZE, INITIAL_CACHE_SIZE))
+            .build();
+        Self {
+            pipeline: TextPipeline::new(
+                device,
+                format,
+                sample_count,
+                globals_bind_group_layout,
+                glyph_brush.texture_dimensions(),
+            ),
+            batches: Vec::new(),
+            glyph_brush,
+            glyphs: Vec::new(),
+            queue: Vec::new(),
+            clip: None,
+            transform: Transform::IDENTITY,
+            split_batch: false,
+        }
+    }
+
+    /// Creates the pipeline and an empty glyph cache again on a new device, e.g. after the old one was lost.
+    /// Everything queued so far is kept.
+    pub fn recreate(
+        &mut self,
+        device: &Device,
+        format: TextureFormat,
+        sample_count: u32,
+        globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+        let size = self.glyph_brush.texture_dimensions();
+        self.pipeline = TextPipeline::new(
+            device,
+            format,
+            sample_count,
+            globals_bind_group_layout,
+            size,
+        );
+        // Makes the glyph brush upload every glyph again
+        self.glyph_brush.resize_texture(size.0, size.1);
+    }
+
+    /// Sets the clip rect for all sections queued from now on.
+    pub fn set_clip(&mut self, clip: Option<ClipRect>) {
+        self.clip = clip;
+    }
+
+    /// Sets the transform for all sections queued from now on.
+    /// Glyphs are rasterized untransformed, so scaled text can look blurry.
+    pub fn set_transform(&mut self, transform: Transform) {
+        self.transform = transform;
+    }
+
+    /// Makes the next queued section start a new batch, e.g. when something else was drawn in between.
+    pub fn split_batch(&mut self) {
+        self.split_batch = true;
+    }
+
+    pub fn queue_section(&mut self, section: &Section) {
+        // Start a new batch if the last one uses a different clip rect or transform
+        let split_batch = std::mem::take(&mut self.split_batch);
+        let continues_batch = matches!(
+            self.batches.last(),
+            Some(batch) if !split_batch && batch.clip == self.clip && batch.transform == self.transform
+        );
+        if !continues_batch {
+            self.batches.push(TextBatch {
+                clip: self.clip,
+                transform: self.transform,
+                range: 0..0,
+            });
+        }
+
+        // The glyph brush passes the batch of every glyph along in `z`
+        let batch = (self.batches.len() - 1) as f32;
+        let mut section = section.clone();
+        for text in &mut section.text {
+            text.extra.z = batch;
+        }
+        self.glyph_brush.queue(section);
+    }
+
+    /// Lays out everything queued so far, uploads new glyphs to the cache and sets the glyph
+    /// ranges of the batches. Needed before any of them can be drawn.
+    /// Returns `false` if there are too many glyphs to draw them with [`TextBrush::draw_prepared`]
+    /// inside of a shared render pass, use [`TextBrush::draw_batches`] then.
+    pub fn prepare(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+    ) -> Result<bool, RenderError> {
+        let max = device.limits().max_texture_dimension_2d;
+        let action = loop {
+            let pipeline = &self.pipeline;
+            let processed = self.glyph_brush.process_queued(
+                |rect, pixels| pipeline.update_cache(device, encoder, rect, pixels),
+                GlyphInstance::from_vertex,
+            );
+            match processed {
+                Ok(action) => break action,
+                // Grow the cache up to the device limit, everything is uploaded again afterwards
+                Err(BrushError::TextureTooSmall { suggested }) => {
+                    let (width, height) = self.glyph_brush.texture_dimensions();
+                    if width >= max && height >= max {
+                        return Err(RenderError::TextureTooLarge {
+                            size: suggested.0.max(suggested.1),
+                            max,
+                        });
+                    }
+                    let size = (suggested.0.min(max), suggested.1.min(max));
+                    self.pipeline.resize_cache(device, size);
+                    self.glyph_brush.resize_texture(size.0, size.1);
+                }
+            }
+        };
+
+        // Unchanged sections keep the glyphs of the last frame, only the batches are new
+        if let BrushAction::Draw(glyphs) = action {
+            self.glyphs = glyphs;
+        }
+        self.queue.clear();
+        for batch in &mut self.batches {
+            batch.range = 0..0;
+        }
+        // Glyphs come in the order their sections were queued
+        for &(batch, glyph) in &self.glyphs {
+            let Some(batch) = self.batches.get_mut(batch) else {
+                continue;
+            };
+            if batch.range.is_empty() {
+                batch.range = self.queue.len()..self.queue.len();
+            }
+            batch.range.end += 1;
+            self.queue.push(GlyphInstance {
+                transform: batch.transform,
+                ..glyph
+            });
+        }
+
+        Ok(self
+            .pipeline
+            .prepare(device, staging_belt, encoder, &self.queue))
+    }
+
+    /// Records the given range of batches into `rpass`, after [`TextBrush::prepare`] returned `true`.
+    pub fn draw_prepared<'a>(
+        &'a self,
+        rpass: &mut wgpu::RenderPass<'a>,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &'a wgpu::BindGroup,
+    ) {
+        self.pipeline.record(
+            rpass,
+            (width, height),
+            &self.batches[batches],
+            0..self.queue.len(),
+            globals_bind_group,
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_queued(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        globals_bind_group: &wgpu::BindGroup,
+    ) -> Result<(), RenderError> {
+        let result = self.prepare(device, staging_belt, encoder);
+        if result.is_ok() {
+            self.draw_batches(
+                device,
+                staging_belt,
+                encoder,
+                view,
+                width,
+                height,
+                0..self.batches.len(),
+                globals_bind_group,
+            );
+        }
+        self.clear();
+        result.map(|_| ())
+    }
+
+    /// Draws only the given range of batches in render passes of their own, leaving the queue untouched.
+    /// Used to interleave text with other brushes in submission order, after [`TextBrush::prepare`].
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw_batches(
+        &mut self,
+        device: &Device,
+        staging_belt: &mut wgpu::util::StagingBelt,
+        encoder: &mut CommandEncoder,
+        view: &TextureView,
+        width: u32,
+        height: u32,
+        batches: Range<usize>,
+        globals_bind_group: &wgpu::BindGroup,
+    ) {
+        self.pipeline.draw(
+            device,
+            staging_belt,
+            encoder,
+            view,
+            (width, height),
+            &self.queue,
+            &self.batches[batches],
+            globals_bind_group,
+        );
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.batches.clear();
+        self.split_batch = false;
+    }
+}
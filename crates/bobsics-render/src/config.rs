@@ -0,0 +1,66 @@
+use crate::Color;
+
+/// Settings for creating a [`crate::BobsicsRenderer`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RendererConfig {
+    pub clear_color: Color,
+    /// Falls back to `Fifo` (vsync) if the surface doesn't support it.
+    pub present_mode: wgpu::PresentMode,
+    /// wgpu only guarantees 1 and 4, anything above 1 uses 4x MSAA if the adapter supports it.
+    pub sample_count: u32,
+    pub power_preference: wgpu::PowerPreference,
+    /// Prefer an sRGB surface format, so colors are converted from linear when written.
+    pub srgb: bool,
+}
+
+impl Default for RendererConfig {
+    fn default() -> Self {
+        Self {
+            clear_color: Color::from_hex(0x23242a),
+            present_mode: wgpu::PresentMode::Fifo,
+            sample_count: 1,
+            power_preference: wgpu::PowerPreference::default(),
+            srgb: true,
+        }
+    }
+}
+
+impl RendererConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_clear_color(mut self, clear_color: Color) -> Self {
+        self.clear_color = clear_color;
+        self
+    }
+
+    pub fn with_present_mode(mut self, present_mode: wgpu::PresentMode) -> Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    /// Shorthand for `Fifo` (on) or `Immediate` (off) present mode.
+    pub fn with_vsync(self, vsync: bool) -> Self {
+        self.with_present_mode(if vsync {
+            wgpu::PresentMode::Fifo
+        } else {
+            wgpu::PresentMode::Immediate
+        })
+    }
+
+    pub fn with_sample_count(mut self, sample_count: u32) -> Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    pub fn with_power_preference(mut self, power_preference: wgpu::PowerPreference) -> Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    pub fn with_srgb(mut self, srgb: bool) -> Self {
+        self.srgb = srgb;
+        self
+    }
+}
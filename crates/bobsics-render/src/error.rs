@@ -0,0 +1,129 @@
+use std::fmt;
+
+/// Everything that can go wrong while opening a window, creating a [`crate::BobsicsRenderer`]
+/// or drawing a frame.
+#[derive(Debug)]
+pub enum RenderError {
+    /// The window couldn't be created, e.g. because there is no display to open it on.
+    Window(winit::error::OsError),
+    /// There is no graphics adapter, or none that can draw to the window.
+    NoAdapter,
+    /// The adapter can't present to the window surface in any format.
+    UnsupportedSurface,
+    RequestDevice(wgpu::RequestDeviceError),
+
+    // Surface errors, reported by `BobsicsRenderer::render`
+    SurfaceLost,
+    SurfaceOutdated,
+    SurfaceTimeout,
+    OutOfMemory,
+    /// The graphics device was lost while drawing a frame. It was recreated, but the frame was dropped.
+    DeviceLost,
+
+    /// More data was queued in a single frame than fits into a GPU buffer. Sizes are in bytes.
+    BufferOverflow {
+        buffer: &'static str,
+        size: u64,
+        max: u64,
+    },
+    /// An image is bigger than the largest texture the device supports. Sizes are in pixels.
+    TextureTooLarge { size: u32, max: u32 },
+    /// The pixels given for an image don't match its size. The size is in pixels, `len` in bytes.
+    ImageSize { width: u32, height: u32, len: usize },
+    Tessellation(lyon::tessellation::TessellationError),
+    /// Errors of other brushes, like the glyph brush used for text.
+    Brush(String),
+    /// A custom shader failed to compile or doesn't match its pipeline.
+    Shader(String),
+    /// Failed to encode or save a [`crate::Screenshot`].
+    Png(png::EncodingError),
+    /// Failed to parse an SVG document.
+    Svg(resvg::usvg::Error),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Window(error) => write!(f, "failed to create the window: {error}"),
+            Self::NoAdapter => write!(
+                f,
+                "no compatible graphics adapter found, make sure your graphics drivers are installed and up to date"
+            ),
+            Self::UnsupportedSurface => {
+                write!(f, "the graphics adapter can't draw to this window")
+            }
+            Self::RequestDevice(error) => {
+                write!(f, "failed to open the graphics device: {error}")
+            }
+            Self::SurfaceLost => write!(f, "the window surface was lost"),
+            Self::SurfaceOutdated => write!(f, "the window surface is outdated"),
+            Self::SurfaceTimeout => write!(f, "timed out waiting for the window surface"),
+            Self::OutOfMemory => write!(f, "the graphics device ran out of memory"),
+            Self::DeviceLost => write!(
+                f,
+                "the graphics device was lost and recreated, the frame was dropped"
+            ),
+            Self::BufferOverflow { buffer, size, max } => write!(
+                f,
+                "{buffer} needs {size} bytes, but the device supports at most {max}"
+            ),
+            Self::TextureTooLarge { size, max } => write!(
+                f,
+                "texture of {size}x{size} pixels is larger than the maximum of {max}x{max}"
+            ),
+            Self::ImageSize { width, height, len } => write!(
+                f,
+                "image of {width}x{height} pixels needs {} bytes of RGBA8 data, but got {len}",
+                *width as u64 * *height as u64 * 4
+            ),
+            Self::Tessellation(error) => write!(f, "failed to tessellate path: {error}"),
+            Self::Brush(error) => write!(f, "{error}"),
+            Self::Shader(error) => write!(f, "invalid custom shader: {error}"),
+            Self::Png(error) => write!(f, "failed to save screenshot: {error}"),
+            Self::Svg(error) => write!(f, "failed to parse SVG: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
+
+impl From<wgpu::SurfaceError> for RenderError {
+    fn from(error: wgpu::SurfaceError) -> Self {
+        match error {
+            wgpu::SurfaceError::Lost => Self::SurfaceLost,
+            wgpu::SurfaceError::Outdated => Self::SurfaceOutdated,
+            wgpu::SurfaceError::Timeout => Self::SurfaceTimeout,
+            wgpu::SurfaceError::OutOfMemory => Self::OutOfMemory,
+        }
+    }
+}
+
+impl From<winit::error::OsError> for RenderError {
+    fn from(error: winit::error::OsError) -> Self {
+        Self::Window(error)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for RenderError {
+    fn from(error: wgpu::RequestDeviceError) -> Self {
+        Self::RequestDevice(error)
+    }
+}
+
+impl From<lyon::tessellation::TessellationError> for RenderError {
+    fn from(error: lyon::tessellation::TessellationError) -> Self {
+        Self::Tessellation(error)
+    }
+}
+
+impl From<png::EncodingError> for RenderError {
+    fn from(error: png::EncodingError) -> Self {
+        Self::Png(error)
+    }
+}
+
+impl From<resvg::usvg::Error> for RenderError {
+    fn from(error: resvg::usvg::Error) -> Self {
+        Self::Svg(error)
+    }
+}
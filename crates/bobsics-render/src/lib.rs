@@ -1,7 +1,19 @@
+mod color;
 mod components;
+mod config;
+mod error;
 mod render;
+mod screenshot;
+mod software;
+mod transform;
 mod utils;
 
+pub use color::*;
 pub use components::*;
+pub use config::*;
+pub use error::*;
 pub use render::*;
+pub use screenshot::*;
+pub use software::*;
+pub use transform::*;
 pub use utils::*;
@@ -1,3 +1,8 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
 use bytemuck::{Pod, Zeroable};
 use wgpu::{
     util::{DeviceExt, StagingBelt},
@@ -5,7 +10,9 @@ use wgpu::{
 };
 use winit::{dpi::PhysicalSize, window::Window};
 
-use crate::utils;
+use crate::{
+    Color, CustomBrush, CustomInstance, CustomShader, RenderError, RendererConfig, Screenshot,
+};
 
 #[repr(C)]
 #[derive(Debug, Copy, Clone, Pod, Zeroable)]
@@ -13,13 +20,21 @@ struct GlobalsUniform {
     u_resolution: [f32; 2],
 }
 
+// Where the renderer draws its frames to
+enum RenderTarget {
+    Surface(Surface),
+    Offscreen(Texture),
+}
+
 #[allow(dead_code)]
 pub struct BobsicsRenderer {
     instance: Instance,
     adapter: Adapter,
-    surface: Surface,
+    target: RenderTarget,
     pub device: Device,
     queue: Queue,
+    // Set by the error handler of the device once it reports that the device was lost
+    device_lost: Arc<AtomicBool>,
     config: SurfaceConfiguration,
     staging_belt: StagingBelt,
 
@@ -30,60 +45,256 @@ pub struct BobsicsRenderer {
 
     has_to_update_globals: bool,
 
+    clear_color: Color,
+    power_preference: PowerPreference,
+    // Brushes draw into this when multisampling, it is resolved into the target afterwards
+    msaa_texture: Option<Texture>,
+    // Window surfaces can't be copied from, so frames that are captured or read back by brushes
+    // are drawn into this first. Created the first time it is needed.
+    capture_texture: Option<Texture>,
+
+    // Captures the next rendered frame, see `request_screenshot`
+    screenshot_requested: bool,
+    screenshot: Option<Screenshot>,
+    // Created the first time a window surface frame is captured
+    blit_pipeline: Option<BlitPipeline>,
+
     pub format: wgpu::TextureFormat,
+    pub sample_count: u32,
 }
 
 impl BobsicsRenderer {
-    pub async fn new(window: &Window) -> Self {
+    pub async fn new(window: &Window) -> Result<Self, RenderError> {
+        Self::new_with_config(window, RendererConfig::default()).await
+    }
+
+    pub async fn new_with_config(
+        window: &Window,
+        renderer_config: RendererConfig,
+    ) -> Result<Self, RenderError> {
         let instance = Instance::new(Backends::all());
 
         let surface = unsafe { instance.create_surface(&window) };
 
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-                force_fallback_adapter: false,
-            })
-            .await
-            .unwrap();
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
-                    label: None,
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        let adapter =
+            Self::request_adapter(&instance, renderer_config.power_preference, Some(&surface))
+                .await?;
 
         let size = window.inner_size();
 
+        // Pick the first format with the requested color space, or any if there is none
+        let formats = surface.get_supported_formats(&adapter);
+        let format = formats
+            .iter()
+            .copied()
+            .find(|format| format.describe().srgb == renderer_config.srgb)
+            .or_else(|| formats.first().copied())
+            .ok_or(RenderError::UnsupportedSurface)?;
+
+        let present_mode = if surface
+            .get_supported_present_modes(&adapter)
+            .contains(&renderer_config.present_mode)
+        {
+            renderer_config.present_mode
+        } else {
+            wgpu::PresentMode::Fifo
+        };
+
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface.get_supported_formats(&adapter)[0],
+            format,
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode,
             alpha_mode: wgpu::CompositeAlphaMode::Auto,
         };
 
+        let (device, queue) = Self::request_device(&adapter).await?;
         surface.configure(&device, &config);
 
-        let staging_belt = StagingBelt::new(10 * 1024);
+        Ok(Self::from_parts(
+            instance,
+            adapter,
+            RenderTarget::Surface(surface),
+            device,
+            queue,
+            config,
+            &renderer_config,
+        ))
+    }
 
-        // Create globals
-        let globals = GlobalsUniform {
-            u_resolution: [size.width as f32, size.height as f32],
+    /// Creates a renderer that draws into an offscreen texture instead of a window.
+    /// Falls back to a software adapter if there is no GPU available.
+    /// Rendered frames can be read back with [`BobsicsRenderer::read_frame`].
+    pub async fn new_headless(width: u32, height: u32) -> Result<Self, RenderError> {
+        Self::new_headless_with_config(width, height, RendererConfig::default()).await
+    }
+
+    /// Like [`BobsicsRenderer::new_headless`], the present mode of the config is ignored.
+    pub async fn new_headless_with_config(
+        width: u32,
+        height: u32,
+        renderer_config: RendererConfig,
+    ) -> Result<Self, RenderError> {
+        let instance = Instance::new(Backends::all());
+
+        let adapter =
+            Self::request_adapter(&instance, renderer_config.power_preference, None).await?;
+
+        // Offscreen frames are always RGBA so they can be read back as is
+        let format = if renderer_config.srgb {
+            wgpu::TextureFormat::Rgba8UnormSrgb
+        } else {
+            wgpu::TextureFormat::Rgba8Unorm
+        };
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Auto,
+        };
+
+        let (device, queue) = Self::request_device(&adapter).await?;
+        let texture = Self::create_offscreen_texture(&device, &config);
+
+        Ok(Self::from_parts(
+            instance,
+            adapter,
+            RenderTarget::Offscreen(texture),
+            device,
+            queue,
+            config,
+            &renderer_config,
+        ))
+    }
+
+    // Headless renderers fall back to a software adapter if there is no GPU available
+    async fn request_adapter(
+        instance: &Instance,
+        power_preference: PowerPreference,
+        surface: Option<&Surface>,
+    ) -> Result<Adapter, RenderError> {
+        let options = |force_fallback_adapter| wgpu::RequestAdapterOptions {
+            power_preference,
+            compatible_surface: surface,
+            force_fallback_adapter,
         };
+        if let Some(adapter) = instance.request_adapter(&options(false)).await {
+            return Ok(adapter);
+        }
+        if surface.is_none() {
+            if let Some(adapter) = instance.request_adapter(&options(true)).await {
+                return Ok(adapter);
+            }
+        }
+        Err(RenderError::NoAdapter)
+    }
+
+    async fn request_device(adapter: &Adapter) -> Result<(Device, Queue), RenderError> {
+        Ok(adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features: wgpu::Features::empty(),
+                    limits: wgpu::Limits::default(),
+                    label: None,
+                },
+                None,
+            )
+            .await?)
+    }
+
+    // wgpu only reports a lost device through the errors of whatever failed because of it.
+    // Those are flagged for `render` to recreate the device, instead of panicking like
+    // the default handler still does for all other errors.
+    fn watch_device_loss(device: &Device) -> Arc<AtomicBool> {
+        let lost = Arc::new(AtomicBool::new(false));
+        let flag = lost.clone();
+        device.on_uncaptured_error(move |error| {
+            // The device error only shows up in the message, it isn't one of the error sources
+            if error.to_string().contains("device is lost") {
+                flag.store(true, Ordering::Relaxed);
+            } else {
+                panic!("wgpu error: {error}");
+            }
+        });
+        lost
+    }
+
+    fn create_offscreen_texture(device: &Device, config: &SurfaceConfiguration) -> Texture {
+        device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Bobsics offscreen frame"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: config.usage,
+        })
+    }
+
+    fn create_capture_texture(device: &Device, config: &SurfaceConfiguration) -> Texture {
+        Self::create_offscreen_texture(
+            device,
+            &SurfaceConfiguration {
+                usage: wgpu::TextureUsages::RENDER_ATTACHMENT
+                    | wgpu::TextureUsages::COPY_SRC
+                    | wgpu::TextureUsages::TEXTURE_BINDING,
+                ..config.clone()
+            },
+        )
+    }
+
+    fn create_msaa_texture(
+        device: &Device,
+        config: &SurfaceConfiguration,
+        sample_count: u32,
+    ) -> Option<Texture> {
+        if sample_count <= 1 {
+            return None;
+        }
+        Some(device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Bobsics multisampled frame"),
+            size: wgpu::Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: config.format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+        }))
+    }
 
+    // wgpu only guarantees 4x MSAA, and only for some formats
+    fn supported_sample_count(adapter: &Adapter, format: TextureFormat, requested: u32) -> u32 {
+        if requested > 1
+            && adapter
+                .get_texture_format_features(format)
+                .flags
+                .contains(wgpu::TextureFormatFeatureFlags::MULTISAMPLE)
+        {
+            4
+        } else {
+            1
+        }
+    }
+
+    fn create_globals(
+        device: &Device,
+        globals: &GlobalsUniform,
+    ) -> (wgpu::Buffer, wgpu::BindGroupLayout, wgpu::BindGroup) {
         // Create globals uniform
         let globals_uniform = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Global uniform buffer"),
-            contents: bytemuck::cast_slice(&[globals]),
+            contents: bytemuck::bytes_of(globals),
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
@@ -111,14 +322,42 @@ impl BobsicsRenderer {
             }],
         });
 
-        let format = surface.get_supported_formats(&adapter)[0];
+        (globals_uniform, globals_bind_group_layout, globals_bind_group)
+    }
+
+    fn from_parts(
+        instance: Instance,
+        adapter: Adapter,
+        target: RenderTarget,
+        device: Device,
+        queue: Queue,
+        config: SurfaceConfiguration,
+        renderer_config: &RendererConfig,
+    ) -> Self {
+        let staging_belt = StagingBelt::new(10 * 1024);
+
+        let sample_count =
+            Self::supported_sample_count(&adapter, config.format, renderer_config.sample_count);
+        let msaa_texture = Self::create_msaa_texture(&device, &config, sample_count);
+
+        // Create globals
+        let globals = GlobalsUniform {
+            u_resolution: [config.width as f32, config.height as f32],
+        };
+
+        let (globals_uniform, globals_bind_group_layout, globals_bind_group) =
+            Self::create_globals(&device, &globals);
+
+        let format = config.format;
+        let device_lost = Self::watch_device_loss(&device);
 
         Self {
             instance,
             adapter,
-            surface,
+            target,
             device,
             queue,
+            device_lost,
             config,
             staging_belt,
 
@@ -129,10 +368,56 @@ impl BobsicsRenderer {
 
             has_to_update_globals: false,
 
+            clear_color: renderer_config.clear_color,
+            power_preference: renderer_config.power_preference,
+            msaa_texture,
+            capture_texture: None,
+
+            screenshot_requested: false,
+            screenshot: None,
+            blit_pipeline: None,
+
             format,
+            sample_count,
         }
     }
 
+    pub fn width(&self) -> u32 {
+        self.config.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.config.height
+    }
+
+    pub fn is_headless(&self) -> bool {
+        matches!(self.target, RenderTarget::Offscreen(_))
+    }
+
+    pub fn clear_color(&self) -> Color {
+        self.clear_color
+    }
+
+    pub fn set_clear_color(&mut self, clear_color: Color) {
+        self.clear_color = clear_color;
+    }
+
+    /// Compiles `shader` into a [`CustomBrush`] for the device, format and sample count of the renderer.
+    pub fn create_custom_brush<I: CustomInstance, U: Pod>(
+        &self,
+        shader: CustomShader,
+        uniforms: U,
+    ) -> Result<CustomBrush<I, U>, RenderError> {
+        CustomBrush::new(
+            &self.device,
+            self.format,
+            self.sample_count,
+            &self.globals_bind_group_layout,
+            shader,
+            uniforms,
+        )
+    }
+
     pub fn resize(&mut self, new_size: PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.config.width = new_size.width;
@@ -142,16 +427,143 @@ impl BobsicsRenderer {
             self.globals.u_resolution = [new_size.width as f32, new_size.height as f32];
             self.has_to_update_globals = true;
 
-            self.surface.configure(&self.device, &self.config);
+            match &mut self.target {
+                RenderTarget::Surface(surface) => surface.configure(&self.device, &self.config),
+                RenderTarget::Offscreen(texture) => {
+                    *texture = Self::create_offscreen_texture(&self.device, &self.config)
+                }
+            }
+            self.msaa_texture =
+                Self::create_msaa_texture(&self.device, &self.config, self.sample_count);
+            if self.capture_texture.is_some() {
+                self.capture_texture =
+                    Some(Self::create_capture_texture(&self.device, &self.config));
+            }
+        }
+    }
+
+    /// Whether the device reported that it was lost, e.g. after a driver reset.
+    /// [`BobsicsRenderer::render`] recreates it on its own.
+    pub fn is_device_lost(&self) -> bool {
+        self.device_lost.load(Ordering::Relaxed)
+    }
+
+    /// Creates a new adapter and device, e.g. after the old device was lost when switching GPUs
+    /// or resuming from suspend. The brush is asked to recreate its pipelines and caches.
+    /// Called by [`BobsicsRenderer::render`] when the device reports that it was lost,
+    /// or when the surface stays lost after reconfiguring it.
+    pub fn recreate_device(&mut self, brush: &mut dyn Brush) -> Result<(), RenderError> {
+        let surface = match &self.target {
+            RenderTarget::Surface(surface) => Some(surface),
+            RenderTarget::Offscreen(_) => None,
+        };
+        let adapter = pollster::block_on(Self::request_adapter(
+            &self.instance,
+            self.power_preference,
+            surface,
+        ))?;
+        let (device, queue) = pollster::block_on(Self::request_device(&adapter))?;
+
+        // The new adapter may not support the old format
+        if let Some(surface) = surface {
+            let formats = surface.get_supported_formats(&adapter);
+            if !formats.contains(&self.config.format) {
+                self.config.format = formats
+                    .iter()
+                    .copied()
+                    .find(|format| format.describe().srgb == self.format.describe().srgb)
+                    .or_else(|| formats.first().copied())
+                    .ok_or(RenderError::UnsupportedSurface)?;
+            }
+            surface.configure(&device, &self.config);
+        }
+        self.format = self.config.format;
+        self.sample_count = Self::supported_sample_count(&adapter, self.format, self.sample_count);
+
+        (
+            self.globals_uniform,
+            self.globals_bind_group_layout,
+            self.globals_bind_group,
+        ) = Self::create_globals(&device, &self.globals);
+        self.has_to_update_globals = false;
+        self.staging_belt = StagingBelt::new(10 * 1024);
+        if let RenderTarget::Offscreen(texture) = &mut self.target {
+            *texture = Self::create_offscreen_texture(&device, &self.config);
+        }
+        self.msaa_texture = Self::create_msaa_texture(&device, &self.config, self.sample_count);
+        if self.capture_texture.is_some() {
+            self.capture_texture = Some(Self::create_capture_texture(&device, &self.config));
+        }
+        self.blit_pipeline = None;
+
+        self.device_lost = Self::watch_device_loss(&device);
+        self.adapter = adapter;
+        self.device = device;
+        self.queue = queue;
+
+        brush.recreate(
+            &self.device,
+            self.format,
+            self.sample_count,
+            &self.globals_bind_group_layout,
+        );
+        Ok(())
+    }
+
+    // Gets the next texture of the window surface. A lost or outdated surface is reconfigured,
+    // and if it is still lost afterwards the device is recreated as well.
+    fn acquire_surface_texture(
+        &mut self,
+        brush: &mut dyn Brush,
+    ) -> Result<Option<SurfaceTexture>, RenderError> {
+        for attempt in 0..3 {
+            let RenderTarget::Surface(surface) = &self.target else {
+                return Ok(None);
+            };
+            match surface.get_current_texture() {
+                Ok(output) => return Ok(Some(output)),
+                Err(SurfaceError::Lost) if attempt == 1 => self.recreate_device(brush)?,
+                Err(SurfaceError::Lost | SurfaceError::Outdated) => {
+                    surface.configure(&self.device, &self.config)
+                }
+                Err(error) => return Err(error.into()),
+            }
         }
+        Err(RenderError::SurfaceLost)
     }
 
-    pub fn render(&mut self, brush: &mut dyn Brush) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
+    pub fn render(&mut self, brush: &mut dyn Brush) -> Result<(), RenderError> {
+        // Window surfaces and offscreen frames alike need a new device once it is lost
+        if self.is_device_lost() {
+            self.recreate_device(brush)?;
+        }
+
+        let output = self.acquire_surface_texture(brush)?;
+        let view = match (&output, &self.target) {
+            (Some(output), _) => output
+                .texture
+                .create_view(&wgpu::TextureViewDescriptor::default()),
+            (None, RenderTarget::Offscreen(texture)) => {
+                texture.create_view(&wgpu::TextureViewDescriptor::default())
+            }
+            // Surfaces always hand out a texture or an error
+            (None, RenderTarget::Surface(_)) => unreachable!(),
+        };
 
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+        // Window surfaces can't be copied from, so a captured frame, or one that brushes read
+        // back without MSAA, is drawn into a texture that can first, and then copied onto the surface.
+        // The texture is kept for the next frames, and put back once this one is drawn.
+        let capture = std::mem::take(&mut self.screenshot_requested);
+        let reads_frame = brush.reads_frame() && self.msaa_texture.is_none();
+        let capture_texture = ((capture || reads_frame) && output.is_some()).then(|| {
+            self.capture_texture
+                .take()
+                .unwrap_or_else(|| Self::create_capture_texture(&self.device, &self.config))
+        });
+        let capture_view = capture_texture
+            .as_ref()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let frame_view = capture_view.as_ref().unwrap_or(&view);
 
         let mut encoder = self
             .device
@@ -165,46 +577,199 @@ impl BobsicsRenderer {
             self.update_globals(&mut encoder);
         }
 
-        // Color render pass
-        {
-            let _render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                label: Some("Bobsics render pass"),
-                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
-                    resolve_target: None,
-                    ops: wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(utils::Color::from_hex(0x23242a).into()),
-                        store: true,
-                    },
-                })],
-                depth_stencil_attachment: None,
-            });
-        }
-
-        // Render method
-        brush
-            .draw_queued(
-                &self.device,
-                &mut self.staging_belt,
-                &mut encoder,
-                &view,
+        // Brushes draw into the multisampled texture, if there is one.
+        // The first pass clears it, and the last one resolves it into the actual target.
+        let msaa_view = self
+            .msaa_texture
+            .as_ref()
+            .map(|texture| texture.create_view(&wgpu::TextureViewDescriptor::default()));
+        let mut target = match &msaa_view {
+            Some(msaa_view) => FrameTarget::new(
+                msaa_view,
+                Some(frame_view),
                 self.config.width,
                 self.config.height,
-                &self.globals_bind_group,
-            )
-            .expect("Draw queued failed");
-        // Quads
+                self.clear_color,
+            ),
+            None => FrameTarget::new(
+                frame_view,
+                None,
+                self.config.width,
+                self.config.height,
+                self.clear_color,
+            ),
+        };
+        let frame_texture = match (&capture_texture, &self.target) {
+            (Some(texture), _) | (None, RenderTarget::Offscreen(texture)) => Some(texture),
+            (None, RenderTarget::Surface(_)) => None,
+        };
+        if let Some(texture) = frame_texture {
+            target = target.with_texture(texture);
+        }
 
-        // self.glyph_brush.draw_queued(&self.device, &mut self.staging_belt, &mut encoder, &view, self.config.width, self.config.height).expect("Draw queued failed");
+        let drawn = brush.draw_queued(
+            &self.device,
+            &mut self.staging_belt,
+            &mut encoder,
+            &mut target,
+            &self.globals_bind_group,
+        );
+        target.finish(&mut encoder);
 
-        // Execute
+        if let Some(capture_view) = &capture_view {
+            self.blit(&mut encoder, capture_view, &view);
+        }
+
+        // Submitting to a lost device panics, so the frame is dropped and the device
+        // recreated instead, if it was lost while recording
         self.staging_belt.finish();
-        self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        let commands = encoder.finish();
+        if self.is_device_lost() {
+            self.recreate_device(brush)?;
+            return Err(RenderError::DeviceLost);
+        }
+
+        // Execute
+        self.queue.submit(std::iter::once(commands));
+        if let Some(output) = output {
+            output.present();
+        }
 
         self.staging_belt.recall();
 
-        Ok(())
+        if capture {
+            self.screenshot = match &capture_texture {
+                Some(texture) => self.read_texture(texture),
+                None => self.read_frame(),
+            }
+            .map(|pixels| Screenshot::new(self.config.width, self.config.height, pixels));
+        }
+        if capture_texture.is_some() {
+            self.capture_texture = capture_texture;
+        }
+
+        // Whatever was drawn before the error is still presented
+        drawn
+    }
+
+    /// Captures the next frame drawn by [`BobsicsRenderer::render`], which can then be taken
+    /// with [`BobsicsRenderer::take_screenshot`]. Works for window surfaces and offscreen frames.
+    pub fn request_screenshot(&mut self) {
+        self.screenshot_requested = true;
+    }
+
+    /// Returns the frame captured after [`BobsicsRenderer::request_screenshot`], once it is rendered.
+    /// `None` if there is none yet, or if the surface format can't be read back as RGBA8.
+    pub fn take_screenshot(&mut self) -> Option<Screenshot> {
+        self.screenshot.take()
+    }
+
+    /// Copies the last rendered offscreen frame back to the CPU as tightly packed RGBA8 pixels.
+    /// Returns `None` when rendering to a window surface, use [`BobsicsRenderer::request_screenshot`] there.
+    pub fn read_frame(&self) -> Option<Vec<u8>> {
+        match &self.target {
+            RenderTarget::Offscreen(texture) => self.read_texture(texture),
+            RenderTarget::Surface(_) => None,
+        }
+    }
+
+    // Copies a texture the size of the frame back to the CPU as RGBA8.
+    // Only 8 bit RGBA and BGRA formats can be read back.
+    fn read_texture(&self, texture: &Texture) -> Option<Vec<u8>> {
+        let bgra = match self.config.format {
+            TextureFormat::Rgba8Unorm | TextureFormat::Rgba8UnormSrgb => false,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb => true,
+            _ => return None,
+        };
+
+        // Rows have to be padded to COPY_BYTES_PER_ROW_ALIGNMENT
+        let unpadded_bytes_per_row = self.config.width * 4;
+        let padded_bytes_per_row = unpadded_bytes_per_row.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT)
+            * COPY_BYTES_PER_ROW_ALIGNMENT;
+
+        let readback = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Bobsics readback buffer"),
+            size: (padded_bytes_per_row * self.config.height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("Bobsics readback encoder"),
+            });
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_bytes_per_row),
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: self.config.width,
+                height: self.config.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.queue.submit(std::iter::once(encoder.finish()));
+
+        // Wait for the copy to finish
+        let slice = readback.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| {});
+        self.device.poll(wgpu::Maintain::Wait);
+
+        let padded = slice.get_mapped_range();
+        let mut pixels = Vec::with_capacity((unpadded_bytes_per_row * self.config.height) as usize);
+        for row in padded.chunks(padded_bytes_per_row as usize) {
+            pixels.extend_from_slice(&row[..unpadded_bytes_per_row as usize]);
+        }
+        drop(padded);
+        readback.unmap();
+
+        if bgra {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        Some(pixels)
+    }
+
+    // Copies the captured frame onto the target
+    fn blit(&mut self, encoder: &mut CommandEncoder, source: &TextureView, target: &TextureView) {
+        if !matches!(&self.blit_pipeline, Some(blit) if blit.format == self.format) {
+            self.blit_pipeline = Some(BlitPipeline::new(&self.device, self.format));
+        }
+        let blit = self.blit_pipeline.as_ref().unwrap();
+
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Bobsics blit bind group"),
+            layout: &blit.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(source),
+            }],
+        });
+
+        let mut rpass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Bobsics blit pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        rpass.set_pipeline(&blit.pipeline);
+        rpass.set_bind_group(0, &bind_group, &[]);
+        rpass.draw(0..3, 0..1);
     }
 
     fn update_globals(&mut self, encoder: &mut wgpu::CommandEncoder) {
@@ -220,16 +785,265 @@ impl BobsicsRenderer {
     }
 }
 
+// Draws one texture onto another of the same size and format
+struct BlitPipeline {
+    pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    format: TextureFormat,
+}
+
+impl BlitPipeline {
+    fn new(device: &Device, format: TextureFormat) -> Self {
+        let shader =
+            device.create_shader_module(wgpu::include_wgsl!("components/shaders/blit.wgsl"));
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Bobsics blit bind group layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("Bobsics blit pipeline layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Bobsics blit pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        Self {
+            pipeline,
+            bind_group_layout,
+            format,
+        }
+    }
+}
+
+// ====< FRAME TARGET >====
+/// The frame brushes draw into, see [`Brush::draw_queued`].
+///
+/// Brushes record into as few render passes as possible: the first pass clears the frame,
+/// and with MSAA the last one resolves it, so no passes are needed just for that.
+pub struct FrameTarget<'a> {
+    pub width: u32,
+    pub height: u32,
+
+    view: &'a TextureView,
+    // Where `view` is resolved to with MSAA
+    resolve_target: Option<&'a TextureView>,
+    // The single sampled frame, if it can be copied from
+    texture: Option<&'a Texture>,
+    // Until the first pass cleared the frame
+    clear_color: Option<Color>,
+    resolved: bool,
+}
+
+impl<'a> FrameTarget<'a> {
+    pub fn new(
+        view: &'a TextureView,
+        resolve_target: Option<&'a TextureView>,
+        width: u32,
+        height: u32,
+        clear_color: Color,
+    ) -> Self {
+        Self {
+            width,
+            height,
+            view,
+            resolve_target,
+            texture: None,
+            clear_color: Some(clear_color),
+            resolved: false,
+        }
+    }
+
+    /// Sets the single sampled texture of the frame, so [`FrameTarget::copy_to`] works without MSAA.
+    /// It has to allow `COPY_SRC`.
+    pub fn with_texture(mut self, texture: &'a Texture) -> Self {
+        self.texture = Some(texture);
+        self
+    }
+
+    /// Begins a render pass drawing into the frame, clearing it if this is the first one.
+    /// Pass `resolve` for the last pass of the frame, so it resolves the multisampled frame.
+    pub fn begin_render_pass<'p>(
+        &mut self,
+        encoder: &'p mut CommandEncoder,
+        label: &str,
+        resolve: bool,
+    ) -> FramePass<'p>
+    where
+        'a: 'p,
+    {
+        let load = match self.clear_color.take() {
+            Some(clear_color) => wgpu::LoadOp::Clear(clear_color.into()),
+            None => wgpu::LoadOp::Load,
+        };
+        self.resolved = resolve || self.resolve_target.is_none();
+        let resolve_target = self.resolve_target.filter(|_| resolve);
+        let pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: self.view,
+                resolve_target,
+                ops: wgpu::Operations { load, store: true },
+            })],
+            depth_stencil_attachment: None,
+        });
+        FramePass::new(pass, resolve_target.map(|_| (self.width, self.height)))
+    }
+
+    /// The view to draw into for brushes that begin render passes of their own.
+    /// Clears the frame first if no pass did yet.
+    pub fn load_view(&mut self, encoder: &mut CommandEncoder) -> &'a TextureView {
+        if self.clear_color.is_some() {
+            self.begin_render_pass(encoder, "Bobsics clear pass", false);
+        }
+        self.resolved = self.resolve_target.is_none();
+        self.view
+    }
+
+    /// Copies everything drawn so far into `destination`, a single sampled texture of the same
+    /// size and format, e.g. to read it back in a shader. With MSAA the frame is resolved into it.
+    /// Returns `false` if the frame can't be copied from, see [`Brush::reads_frame`].
+    pub fn copy_to(&mut self, encoder: &mut CommandEncoder, destination: &Texture) -> bool {
+        if self.resolve_target.is_some() {
+            let load = match self.clear_color.take() {
+                Some(clear_color) => wgpu::LoadOp::Clear(clear_color.into()),
+                None => wgpu::LoadOp::Load,
+            };
+            let destination = destination.create_view(&wgpu::TextureViewDescriptor::default());
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Bobsics copy pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self.view,
+                    resolve_target: Some(&destination),
+                    ops: wgpu::Operations { load, store: true },
+                })],
+                depth_stencil_attachment: None,
+            });
+            return true;
+        }
+
+        let Some(texture) = self.texture else {
+            return false;
+        };
+        if self.clear_color.is_some() {
+            self.begin_render_pass(encoder, "Bobsics clear pass", false);
+        }
+        encoder.copy_texture_to_texture(
+            texture.as_image_copy(),
+            destination.as_image_copy(),
+            wgpu::Extent3d {
+                width: self.width,
+                height: self.height,
+                depth_or_array_layers: 1,
+            },
+        );
+        true
+    }
+
+    /// Clears and resolves the frame if the brushes didn't, called by the renderer after drawing.
+    pub fn finish(&mut self, encoder: &mut CommandEncoder) {
+        if self.clear_color.is_some() || !self.resolved {
+            self.begin_render_pass(encoder, "Bobsics resolve pass", true);
+        }
+    }
+}
+
+/// A render pass returned by [`FrameTarget::begin_render_pass`], used like a [`RenderPass`].
+///
+/// The GL backend only resolves multisampled frames inside of the scissor rect, so a pass that
+/// resolves sets it back to the whole frame before it ends, whatever clip was drawn last.
+pub struct FramePass<'p> {
+    pass: RenderPass<'p>,
+    // Size of the resolved texture, if the pass resolves
+    resolve_size: Option<(u32, u32)>,
+}
+
+impl<'p> FramePass<'p> {
+    /// Wraps `pass`, which resolves into a texture of `resolve_size` if there is one.
+    pub fn new(pass: RenderPass<'p>, resolve_size: Option<(u32, u32)>) -> Self {
+        Self { pass, resolve_size }
+    }
+}
+
+impl<'p> std::ops::Deref for FramePass<'p> {
+    type Target = RenderPass<'p>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.pass
+    }
+}
+
+impl std::ops::DerefMut for FramePass<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.pass
+    }
+}
+
+impl Drop for FramePass<'_> {
+    fn drop(&mut self) {
+        if let Some((width, height)) = self.resolve_size {
+            self.pass.set_scissor_rect(0, 0, width, height);
+        }
+    }
+}
+
 #[allow(clippy::too_many_arguments)]
 pub trait Brush {
+    /// Draws everything queued into `target`, preferably with as few render passes as possible.
     fn draw_queued(
         &mut self,
         device: &wgpu::Device,
         staging_belt: &mut StagingBelt,
         encoder: &mut wgpu::CommandEncoder,
-        target: &wgpu::TextureView,
-        width: u32,
-        height: u32,
+        target: &mut FrameTarget,
         global_bind_group: &wgpu::BindGroup,
-    ) -> Result<(), &str>;
+    ) -> Result<(), RenderError>;
+
+    /// Called after the renderer created a new device, see [`BobsicsRenderer::recreate_device`].
+    /// Everything the brush created with the old device has to be created again.
+    fn recreate(
+        &mut self,
+        _device: &wgpu::Device,
+        _format: wgpu::TextureFormat,
+        _sample_count: u32,
+        _globals_bind_group_layout: &wgpu::BindGroupLayout,
+    ) {
+    }
+
+    /// Whether drawing needs [`FrameTarget::copy_to`]. Without MSAA, the renderer then draws
+    /// window surfaces into a texture that can be copied from first.
+    fn reads_frame(&self) -> bool {
+        false
+    }
 }
@@ -0,0 +1,55 @@
+use std::{fs::File, io::BufWriter, path::Path};
+
+use crate::RenderError;
+
+/// A frame copied back from the GPU, see [`crate::BobsicsRenderer::request_screenshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    pub width: u32,
+    pub height: u32,
+    /// Tightly packed RGBA8 pixels, sRGB encoded unless the renderer was configured without sRGB.
+    pub pixels: Vec<u8>,
+}
+
+impl Screenshot {
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Self {
+        assert_eq!(
+            pixels.len(),
+            (width * height * 4) as usize,
+            "Screenshot data doesn't match its size"
+        );
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Color of the pixel at `(x, y)` as RGBA8.
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let index = ((y * self.width + x) * 4) as usize;
+        self.pixels[index..index + 4].try_into().unwrap()
+    }
+
+    /// Encodes the screenshot as a PNG file in memory.
+    pub fn to_png(&self) -> Result<Vec<u8>, RenderError> {
+        let mut png = Vec::new();
+        self.write_png(&mut png)?;
+        Ok(png)
+    }
+
+    pub fn save_png(&self, path: impl AsRef<Path>) -> Result<(), RenderError> {
+        let file = File::create(path).map_err(png::EncodingError::IoError)?;
+        self.write_png(BufWriter::new(file))
+    }
+
+    fn write_png(&self, writer: impl std::io::Write) -> Result<(), RenderError> {
+        let mut encoder = png::Encoder::new(writer, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        writer.finish()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,490 @@
+use glyph_brush::{
+    ab_glyph::{Font, FontArc},
+    FontId, GlyphCalculator, GlyphCalculatorBuilder, GlyphCruncher, Section,
+};
+
+use crate::{
+    components::GpuGradient, linear_to_srgb, ClipRect, Color, Gradient, Quad, RendererConfig,
+};
+
+/// Draws quads and text on the CPU, without a GPU adapter.
+///
+/// Quads match `quad.wgsl` (rounded corners, borders, gradients and transforms),
+/// sampled once per pixel like a renderer with a sample count of 1. Text is laid out
+/// like the glyph brush, but rasterized directly from the outlines, so glyph edges
+/// may differ slightly. Useful as a deterministic reference for the wgpu output.
+pub struct SoftwareRenderer {
+    width: u32,
+    height: u32,
+    clear_color: Color,
+    srgb: bool,
+
+    // Linear colors with straight alpha, blended like `wgpu::BlendState::ALPHA_BLENDING`
+    pixels: Vec<[f32; 4]>,
+    gradients: Vec<GpuGradient>,
+    clip: Option<ClipRect>,
+    glyph_calculator: Option<GlyphCalculator<FontArc>>,
+}
+
+impl SoftwareRenderer {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::with_config(width, height, RendererConfig::default())
+    }
+
+    /// Only the clear color and the color space of the config are used.
+    pub fn with_config(width: u32, height: u32, renderer_config: RendererConfig) -> Self {
+        let mut renderer = Self {
+            width,
+            height,
+            clear_color: renderer_config.clear_color,
+            srgb: renderer_config.srgb,
+            pixels: Vec::new(),
+            gradients: Vec::new(),
+            clip: None,
+            glyph_calculator: None,
+        };
+        renderer.clear();
+        renderer
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn clear_color(&self) -> Color {
+        self.clear_color
+    }
+
+    pub fn set_clear_color(&mut self, clear_color: Color) {
+        self.clear_color = clear_color;
+    }
+
+    /// Resizes and clears the frame.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.width = width;
+        self.height = height;
+        self.clear();
+    }
+
+    /// Adds a font for [`SoftwareRenderer::draw_text`], the first one added gets `FontId(0)`.
+    /// Use the fonts of the glyph brush, in the same order, to get the same layout.
+    pub fn add_font(&mut self, font: FontArc) -> FontId {
+        let mut fonts = self
+            .glyph_calculator
+            .take()
+            .map(|calculator| calculator.fonts().to_vec())
+            .unwrap_or_default();
+        fonts.push(font);
+        let id = FontId(fonts.len() - 1);
+        self.glyph_calculator = Some(GlyphCalculatorBuilder::using_fonts(fonts).build());
+        id
+    }
+
+    pub fn with_font(mut self, font: FontArc) -> Self {
+        self.add_font(font);
+        self
+    }
+
+    /// Starts a new frame, filled with the clear color and without any gradients.
+    pub fn clear(&mut self) {
+        let color = self.clear_color.to_linear_rgba();
+        self.pixels = vec![color; (self.width * self.height) as usize];
+        self.gradients.clear();
+    }
+
+    /// Restricts drawing to `clip` from now on, like the scissor rect of a batch.
+    pub fn set_clip(&mut self, clip: Option<ClipRect>) {
+        self.clip = clip;
+    }
+
+    /// Adds a gradient for this frame, like [`crate::QuadBrush::queue_gradient`].
+    pub fn queue_gradient(&mut self, gradient: &Gradient) -> u32 {
+        self.gradients.push(gradient.into());
+        self.gradients.len() as u32
+    }
+
+    pub fn draw_quads(&mut self, quads: &[Quad]) {
+        for quad in quads {
+            self.draw_quad(quad);
+        }
+    }
+
+    pub fn draw_quad(&mut self, quad: &Quad) {
+        let inverse = match quad.transform.inverse() {
+            Some(inverse) => inverse,
+            None => return,
+        };
+        let top_left = [
+            quad.top_left[0].min(quad.bottom_right[0]),
+            quad.top_left[1].min(quad.bottom_right[1]),
+        ];
+        let bottom_right = [
+            quad.top_left[0].max(quad.bottom_right[0]),
+            quad.top_left[1].max(quad.bottom_right[1]),
+        ];
+        let size = [bottom_right[0] - top_left[0], bottom_right[1] - top_left[1]];
+        let scale = quad.transform.scale_factor();
+
+        let (min, max) = quad.transform.transform_rect(top_left, bottom_right);
+        let [x0, y0, x1, y1] = match self.pixel_bounds(min, max) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        for y in y0..y1 {
+            for x in x0..x1 {
+                // Like the GPU, only pixels whose center is inside the quad are drawn
+                let p = inverse.transform_point([x as f32 + 0.5, y as f32 + 0.5]);
+                let local = [p[0] - top_left[0], p[1] - top_left[1]];
+                if local[0] < 0.0 || local[1] < 0.0 || local[0] >= size[0] || local[1] >= size[1] {
+                    continue;
+                }
+                let color = self.quad_color(quad, local, size, scale);
+                self.blend(x, y, color);
+            }
+        }
+    }
+
+    // Same as `fs_main` in quad.wgsl
+    fn quad_color(&self, quad: &Quad, local: [f32; 2], size: [f32; 2], scale: f32) -> [f32; 4] {
+        let mut color = quad.color;
+        if quad.fill_gradient > 0 {
+            color = self.sample_gradient(quad.fill_gradient, local, size);
+        }
+
+        // Corners can't be rounder than half of the rectangle
+        let radii = quad
+            .border_radius
+            .map(|r| r.min(size[0].min(size[1]) / 2.0));
+
+        // Distance to the rectangle, in screen pixels
+        let p = [local[0] - size[0] / 2.0, local[1] - size[1] / 2.0];
+        let dist = box_dist(p, [size[0] / 2.0, size[1] / 2.0], radii) * scale;
+        let alpha = 1.0 - smoothstep(-0.75, -0.1, dist);
+
+        let widths = quad.border_width;
+        if widths.iter().any(|&w| w > 0.0) {
+            // The inner edge of the border is the rectangle shrunk by the width of each side,
+            // with each corner shrunk by the widest of its two sides
+            let [top, right, bottom, left] = widths;
+            let inner_top_left = [left, top];
+            let inner_bottom_right = [
+                (size[0] - right).max(inner_top_left[0]),
+                (size[1] - bottom).max(inner_top_left[1]),
+            ];
+            let corner_widths = [
+                left.max(top),
+                top.max(right),
+                right.max(bottom),
+                bottom.max(left),
+            ];
+            let inner_radii: [f32; 4] =
+                std::array::from_fn(|i| (radii[i] - corner_widths[i]).max(0.0));
+            let inner_half = [
+                (inner_bottom_right[0] - inner_top_left[0]) / 2.0,
+                (inner_bottom_right[1] - inner_top_left[1]) / 2.0,
+            ];
+            let inner_dist = box_dist(
+                [
+                    local[0] - inner_top_left[0] - inner_half[0],
+                    local[1] - inner_top_left[1] - inner_half[1],
+                ],
+                inner_half,
+                inner_radii,
+            );
+
+            // Draw the border outside of the inner edge
+            if inner_dist > 0.0 {
+                let mut border = if quad.border_gradient > 0 {
+                    self.sample_gradient(quad.border_gradient, local, size)
+                } else {
+                    border_side_color(quad, local, size)
+                };
+
+                // The fill shows through the gaps of dashed and dotted borders
+                if quad.border_style != 0 {
+                    let coverage = border_pattern(quad, local, size, radii, scale);
+                    let premultiply = |c: [f32; 4]| [c[0] * c[3], c[1] * c[3], c[2] * c[3], c[3]];
+                    let (fill, stroke) = (premultiply(color), premultiply(border));
+                    let mixed: [f32; 4] =
+                        std::array::from_fn(|i| fill[i] + (stroke[i] - fill[i]) * coverage);
+                    let alpha = mixed[3].max(0.0001);
+                    border = [
+                        mixed[0] / alpha,
+                        mixed[1] / alpha,
+                        mixed[2] / alpha,
+                        mixed[3],
+                    ];
+                }
+                color = border;
+            }
+        }
+
+        [color[0], color[1], color[2], alpha * color[3]]
+    }
+
+    fn sample_gradient(&self, gradient: u32, p: [f32; 2], size: [f32; 2]) -> [f32; 4] {
+        match self.gradients.get(gradient as usize - 1) {
+            Some(gradient) => gradient.sample(p, size),
+            None => [0.0; 4],
+        }
+    }
+
+    /// Lays out and draws text like the glyph brush, with the fonts from [`SoftwareRenderer::add_font`].
+    /// Nothing is drawn if no font was added yet.
+    pub fn draw_text(&mut self, section: &Section) {
+        let calculator = match &self.glyph_calculator {
+            Some(calculator) => calculator,
+            None => return,
+        };
+
+        let mut coverage = Vec::new();
+        for glyph in calculator.cache_scope().glyphs(section) {
+            let font = match calculator.fonts().get(glyph.font_id.0) {
+                Some(font) => font,
+                None => continue,
+            };
+            let outline = match font.outline_glyph(glyph.glyph.clone()) {
+                Some(outline) => outline,
+                None => continue,
+            };
+            let color = section.text[glyph.section_index].extra.color;
+            let bounds = outline.px_bounds();
+            outline.draw(|x, y, c| {
+                let x = bounds.min.x as i32 + x as i32;
+                let y = bounds.min.y as i32 + y as i32;
+                coverage.push((x, y, [color[0], color[1], color[2], color[3] * c]));
+            });
+        }
+
+        let [x0, y0, x1, y1] = match self.pixel_bounds([0.0, 0.0], [f32::MAX, f32::MAX]) {
+            Some(bounds) => bounds,
+            None => return,
+        };
+        for (x, y, color) in coverage {
+            if x >= x0 as i32 && y >= y0 as i32 && x < x1 as i32 && y < y1 as i32 {
+                self.blend(x as u32, y as u32, color);
+            }
+        }
+    }
+
+    // Pixels covering `min..max`, limited to the frame and the clip rect
+    fn pixel_bounds(&self, min: [f32; 2], max: [f32; 2]) -> Option<[u32; 4]> {
+        let [cx, cy, cw, ch] = match self.clip {
+            Some(clip) => clip.to_scissor(self.width, self.height)?,
+            None => [0, 0, self.width, self.height],
+        };
+        let x0 = (min[0].floor().max(0.0) as u32).max(cx);
+        let y0 = (min[1].floor().max(0.0) as u32).max(cy);
+        let x1 = (max[0].ceil().max(0.0) as u32).min(cx + cw);
+        let y1 = (max[1].ceil().max(0.0) as u32).min(cy + ch);
+        (x0 < x1 && y0 < y1).then_some([x0, y0, x1, y1])
+    }
+
+    fn blend(&mut self, x: u32, y: u32, src: [f32; 4]) {
+        let dst = &mut self.pixels[(y * self.width + x) as usize];
+        let a = src[3].clamp(0.0, 1.0);
+        for c in 0..3 {
+            dst[c] = src[c] * a + dst[c] * (1.0 - a);
+        }
+        dst[3] = a + dst[3] * (1.0 - a);
+    }
+
+    /// Returns the frame as tightly packed RGBA8 pixels, like [`crate::BobsicsRenderer::read_frame`].
+    pub fn read_frame(&self) -> Vec<u8> {
+        let to_u8 = |x: f32| (x.clamp(0.0, 1.0) * 255.0).round() as u8;
+        self.pixels
+            .iter()
+            .flat_map(|&[r, g, b, a]| {
+                let [r, g, b] = if self.srgb {
+                    [r, g, b].map(linear_to_srgb)
+                } else {
+                    [r, g, b]
+                };
+                [to_u8(r), to_u8(g), to_u8(b), to_u8(a)]
+            })
+            .collect()
+    }
+}
+
+// Same as `box_dist` in common.wgsl
+fn box_dist(p: [f32; 2], size: [f32; 2], radii: [f32; 4]) -> f32 {
+    // Pick the radius of the corner closest to `p`
+    let r = match (p[0] > 0.0, p[1] > 0.0) {
+        (false, false) => radii[0],
+        (true, false) => radii[1],
+        (true, true) => radii[2],
+        (false, true) => radii[3],
+    };
+
+    let d = [p[0].abs() - size[0] + r, p[1].abs() - size[1] + r];
+    let outside = (d[0].max(0.0).powi(2) + d[1].max(0.0).powi(2)).sqrt();
+    outside + d[0].max(d[1]).min(0.0) - r
+}
+
+// Same as `border_side_color` in quad.wgsl
+fn border_side_color(quad: &Quad, p: [f32; 2], size: [f32; 2]) -> [f32; 4] {
+    let dists = [p[1], size[0] - p[0], size[1] - p[1], p[0]];
+    let side_dist: [f32; 4] = std::array::from_fn(|i| {
+        let width = quad.border_width[i];
+        if width > 0.0 {
+            dists[i] / width.max(0.0001)
+        } else {
+            1e9
+        }
+    });
+
+    let min_dist = side_dist.iter().copied().fold(f32::INFINITY, f32::min);
+    let side = side_dist.iter().position(|&d| d == min_dist).unwrap_or(3);
+    quad.border_color[side]
+}
+
+// Same as `perimeter_position` in quad.wgsl
+fn perimeter_position(
+    p: [f32; 2],
+    half_size: [f32; 2],
+    radii: [f32; 4],
+    widths: [f32; 4],
+) -> [f32; 3] {
+    use std::f32::consts::FRAC_PI_2;
+
+    let [h, v] = half_size;
+    let arcs = radii.map(|r| r * FRAC_PI_2);
+    let top = (2.0 * h - radii[0] - radii[1]).max(0.0);
+    let right = (2.0 * v - radii[1] - radii[2]).max(0.0);
+    let bottom = (2.0 * h - radii[2] - radii[3]).max(0.0);
+    let left = (2.0 * v - radii[3] - radii[0]).max(0.0);
+    let total = arcs.iter().sum::<f32>() + top + right + bottom + left;
+
+    // Where each straight side and the corner after it start
+    let top_start = arcs[0] / 2.0;
+    let right_start = top_start + top + arcs[1];
+    let bottom_start = right_start + right + arcs[2];
+    let left_start = bottom_start + bottom + arcs[3];
+
+    // Inside the square of a rounded corner, the position follows its arc
+    let top_left = [-h + radii[0], -v + radii[0]];
+    let top_right = [h - radii[1], -v + radii[1]];
+    let bottom_right = [h - radii[2], v - radii[2]];
+    let bottom_left = [-h + radii[3], v - radii[3]];
+    let angle = |y: f32, x: f32| y.atan2(x).clamp(0.0, FRAC_PI_2);
+    if p[0] < top_left[0] && p[1] < top_left[1] {
+        let d = [p[0] - top_left[0], p[1] - top_left[1]];
+        let position = angle(-d[1], -d[0]) * radii[0] - top_start;
+        return [position, total, widths[3].max(widths[0])];
+    }
+    if p[0] > top_right[0] && p[1] < top_right[1] {
+        let d = [p[0] - top_right[0], p[1] - top_right[1]];
+        let position = top_start + top + angle(d[0], -d[1]) * radii[1];
+        return [position, total, widths[0].max(widths[1])];
+    }
+    if p[0] > bottom_right[0] && p[1] > bottom_right[1] {
+        let d = [p[0] - bottom_right[0], p[1] - bottom_right[1]];
+        let position = right_start + right + angle(d[1], d[0]) * radii[2];
+        return [position, total, widths[1].max(widths[2])];
+    }
+    if p[0] < bottom_left[0] && p[1] > bottom_left[1] {
+        let d = [p[0] - bottom_left[0], p[1] - bottom_left[1]];
+        let position = bottom_start + bottom + angle(-d[0], d[1]) * radii[3];
+        return [position, total, widths[2].max(widths[3])];
+    }
+
+    // Elsewhere it follows the closest straight side, split diagonally at square corners
+    let dists = [p[1] + v, h - p[0], v - p[1], p[0] + h];
+    let min_dist = dists.iter().copied().fold(f32::INFINITY, f32::min);
+    if dists[0] == min_dist {
+        [
+            top_start + (p[0] - top_left[0]).clamp(0.0, top),
+            total,
+            widths[0],
+        ]
+    } else if dists[1] == min_dist {
+        [
+            right_start + (p[1] - top_right[1]).clamp(0.0, right),
+            total,
+            widths[1],
+        ]
+    } else if dists[2] == min_dist {
+        [
+            bottom_start + (bottom_right[0] - p[0]).clamp(0.0, bottom),
+            total,
+            widths[2],
+        ]
+    } else {
+        [
+            left_start + (bottom_left[1] - p[1]).clamp(0.0, left),
+            total,
+            widths[3],
+        ]
+    }
+}
+
+// Same as `border_pattern` in quad.wgsl
+fn border_pattern(
+    quad: &Quad,
+    local: [f32; 2],
+    size: [f32; 2],
+    radii: [f32; 4],
+    scale: f32,
+) -> f32 {
+    // The pattern runs along the middle of the border
+    let widths = quad.border_width;
+    let [top, right, bottom, left] = widths;
+    let center_top_left = [left / 2.0, top / 2.0];
+    let center_bottom_right = [
+        (size[0] - right / 2.0).max(center_top_left[0]),
+        (size[1] - bottom / 2.0).max(center_top_left[1]),
+    ];
+    let corner_widths = [
+        left.max(top),
+        top.max(right),
+        right.max(bottom),
+        bottom.max(left),
+    ];
+    let center_radii: [f32; 4] =
+        std::array::from_fn(|i| (radii[i] - corner_widths[i] / 2.0).max(0.0));
+    let half_size = [
+        (center_bottom_right[0] - center_top_left[0]) / 2.0,
+        (center_bottom_right[1] - center_top_left[1]) / 2.0,
+    ];
+    let p = [
+        local[0] - center_top_left[0] - half_size[0],
+        local[1] - center_top_left[1] - half_size[1],
+    ];
+    let [position, total, width] = perimeter_position(p, half_size, center_radii, widths);
+
+    // Dots are as wide as the border
+    let dash = if quad.border_style == 2 {
+        widths.iter().copied().fold(0.0, f32::max)
+    } else {
+        quad.border_dash[0]
+    };
+    let period = dash + quad.border_dash[1];
+    if period <= 0.0 || total <= 0.0 {
+        return 1.0;
+    }
+
+    // Scale the pattern so it repeats a whole number of times around the border,
+    // with a dash or dot centered on the top left corner.
+    // Dashes scale along with their gaps, dots keep their size
+    let count = (total / period).round().max(1.0);
+    let spacing = total / count;
+    let along = (position - spacing * (position / spacing).round()).abs();
+
+    // Anti-aliased over about one pixel
+    let dist = if quad.border_style == 2 {
+        let across = box_dist(p, half_size, center_radii);
+        (along * along + across * across).sqrt() - width / 2.0
+    } else {
+        along - dash * spacing / period / 2.0
+    };
+    (0.5 - dist * scale).clamp(0.0, 1.0)
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
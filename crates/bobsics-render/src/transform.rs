@@ -0,0 +1,176 @@
+use bytemuck::{Pod, Zeroable};
+
+/// 2D affine transform in screen space (y points down), mapping `p` to
+/// `x_axis * p.x + y_axis * p.y + translation`.
+///
+/// Rotations are in degrees and clockwise on screen, as y points down,
+/// like the other angles of bobsics such as those of `PathBuilder::arc`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    pub x_axis: [f32; 2],
+    pub y_axis: [f32; 2],
+    pub translation: [f32; 2],
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        x_axis: [1.0, 0.0],
+        y_axis: [0.0, 1.0],
+        translation: [0.0, 0.0],
+    };
+
+    pub const fn translation(x: f32, y: f32) -> Self {
+        Self {
+            x_axis: [1.0, 0.0],
+            y_axis: [0.0, 1.0],
+            translation: [x, y],
+        }
+    }
+
+    pub const fn scale(x: f32, y: f32) -> Self {
+        Self {
+            x_axis: [x, 0.0],
+            y_axis: [0.0, y],
+            translation: [0.0, 0.0],
+        }
+    }
+
+    pub fn rotation(angle: f32) -> Self {
+        let (sin, cos) = angle.to_radians().sin_cos();
+        Self {
+            x_axis: [cos, sin],
+            y_axis: [-sin, cos],
+            translation: [0.0, 0.0],
+        }
+    }
+
+    /// Applies `self` first, then `next`.
+    pub fn then(&self, next: &Transform) -> Self {
+        Self {
+            x_axis: next.transform_vector(self.x_axis),
+            y_axis: next.transform_vector(self.y_axis),
+            translation: next.transform_point(self.translation),
+        }
+    }
+
+    pub fn then_translate(&self, x: f32, y: f32) -> Self {
+        self.then(&Self::translation(x, y))
+    }
+
+    pub fn then_scale(&self, x: f32, y: f32) -> Self {
+        self.then(&Self::scale(x, y))
+    }
+
+    pub fn then_rotate(&self, angle: f32) -> Self {
+        self.then(&Self::rotation(angle))
+    }
+
+    /// Applies the transform relative to `origin` instead of `(0, 0)`,
+    /// e.g. to rotate or scale a widget around its center.
+    pub fn around(&self, origin: [f32; 2]) -> Self {
+        Self::translation(-origin[0], -origin[1])
+            .then(self)
+            .then_translate(origin[0], origin[1])
+    }
+
+    pub fn transform_point(&self, p: [f32; 2]) -> [f32; 2] {
+        let [x, y] = self.transform_vector(p);
+        [x + self.translation[0], y + self.translation[1]]
+    }
+
+    /// Transforms a direction or size, ignoring the translation.
+    pub fn transform_vector(&self, v: [f32; 2]) -> [f32; 2] {
+        [
+            self.x_axis[0] * v[0] + self.y_axis[0] * v[1],
+            self.x_axis[1] * v[0] + self.y_axis[1] * v[1],
+        ]
+    }
+
+    /// Axis aligned bounding box `(min, max)` of the transformed rect.
+    pub fn transform_rect(&self, min: [f32; 2], max: [f32; 2]) -> ([f32; 2], [f32; 2]) {
+        let corners = [
+            self.transform_point(min),
+            self.transform_point([max[0], min[1]]),
+            self.transform_point(max),
+            self.transform_point([min[0], max[1]]),
+        ];
+        corners.iter().fold(
+            ([f32::INFINITY; 2], [f32::NEG_INFINITY; 2]),
+            |(min, max), p| {
+                (
+                    [min[0].min(p[0]), min[1].min(p[1])],
+                    [max[0].max(p[0]), max[1].max(p[1])],
+                )
+            },
+        )
+    }
+
+    pub fn determinant(&self) -> f32 {
+        self.x_axis[0] * self.y_axis[1] - self.x_axis[1] * self.y_axis[0]
+    }
+
+    /// Average scale of the transform, used to scale stroke widths and anti-aliasing.
+    pub fn scale_factor(&self) -> f32 {
+        self.determinant().abs().sqrt()
+    }
+
+    /// Returns `None` if the transform collapses everything onto a line or point.
+    /// Useful to map the cursor into a transformed widget.
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.determinant();
+        if det.abs() <= f32::EPSILON {
+            return None;
+        }
+        let x_axis = [self.y_axis[1] / det, -self.x_axis[1] / det];
+        let y_axis = [-self.y_axis[0] / det, self.x_axis[0] / det];
+        let [tx, ty] = self.translation;
+        Some(Self {
+            x_axis,
+            y_axis,
+            translation: [
+                -(x_axis[0] * tx + y_axis[0] * ty),
+                -(x_axis[1] * tx + y_axis[1] * ty),
+            ],
+        })
+    }
+
+    pub fn is_identity(&self) -> bool {
+        *self == Self::IDENTITY
+    }
+
+    /// Column major matrix mapping transformed screen coordinates to NDC, like
+    /// `wgpu_glyph::orthographic_projection` does for untransformed ones.
+    pub fn to_projection(&self, width: u32, height: u32) -> [f32; 16] {
+        let (sx, sy) = (2.0 / width as f32, -2.0 / height as f32);
+        let [tx, ty] = self.translation;
+        #[rustfmt::skip]
+        let matrix = [
+            self.x_axis[0] * sx, self.x_axis[1] * sy, 0.0, 0.0,
+            self.y_axis[0] * sx, self.y_axis[1] * sy, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            tx * sx - 1.0, ty * sy + 1.0, 0.0, 1.0,
+        ];
+        matrix
+    }
+}
+
+impl From<Transform> for lyon::math::Transform {
+    fn from(t: Transform) -> Self {
+        lyon::math::Transform::new(
+            t.x_axis[0],
+            t.x_axis[1],
+            t.y_axis[0],
+            t.y_axis[1],
+            t.translation[0],
+            t.translation[1],
+        )
+    }
+}
@@ -1,83 +1,3 @@
-#[derive(Debug, Clone, Copy)]
-pub struct Color {
-    r: f32,
-    g: f32,
-    b: f32,
-    a: f32,
-}
-
-impl Color {
-    pub const TRANSPARENT: Self = Self::from_rgba(0.0, 0.0, 0.0, 0.0);
-    pub const BLACK: Self = Self::from_rgba(0.0, 0.0, 0.0, 1.0);
-    pub const WHITE: Self = Self::from_rgba(1.0, 1.0, 1.0, 1.0);
-    pub const RED: Self = Self::from_rgba(1.0, 0.0, 0.0, 1.0);
-
-    pub const fn from_rgba(r: f32, g: f32, b: f32, a: f32) -> Self {
-        Self { r, g, b, a }
-    }
-
-    pub fn from_hex(hex: u32) -> Self {
-        let linear = to_linear_rgb(hex);
-        Self {
-            r: linear[0],
-            g: linear[1],
-            b: linear[2],
-            a: 1.0,
-        }
-    }
-
-    pub fn with_alpha(&self, alpha: f32) -> Self {
-        Self {
-            r: self.r,
-            g: self.g,
-            b: self.b,
-            a: alpha,
-        }
-    }
-
-    pub fn r(&self) -> u8 {
-        (self.r * 255.0) as u8
-    }
-    pub fn g(&self) -> u8 {
-        (self.g * 255.0) as u8
-    }
-    pub fn b(&self) -> u8 {
-        (self.b * 255.0) as u8
-    }
-    pub fn a(&self) -> u8 {
-        (self.a * 255.0) as u8
-    }
-}
-
-impl From<Color> for [f32; 4] {
-    fn from(val: Color) -> Self {
-        [val.r, val.g, val.b, val.a]
-    }
-}
-
-fn to_linear_rgb(c: u32) -> [f32; 3] {
-    let f = |xu: u32| {
-        let x = (xu & 0xFF) as f32 / 255.0;
-        if x > 0.04045 {
-            ((x + 0.055) / 1.055).powf(2.4)
-        } else {
-            x / 12.92
-        }
-    };
-    [f(c >> 16), f(c >> 8), f(c)]
-}
-
-impl From<Color> for wgpu::Color {
-    fn from(val: Color) -> Self {
-        wgpu::Color {
-            r: val.r as f64,
-            g: val.g as f64,
-            b: val.b as f64,
-            a: val.a as f64,
-        }
-    }
-}
-
 pub fn ndc_to_framebuffer_space(ndc: [f32; 2], framebuffer_size: [u32; 2]) -> [u32; 2] {
     [
         ((ndc[0] + 1.0) / 2.0 * framebuffer_size[0] as f32) as u32,
@@ -90,3 +10,45 @@ pub fn framebuffer_space_to_ndc(coordinates: [u32; 2], framebuffer_size: [u32; 2
         -((coordinates[1] as f32 / framebuffer_size[1] as f32) * 2.0 - 1.0),
     ]
 }
+
+/// Axis aligned clipping rectangle in framebuffer pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ClipRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl ClipRect {
+    pub const fn new(x: f32, y: f32, width: f32, height: f32) -> Self {
+        Self {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    pub fn intersect(&self, other: &ClipRect) -> ClipRect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        ClipRect::new(x, y, (right - x).max(0.0), (bottom - y).max(0.0))
+    }
+
+    /// Converts the rectangle to a scissor rect `[x, y, width, height]` that fits inside the target.
+    /// Returns `None` if nothing of the target would be visible.
+    pub fn to_scissor(&self, target_width: u32, target_height: u32) -> Option<[u32; 4]> {
+        let x = self.x.max(0.0).floor() as u32;
+        let y = self.y.max(0.0).floor() as u32;
+        let right = ((self.x + self.width).ceil().max(0.0) as u32).min(target_width);
+        let bottom = ((self.y + self.height).ceil().max(0.0) as u32).min(target_height);
+        if x >= right || y >= bottom {
+            return None;
+        }
+        Some([x, y, right - x, bottom - y])
+    }
+}
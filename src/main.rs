@@ -7,7 +7,14 @@ use bobsics_gui::{
 };
 
 fn main() {
-    let mut gui = BobsicsGUIApp::new("Bobsics GUI");
+    let mut gui = match BobsicsGUIApp::new("Bobsics GUI") {
+        Ok(gui) => gui,
+        Err(error) => {
+            // Show something helpful instead of a panic on machines with broken drivers
+            eprintln!("Bobsics GUI could not start: {error}");
+            std::process::exit(1);
+        }
+    };
     gui.set_widget(
         VerticalStack::new()
             .with_padding(5.0.into())